@@ -0,0 +1,49 @@
+;; Example plugin for tcbee, calculates SND_NXT - SND_UNA of tcp_probe events
+;; Compile with `wat2wasm bytes_in_flight.wat` and load the resulting .wasm file
+(module
+  (import "tcbee" "series_len" (func $series_len (param i32) (result i32)))
+  (import "tcbee" "series_timestamp" (func $series_timestamp (param i32 i32) (result f64)))
+  (import "tcbee" "series_value" (func $series_value (param i32 i32) (result f64)))
+  (import "tcbee" "emit_series" (func $emit_series (param i32 i32 i32) (result i32)))
+  (import "tcbee" "emit_point" (func $emit_point (param i32 f64 f64) (result i32)))
+
+  (memory (export "memory") 1)
+  (data (i32.const 0) "Bytes in flight")
+  (data (i32.const 32) "Calculates SND_NXT - SND_UNA")
+  (data (i32.const 64) "SND_NXT,SND_UNA")
+  (data (i32.const 96) "BYTES_IN_FLIGHT")
+
+  ;; Strings are returned as (pointer << 32) | length
+  (func (export "tcbee_name") (result i64)
+    (i64.const 15))
+  (func (export "tcbee_description") (result i64)
+    (i64.or (i64.shl (i64.const 32) (i64.const 32)) (i64.const 28)))
+  (func (export "tcbee_required") (result i64)
+    (i64.or (i64.shl (i64.const 64) (i64.const 32)) (i64.const 15)))
+
+  (func (export "tcbee_run") (result i32)
+    (local $handle i32)
+    (local $len i32)
+    (local $i i32)
+    ;; Use the shorter of both series
+    (local.set $len (call $series_len (i32.const 0)))
+    (if (i32.lt_s (call $series_len (i32.const 1)) (local.get $len))
+      (then (local.set $len (call $series_len (i32.const 1)))))
+    ;; New INT series
+    (local.set $handle (call $emit_series (i32.const 96) (i32.const 15) (i32.const 0)))
+    (if (i32.lt_s (local.get $handle) (i32.const 0))
+      (then (return (i32.const 1))))
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_s (local.get $i) (local.get $len)))
+        (drop
+          (call $emit_point
+            (local.get $handle)
+            (call $series_timestamp (i32.const 0) (local.get $i))
+            (f64.sub
+              (call $series_value (i32.const 0) (local.get $i))
+              (call $series_value (i32.const 1) (local.get $i)))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (i32.const 0))
+)
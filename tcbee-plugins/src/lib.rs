@@ -0,0 +1,22 @@
+// Plugin interface shared by tcbee-process and tcbee-viz
+// Plugins consume named time series of a single flow and return new time series
+// They are either compiled in or loaded at runtime from WebAssembly modules
+pub mod pipeline;
+pub mod plugin_cc_state;
+pub mod plugin_ce_fraction;
+pub mod plugin_lifecycle;
+pub mod plugin_limiter;
+pub mod plugin_reordering;
+pub mod plugin_upper_window;
+pub mod plugin_zero_window;
+pub mod processor;
+pub mod processor_dummy;
+pub mod series_data;
+pub mod wasm;
+
+pub use pipeline::{
+    load_optional_series, load_required_series, run_processor_on_flow, store_series,
+};
+pub use processor::{PreProcessor, ProcessorImplementation};
+pub use series_data::{numeric_value, SeriesData};
+pub use wasm::{WasmPlugin, WasmProcessor};
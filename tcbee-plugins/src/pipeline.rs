@@ -0,0 +1,161 @@
+// contains helpers to run a processor directly against a database
+// used by tcbee-process to run plugins without the UI
+use ts_storage::{DataPoint, DataValue, Flow, TSDBInterface, TimeSeries};
+
+use crate::{processor::PreProcessor, series_data::SeriesData};
+
+/// Loads the series with the given names for a flow, in the order of names
+pub fn load_required_series(
+    db: &dyn TSDBInterface,
+    flow: &Flow,
+    names: &Vec<String>,
+) -> Result<Vec<SeriesData>, String> {
+    let available: Vec<TimeSeries> = db
+        .list_time_series(flow)
+        .map_err(|e| format!("Could not list time series: {}", e))?
+        .collect();
+
+    let mut collected: Vec<SeriesData> = Vec::with_capacity(names.len());
+
+    for name in names {
+        let series = available
+            .iter()
+            .find(|ts| &ts.name == name)
+            .ok_or(format!("Flow does not contain time series {}", name))?;
+
+        let mut data = SeriesData::new(name, series.ts_type.clone());
+        let points = db
+            .get_data_points(series)
+            .map_err(|e| format!("Could not read time series {}: {}", name, e))?;
+
+        for point in points {
+            data.push(point.timestamp, point.value);
+        }
+
+        collected.push(data);
+    }
+
+    Ok(collected)
+}
+
+/// Loads the series with the given names for a flow, in the order of names
+/// Series the flow does not contain are returned empty
+pub fn load_optional_series(
+    db: &dyn TSDBInterface,
+    flow: &Flow,
+    names: &Vec<String>,
+) -> Result<Vec<SeriesData>, String> {
+    let available: Vec<String> = db
+        .list_time_series(flow)
+        .map_err(|e| format!("Could not list time series: {}", e))?
+        .map(|ts| ts.name)
+        .collect();
+
+    let mut collected: Vec<SeriesData> = Vec::with_capacity(names.len());
+
+    for name in names {
+        if available.contains(name) {
+            collected.extend(load_required_series(db, flow, &vec![name.clone()])?);
+        } else {
+            // Type of a missing series is irrelevant, it contains no values
+            collected.push(SeriesData::new(name, DataValue::Boolean(false)));
+        }
+    }
+
+    Ok(collected)
+}
+
+/// Creates a new time series for the flow and writes all values of series to it
+/// Fails if the flow already contains a time series with the same name
+pub fn store_series(
+    db: &dyn TSDBInterface,
+    flow: &Flow,
+    series: &SeriesData,
+) -> Result<TimeSeries, String> {
+    let exists = db
+        .list_time_series(flow)
+        .map_err(|e| format!("Could not list time series: {}", e))?
+        .any(|ts| ts.name == series.name);
+
+    if exists {
+        return Err(format!("Time series {} already exists!", series.name));
+    }
+
+    let time_series = db
+        .create_time_series(flow, &series.name, series.data_val_type.clone())
+        .map_err(|e| format!("Could not create time series {}: {}", series.name, e))?;
+
+    let points: Vec<DataPoint> = series
+        .timestamps
+        .iter()
+        .zip(series.data.iter())
+        .map(|(timestamp, value)| DataPoint {
+            timestamp: *timestamp,
+            value: value.clone(),
+        })
+        .collect();
+
+    if !points.is_empty() {
+        db.insert_multiple_points(&time_series, &points)
+            .map_err(|e| format!("Could not write time series {}: {}", series.name, e))?;
+    }
+
+    Ok(time_series)
+}
+
+/// Runs a processor on a single flow and stores the results
+/// Returns the names of the created time series and flow attributes
+pub fn run_processor_on_flow(
+    db: &dyn TSDBInterface,
+    flow: &Flow,
+    processor: &dyn PreProcessor,
+) -> Result<Vec<String>, String> {
+    let mut input = load_required_series(db, flow, &processor.receive_required_timeseries())?;
+    input.extend(load_optional_series(
+        db,
+        flow,
+        &processor.receive_optional_timeseries(),
+    )?);
+
+    let output = processor.create_new_time_series(&input)?;
+
+    // Series and attributes of a run are stored together, a failure leaves no partial results
+    db.begin_transaction()
+        .map_err(|e| format!("Could not begin transaction: {}", e))?;
+    match store_results(db, flow, processor, &input, &output) {
+        Ok(created) => {
+            db.commit_transaction()
+                .map_err(|e| format!("Could not commit results: {}", e))?;
+            Ok(created)
+        }
+        Err(err) => {
+            if let Err(e) = db.rollback_transaction() {
+                return Err(format!("{}, rollback failed: {}", err, e));
+            }
+            Err(err)
+        }
+    }
+}
+
+fn store_results(
+    db: &dyn TSDBInterface,
+    flow: &Flow,
+    processor: &dyn PreProcessor,
+    input: &Vec<SeriesData>,
+    output: &Vec<SeriesData>,
+) -> Result<Vec<String>, String> {
+    let mut created: Vec<String> = Vec::with_capacity(output.len());
+    for series in output.iter() {
+        store_series(db, flow, series)?;
+        created.push(series.name.clone());
+    }
+
+    // Attributes are replaced if the processor runs again
+    for attribute in processor.create_flow_attributes(input, output)? {
+        db.set_flow_attribute(flow, &attribute)
+            .map_err(|e| format!("Could not store attribute {}: {}", attribute.name, e))?;
+        created.push(attribute.name);
+    }
+
+    Ok(created)
+}
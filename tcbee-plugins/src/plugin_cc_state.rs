@@ -0,0 +1,135 @@
+use ts_storage::DataValue;
+
+use crate::{
+    processor::PreProcessor,
+    series_data::{numeric_value, SeriesData},
+};
+
+// Infers the congestion control phase of the sender from the socket state of tcp_probe
+// and the retransmitted segments seen on egress
+pub struct CcState {
+    required_timeseries_as_string: Vec<String>,
+}
+
+impl Default for CcState {
+    fn default() -> Self {
+        CcState {
+            required_timeseries_as_string: Vec::from([
+                "SND_CWND".to_string(),
+                "SSTRESH".to_string(),
+                "SND_NXT".to_string(),
+                "SND_UNA".to_string(),
+                "SEQ_NUM_OUT".to_string(),
+            ]),
+        }
+    }
+}
+
+pub const STATE_SLOW_START: &str = "SLOW_START";
+pub const STATE_CONGESTION_AVOIDANCE: &str = "CONGESTION_AVOIDANCE";
+pub const STATE_FAST_RECOVERY: &str = "FAST_RECOVERY";
+pub const STATE_LOSS: &str = "LOSS";
+pub const STATE_APP_LIMITED: &str = "APP_LIMITED";
+
+// Sequence numbers wrap around, a is before b if the distance is less than half the space
+fn seq_before(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) < 0
+}
+
+fn u32_at(series: &SeriesData, timestamp: f64) -> Option<u32> {
+    numeric_value(series.value_at(timestamp)?).map(|value| value as u32)
+}
+
+impl PreProcessor for CcState {
+    fn receive_name(&self) -> String {
+        "Congestion Control State".to_string()
+    }
+
+    fn receive_description(&self) -> String {
+        "
+        Infers the congestion control phase of the sender as CC_STATE, one point per change:
+        LOSS after a retransmission timeout (SND_CWND falls to 1),
+        FAST_RECOVERY after a retransmission or a reduced SSTRESH,
+        both until SND_UNA passes the SND_NXT of their start,
+        APP_LIMITED if all sent data is acknowledged,
+        SLOW_START below and CONGESTION_AVOIDANCE above SSTRESH otherwise.
+        "
+        .to_string()
+    }
+
+    fn create_new_time_series(&self, series: &Vec<SeriesData>) -> Result<Vec<SeriesData>, String> {
+        // Get required series from data vector
+        let cwnd = series.first().ok_or("No SND_CWND series!")?;
+        let ssthresh = series.get(1).ok_or("No SSTRESH series!")?;
+        let snd_nxt = series.get(2).ok_or("No SND_NXT series!")?;
+        let snd_una = series.get(3).ok_or("No SND_UNA series!")?;
+        let seq_out = series.get(4).ok_or("No SEQ_NUM_OUT series!")?;
+
+        let mut states = SeriesData::new("CC_STATE", DataValue::String(String::new()));
+
+        // Highest sequence number sent so far, lower ones are retransmissions
+        let mut highest_seq: Option<u32> = None;
+        let mut seq_pos = 0;
+        let mut last_ssthresh: Option<u32> = None;
+        // Recovery ends once SND_UNA reaches this point
+        let mut recovery: Option<(&str, u32)> = None;
+        let mut last_state: Option<&str> = None;
+
+        for (timestamp, value) in cwnd.timestamps.iter().zip(cwnd.data.iter()) {
+            let Some(window) = numeric_value(value) else {
+                continue;
+            };
+            let (Some(threshold), Some(nxt), Some(una)) = (
+                u32_at(ssthresh, *timestamp),
+                u32_at(snd_nxt, *timestamp),
+                u32_at(snd_una, *timestamp),
+            ) else {
+                continue;
+            };
+
+            // Segments sent since the last sample
+            let mut retransmitted = false;
+            while seq_pos < seq_out.len() && seq_out.timestamps[seq_pos] <= *timestamp {
+                if let Some(seq) = numeric_value(&seq_out.data[seq_pos]).map(|seq| seq as u32) {
+                    match highest_seq {
+                        Some(highest) if seq_before(seq, highest) => retransmitted = true,
+                        _ => highest_seq = Some(seq),
+                    }
+                }
+                seq_pos += 1;
+            }
+            let ssthresh_reduced = last_ssthresh.is_some_and(|last| threshold < last);
+            last_ssthresh = Some(threshold);
+
+            if recovery.is_some_and(|(_, point)| !seq_before(una, point)) {
+                recovery = None;
+            }
+            if window <= 1.0 && (retransmitted || ssthresh_reduced || recovery.is_some()) {
+                // Timeout resets the window, also during fast recovery
+                if !recovery.is_some_and(|(state, _)| state == STATE_LOSS) {
+                    recovery = Some((STATE_LOSS, nxt));
+                }
+            } else if recovery.is_none() && (retransmitted || ssthresh_reduced) {
+                recovery = Some((STATE_FAST_RECOVERY, nxt));
+            }
+
+            let state = match recovery {
+                Some((state, _)) => state,
+                None if una == nxt => STATE_APP_LIMITED,
+                None if window < threshold as f64 => STATE_SLOW_START,
+                None => STATE_CONGESTION_AVOIDANCE,
+            };
+
+            if last_state != Some(state) {
+                states.push(*timestamp, DataValue::String(state.to_string()));
+                last_state = Some(state);
+            }
+        }
+
+        Ok(Vec::from([states]))
+    }
+
+    fn receive_required_timeseries(&self) -> Vec<String> {
+        self.required_timeseries_as_string.clone()
+    }
+}
@@ -0,0 +1,96 @@
+use ts_storage::DataValue;
+
+use crate::{
+    processor::PreProcessor,
+    series_data::{numeric_value, SeriesData},
+};
+
+// Fraction of ACKs that echo a CE mark per RTT and the cwnd change of the same RTT
+// Seen from the sender: the receiver echoes CE marked data with ECE on its ACKs.
+// With DCTCP and L4S every ECE stands for marked data, classic ECN repeats ECE until CWR.
+pub struct CeFraction {
+    required_timeseries_as_string: Vec<String>,
+}
+
+impl Default for CeFraction {
+    fn default() -> Self {
+        CeFraction {
+            required_timeseries_as_string: Vec::from([
+                "FLAG_ACK_IN".to_string(),
+                "FLAG_ECE_IN".to_string(),
+                "SRTT".to_string(),
+                "SND_CWND".to_string(),
+            ]),
+        }
+    }
+}
+
+// Number of points in [start, end)
+fn count_between(timestamps: &[f64], start: f64, end: f64) -> usize {
+    timestamps.partition_point(|ts| *ts < end) - timestamps.partition_point(|ts| *ts < start)
+}
+
+impl PreProcessor for CeFraction {
+    fn receive_name(&self) -> String {
+        "CE Mark Fraction per RTT".to_string()
+    }
+
+    fn receive_description(&self) -> String {
+        "
+        Calculates the fraction of received ACKs with ECE per RTT (SRTT) as CE_FRACTION
+        and the relative change of SND_CWND over the same RTT as CWND_CHANGE, e.g. -0.5 if cwnd was halved.
+        The reaction to the marks of one RTT shows in the change of the next RTT.
+        "
+        .to_string()
+    }
+
+    fn create_new_time_series(&self, series: &Vec<SeriesData>) -> Result<Vec<SeriesData>, String> {
+        // Get required series from data vector
+        let acks = series.first().ok_or("No FLAG_ACK_IN series!")?;
+        let ece = series.get(1).ok_or("No FLAG_ECE_IN series!")?;
+        let srtt = series.get(2).ok_or("No SRTT series!")?;
+        let cwnd = series.get(3).ok_or("No SND_CWND series!")?;
+
+        if srtt.is_empty() || cwnd.is_empty() {
+            return Err("No SRTT or SND_CWND values!".to_string());
+        }
+
+        let mut fraction = SeriesData::new("CE_FRACTION", DataValue::Float(0.0));
+        let mut change = SeriesData::new("CWND_CHANGE", DataValue::Float(0.0));
+
+        let mut pos = 0;
+        while pos < acks.timestamps.len() {
+            let start = acks.timestamps[pos];
+            // SRTT is in microseconds, timestamps in nanoseconds
+            let rtt = srtt
+                .value_at(start)
+                .and_then(numeric_value)
+                .unwrap_or(0.0)
+                .max(1.0)
+                * 1000.0;
+            let end = start + rtt;
+
+            let acked = count_between(&acks.timestamps[pos..], start, end);
+            let echoed = count_between(&ece.timestamps, start, end);
+            fraction.push(end, DataValue::Float(echoed as f64 / acked as f64));
+
+            if let (Some(before), Some(after)) = (
+                cwnd.value_at(start).and_then(numeric_value),
+                cwnd.value_at(end).and_then(numeric_value),
+            ) {
+                if before > 0.0 {
+                    change.push(end, DataValue::Float(after / before - 1.0));
+                }
+            }
+
+            // Next RTT starts with the next ACK, skips RTTs without ACKs
+            pos += acked;
+        }
+
+        Ok(Vec::from([fraction, change]))
+    }
+
+    fn receive_required_timeseries(&self) -> Vec<String> {
+        self.required_timeseries_as_string.clone()
+    }
+}
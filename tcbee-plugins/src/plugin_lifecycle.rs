@@ -0,0 +1,347 @@
+use ts_storage::{DataValue, FlowAttribute};
+
+use crate::{
+    processor::PreProcessor,
+    series_data::{numeric_value, SeriesData},
+};
+
+// Interprets SYN, FIN and RST of both directions of a merged flow
+// The side that sends the first SYN without ACK is the client.
+// Flags are only stored when set, so all series are optional.
+pub struct Lifecycle {
+    optional_timeseries_as_string: Vec<String>,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Lifecycle {
+            optional_timeseries_as_string: Vec::from([
+                "FLAG_SYN_OUT".to_string(),
+                "FLAG_SYN_IN".to_string(),
+                "FLAG_ACK_OUT".to_string(),
+                "FLAG_ACK_IN".to_string(),
+                "FLAG_FIN_OUT".to_string(),
+                "FLAG_FIN_IN".to_string(),
+                "FLAG_RST_OUT".to_string(),
+                "FLAG_RST_IN".to_string(),
+                "PAYLOAD_LEN_OUT".to_string(),
+                "PAYLOAD_LEN_IN".to_string(),
+                "RETRANSMIT_SYNACK".to_string(),
+            ]),
+        }
+    }
+}
+
+pub const EVENT_SYN: &str = "SYN";
+pub const EVENT_SYN_RETRANSMIT: &str = "SYN_RETRANSMIT";
+pub const EVENT_SYN_ACK: &str = "SYN_ACK";
+pub const EVENT_SYN_ACK_RETRANSMIT: &str = "SYN_ACK_RETRANSMIT";
+pub const EVENT_ESTABLISHED: &str = "ESTABLISHED";
+pub const EVENT_FIRST_BYTE: &str = "FIRST_BYTE";
+
+// Packets of one direction
+struct Side<'a> {
+    // "OUT" or "IN", suffix of the series and of the teardown events
+    name: &'static str,
+    syn: &'a SeriesData,
+    ack: &'a SeriesData,
+    fin: &'a SeriesData,
+    rst: &'a SeriesData,
+    payload: &'a SeriesData,
+}
+
+impl Side<'_> {
+    fn has_ack(&self, timestamp: f64) -> bool {
+        self.ack
+            .timestamps
+            .binary_search_by(|ts| ts.total_cmp(&timestamp))
+            .is_ok()
+    }
+
+    fn has_syn(&self, timestamp: f64) -> bool {
+        self.syn
+            .timestamps
+            .binary_search_by(|ts| ts.total_cmp(&timestamp))
+            .is_ok()
+    }
+
+    /// SYN without ACK opens, SYN with ACK answers
+    fn syns(&self, with_ack: bool) -> Vec<f64> {
+        self.syn
+            .timestamps
+            .iter()
+            .filter(|ts| self.has_ack(**ts) == with_ack)
+            .cloned()
+            .collect()
+    }
+
+    /// First ACK that is not part of the handshake after timestamp
+    fn first_ack_after(&self, timestamp: f64) -> Option<f64> {
+        self.ack
+            .timestamps
+            .iter()
+            .find(|ts| **ts > timestamp && !self.has_syn(**ts))
+            .cloned()
+    }
+
+    fn first_data_after(&self, timestamp: f64) -> Option<f64> {
+        self.payload
+            .timestamps
+            .iter()
+            .zip(self.payload.data.iter())
+            .find(|(ts, len)| **ts >= timestamp && numeric_value(len).unwrap_or(0.0) > 0.0)
+            .map(|(ts, _)| *ts)
+    }
+}
+
+// Timestamps of the handshake, the client sent the first SYN
+struct Handshake<'a, 'b> {
+    client: &'b Side<'a>,
+    server: &'b Side<'a>,
+    syns: Vec<f64>,
+    syn_acks: Vec<f64>,
+    // Retransmitted SYN-ACKs reported by the kernel of the server
+    synack_events: &'a SeriesData,
+}
+
+impl Handshake<'_, '_> {
+    fn syn(&self) -> f64 {
+        self.syns[0]
+    }
+
+    fn syn_ack(&self) -> Option<f64> {
+        self.syn_acks.first().cloned()
+    }
+
+    fn established(&self) -> Option<f64> {
+        self.client.first_ack_after(self.syn_ack()?)
+    }
+
+    /// Retransmitted SYN-ACKs, the packets are missing if only the client side was recorded
+    fn syn_ack_retransmits(&self) -> Vec<f64> {
+        let packets = self.syn_acks.iter().skip(1).cloned().collect::<Vec<f64>>();
+        if packets.len() >= self.synack_events.len() {
+            packets
+        } else {
+            self.synack_events.timestamps.clone()
+        }
+    }
+
+    /// Time from the last handshake segment of the local host to the answer of the peer
+    fn rtt(&self) -> Option<f64> {
+        let answer = |sent: &Vec<f64>, received: f64| {
+            sent.iter()
+                .rev()
+                .find(|ts| **ts <= received)
+                .map(|ts| received - ts)
+        };
+        if self.client.name == "OUT" {
+            answer(&self.syns, self.syn_ack()?)
+        } else {
+            answer(&self.syn_acks, self.established()?)
+        }
+    }
+}
+
+impl PreProcessor for Lifecycle {
+    fn receive_name(&self) -> String {
+        "Connection Lifecycle".to_string()
+    }
+
+    fn receive_description(&self) -> String {
+        "
+        Interprets the handshake and teardown of a merged flow as LIFECYCLE events:
+        SYN, SYN_RETRANSMIT, SYN_ACK, SYN_ACK_RETRANSMIT, ESTABLISHED, FIRST_BYTE of the server,
+        FIN_OUT, FIN_IN, RST_OUT and RST_IN.
+        Timings, retransmissions and the teardown type are stored in the flow attributes.
+        "
+        .to_string()
+    }
+
+    fn create_new_time_series(&self, series: &Vec<SeriesData>) -> Result<Vec<SeriesData>, String> {
+        let (outgoing, incoming, synack_events) = sides(series)?;
+
+        let mut events: Vec<(f64, String)> = Vec::new();
+
+        if let Some(handshake) = handshake(&outgoing, &incoming, synack_events) {
+            events.push((handshake.syn(), EVENT_SYN.to_string()));
+            for ts in handshake.syns.iter().skip(1) {
+                events.push((*ts, EVENT_SYN_RETRANSMIT.to_string()));
+            }
+            if let Some(ts) = handshake.syn_ack() {
+                events.push((ts, EVENT_SYN_ACK.to_string()));
+            }
+            for ts in handshake.syn_ack_retransmits() {
+                events.push((ts, EVENT_SYN_ACK_RETRANSMIT.to_string()));
+            }
+            if let Some(ts) = handshake.established() {
+                events.push((ts, EVENT_ESTABLISHED.to_string()));
+            }
+            if let Some(ts) = handshake.server.first_data_after(handshake.syn()) {
+                events.push((ts, EVENT_FIRST_BYTE.to_string()));
+            }
+        }
+
+        for (time, event) in teardown(&outgoing, &incoming) {
+            events.push((time, event));
+        }
+
+        events.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut lifecycle = SeriesData::new("LIFECYCLE", DataValue::String(String::new()));
+        for (timestamp, event) in events {
+            lifecycle.push(timestamp, DataValue::String(event));
+        }
+
+        Ok(Vec::from([lifecycle]))
+    }
+
+    fn create_flow_attributes(
+        &self,
+        series: &Vec<SeriesData>,
+        _created: &Vec<SeriesData>,
+    ) -> Result<Vec<FlowAttribute>, String> {
+        let (outgoing, incoming, synack_events) = sides(series)?;
+
+        let mut values: Vec<(&str, DataValue)> = Vec::new();
+
+        if let Some(handshake) = handshake(&outgoing, &incoming, synack_events) {
+            let role = if handshake.client.name == "OUT" {
+                "CLIENT"
+            } else {
+                "SERVER"
+            };
+            values.push(("HANDSHAKE_ROLE", DataValue::String(role.to_string())));
+            values.push((
+                "SYN_RETRANSMISSIONS",
+                DataValue::Int(handshake.syns.len() as i64 - 1),
+            ));
+            values.push((
+                "SYN_ACK_RETRANSMISSIONS",
+                DataValue::Int(handshake.syn_ack_retransmits().len() as i64),
+            ));
+
+            if let Some(syn_ack) = handshake.syn_ack() {
+                values.push((
+                    "SYN_TO_SYN_ACK",
+                    DataValue::Float(syn_ack - handshake.syn()),
+                ));
+                if let Some(established) = handshake.established() {
+                    values.push(("SYN_ACK_TO_ACK", DataValue::Float(established - syn_ack)));
+                }
+            }
+            if let Some(rtt) = handshake.rtt() {
+                values.push(("HANDSHAKE_RTT", DataValue::Float(rtt)));
+            }
+            if let Some(first_byte) = handshake.server.first_data_after(handshake.syn()) {
+                values.push((
+                    "TIME_TO_FIRST_BYTE",
+                    DataValue::Float(first_byte - handshake.syn()),
+                ));
+            }
+        }
+
+        let closing = teardown(&outgoing, &incoming);
+        let teardown_type = if closing.iter().any(|(_, event)| event.starts_with("RST")) {
+            "RST"
+        } else {
+            match closing.len() {
+                0 => "OPEN",
+                1 => "HALF_CLOSED",
+                _ => "FIN",
+            }
+        };
+        values.push(("TEARDOWN", DataValue::String(teardown_type.to_string())));
+
+        if let (Some((first, event)), Some((last, _))) = (closing.first(), closing.last()) {
+            let by = if event.ends_with("_OUT") {
+                "LOCAL"
+            } else {
+                "REMOTE"
+            };
+            values.push(("TEARDOWN_BY", DataValue::String(by.to_string())));
+            values.push(("TEARDOWN_TIME", DataValue::Float(*first)));
+            values.push(("TEARDOWN_DURATION", DataValue::Float(last - first)));
+        }
+
+        Ok(values
+            .into_iter()
+            .map(|(name, value)| FlowAttribute {
+                name: name.to_string(),
+                value,
+            })
+            .collect())
+    }
+
+    fn receive_required_timeseries(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn receive_optional_timeseries(&self) -> Vec<String> {
+        self.optional_timeseries_as_string.clone()
+    }
+}
+
+// Splits the series into both directions and the SYN-ACK retransmissions
+fn sides(series: &Vec<SeriesData>) -> Result<(Side<'_>, Side<'_>, &SeriesData), String> {
+    if series.len() < 11 {
+        return Err("Missing series!".to_string());
+    }
+    if series[8].is_empty() && series[9].is_empty() {
+        return Err("Flow contains no packets!".to_string());
+    }
+
+    let side = move |name: &'static str, offset: usize| Side {
+        name,
+        syn: &series[offset],
+        ack: &series[offset + 2],
+        fin: &series[offset + 4],
+        rst: &series[offset + 6],
+        payload: &series[offset + 8],
+    };
+    Ok((side("OUT", 0), side("IN", 1), &series[10]))
+}
+
+fn handshake<'a, 'b>(
+    outgoing: &'b Side<'a>,
+    incoming: &'b Side<'a>,
+    synack_events: &'a SeriesData,
+) -> Option<Handshake<'a, 'b>> {
+    let (out_syns, in_syns) = (outgoing.syns(false), incoming.syns(false));
+
+    // Client sent the first SYN
+    let (client, server, syns) = match (out_syns.first(), in_syns.first()) {
+        (Some(out), Some(inc)) if inc < out => (incoming, outgoing, in_syns),
+        (Some(_), _) => (outgoing, incoming, out_syns),
+        (None, Some(_)) => (incoming, outgoing, in_syns),
+        (None, None) => return None,
+    };
+    let syn_acks = server
+        .syns(true)
+        .into_iter()
+        .filter(|ts| *ts >= syns[0])
+        .collect();
+
+    Some(Handshake {
+        client,
+        server,
+        syns,
+        syn_acks,
+        synack_events,
+    })
+}
+
+// First FIN and RST of each direction, sorted by time
+fn teardown(outgoing: &Side, incoming: &Side) -> Vec<(f64, String)> {
+    let mut closing: Vec<(f64, String)> = Vec::new();
+    for side in [outgoing, incoming] {
+        if let Some(ts) = side.fin.timestamps.first() {
+            closing.push((*ts, format!("FIN_{}", side.name)));
+        }
+        if let Some(ts) = side.rst.timestamps.first() {
+            closing.push((*ts, format!("RST_{}", side.name)));
+        }
+    }
+    closing.sort_by(|a, b| a.0.total_cmp(&b.0));
+    closing
+}
@@ -0,0 +1,150 @@
+use ts_storage::{DataValue, FlowAttribute};
+
+use crate::{
+    processor::PreProcessor,
+    series_data::{numeric_value, SeriesData},
+};
+
+// Classifies what limited the sending rate at every tcp_probe sample
+// Compares the bytes in flight with the congestion window, the window advertised by the peer
+// and the bytes the pacing rate allows per RTT.
+// pacing_rate and advmss are read from the sock trace of the sending socket (send_sock.tcp).
+pub struct Limiter {
+    required_timeseries_as_string: Vec<String>,
+}
+
+impl Default for Limiter {
+    fn default() -> Self {
+        Limiter {
+            required_timeseries_as_string: Vec::from([
+                "SND_NXT".to_string(),
+                "SND_UNA".to_string(),
+                "SND_CWND".to_string(),
+                "SND_WND".to_string(),
+                "SRTT".to_string(),
+                "pacing_rate_OUT".to_string(),
+                "advmss_OUT".to_string(),
+            ]),
+        }
+    }
+}
+
+pub const LIMITED_CWND: &str = "CWND_LIMITED";
+pub const LIMITED_RWND: &str = "RWND_LIMITED";
+pub const LIMITED_PACING: &str = "PACING_LIMITED";
+pub const LIMITED_APP: &str = "APP_LIMITED";
+
+const LIMITERS: [&str; 4] = [LIMITED_CWND, LIMITED_RWND, LIMITED_PACING, LIMITED_APP];
+
+fn value_at(series: &SeriesData, timestamp: f64) -> Option<f64> {
+    numeric_value(series.value_at(timestamp)?)
+}
+
+impl PreProcessor for Limiter {
+    fn receive_name(&self) -> String {
+        "Throughput Limiter".to_string()
+    }
+
+    fn receive_description(&self) -> String {
+        "
+        Classifies what limited the sender as LIMITER, one point per change.
+        A limit is reached if less than one segment (advmss_OUT) fits in:
+        CWND_LIMITED by SND_CWND segments, RWND_LIMITED by SND_WND advertised by the peer,
+        PACING_LIMITED by pacing_rate_OUT times SRTT, APP_LIMITED if no limit is reached.
+        The time share of each limiter is stored in the flow attributes, e.g. CWND_LIMITED_SHARE.
+        "
+        .to_string()
+    }
+
+    fn create_new_time_series(&self, series: &Vec<SeriesData>) -> Result<Vec<SeriesData>, String> {
+        // Get required series from data vector
+        let snd_nxt = series.first().ok_or("No SND_NXT series!")?;
+        let snd_una = series.get(1).ok_or("No SND_UNA series!")?;
+        let snd_cwnd = series.get(2).ok_or("No SND_CWND series!")?;
+        let snd_wnd = series.get(3).ok_or("No SND_WND series!")?;
+        let srtt = series.get(4).ok_or("No SRTT series!")?;
+        let pacing_rate = series.get(5).ok_or("No pacing_rate_OUT series!")?;
+        let advmss = series.get(6).ok_or("No advmss_OUT series!")?;
+
+        let mut limiter = SeriesData::new("LIMITER", DataValue::String(String::new()));
+        let mut last: Option<&str> = None;
+
+        for timestamp in snd_cwnd.timestamps.iter() {
+            let (Some(nxt), Some(una), Some(cwnd), Some(window), Some(mss)) = (
+                value_at(snd_nxt, *timestamp),
+                value_at(snd_una, *timestamp),
+                value_at(snd_cwnd, *timestamp),
+                value_at(snd_wnd, *timestamp),
+                value_at(advmss, *timestamp),
+            ) else {
+                continue;
+            };
+
+            // Sequence numbers wrap around
+            let in_flight = (nxt as u32).wrapping_sub(una as u32) as f64;
+            let cwnd_bytes = cwnd * mss;
+            // Pacing rate is in bytes per second, SRTT in microseconds
+            let paced_bytes = match (
+                value_at(pacing_rate, *timestamp),
+                value_at(srtt, *timestamp),
+            ) {
+                (Some(rate), Some(rtt)) if rate > 0.0 && rtt > 0.0 => rate * rtt / 1e6,
+                _ => f64::MAX,
+            };
+
+            let window_limit = cwnd_bytes.min(window);
+            let state = if in_flight + mss > window_limit {
+                if window <= cwnd_bytes {
+                    LIMITED_RWND
+                } else {
+                    LIMITED_CWND
+                }
+            } else if paced_bytes < window_limit && in_flight + mss > paced_bytes {
+                LIMITED_PACING
+            } else {
+                LIMITED_APP
+            };
+
+            if last != Some(state) {
+                limiter.push(*timestamp, DataValue::String(state.to_string()));
+                last = Some(state);
+            }
+        }
+
+        Ok(Vec::from([limiter]))
+    }
+
+    fn create_flow_attributes(
+        &self,
+        series: &Vec<SeriesData>,
+        created: &Vec<SeriesData>,
+    ) -> Result<Vec<FlowAttribute>, String> {
+        let limiter = created.first().ok_or("No LIMITER series!")?;
+        let (Some(start), Some(end)) = (
+            limiter.timestamps.first(),
+            series.get(2).and_then(|cwnd| cwnd.timestamps.last()),
+        ) else {
+            return Ok(Vec::new());
+        };
+
+        // The last state holds until the last sample
+        let durations = limiter.state_durations(*end);
+        let total = end - start;
+
+        Ok(LIMITERS
+            .iter()
+            .map(|state| FlowAttribute {
+                name: format!("{}_SHARE", state),
+                value: DataValue::Float(if total > 0.0 {
+                    durations.get(*state).cloned().unwrap_or(0.0) / total
+                } else {
+                    0.0
+                }),
+            })
+            .collect())
+    }
+
+    fn receive_required_timeseries(&self) -> Vec<String> {
+        self.required_timeseries_as_string.clone()
+    }
+}
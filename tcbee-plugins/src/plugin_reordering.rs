@@ -0,0 +1,221 @@
+use std::collections::{BTreeSet, HashSet};
+
+use ts_storage::{DataValue, FlowAttribute};
+
+use crate::{
+    processor::PreProcessor,
+    series_data::{numeric_value, SeriesData},
+};
+
+// Detects out-of-order and duplicate data segments received by the local host
+// A segment is out of order if data after it was received before it.
+// The extent is measured like RFC 4737, in bytes and in packets of later data received before it.
+pub struct Reordering {
+    required_timeseries_as_string: Vec<String>,
+    optional_timeseries_as_string: Vec<String>,
+}
+
+impl Default for Reordering {
+    fn default() -> Self {
+        Reordering {
+            required_timeseries_as_string: Vec::from([
+                "SEQ_NUM_IN".to_string(),
+                "PAYLOAD_LEN_IN".to_string(),
+            ]),
+            // Both sock traces sample the same socket
+            optional_timeseries_as_string: Vec::from([
+                "reordering_OUT".to_string(),
+                "reordering_IN".to_string(),
+            ]),
+        }
+    }
+}
+
+// Maps 32 bit sequence numbers to a continuous 64 bit space
+#[derive(Default)]
+struct SeqUnwrapper {
+    last: Option<(u32, i64)>,
+}
+
+impl SeqUnwrapper {
+    fn unwrap(&mut self, seq: u32) -> i64 {
+        let unwrapped = match self.last {
+            // Distance is less than half the sequence space in either direction
+            Some((raw, unwrapped)) => unwrapped + seq.wrapping_sub(raw) as i32 as i64,
+            None => seq as i64,
+        };
+        self.last = Some((seq, unwrapped));
+        unwrapped
+    }
+}
+
+// Result of the analysis, attributes are derived from the series
+struct Analysis {
+    extent_bytes: SeriesData,
+    extent_packets: SeriesData,
+    duplicates: SeriesData,
+}
+
+fn analyze(seq_num: &SeriesData, payload_len: &SeriesData) -> Analysis {
+    let mut analysis = Analysis {
+        extent_bytes: SeriesData::new("REORDER_BYTES_IN", DataValue::Int(0)),
+        extent_packets: SeriesData::new("REORDER_PACKETS_IN", DataValue::Int(0)),
+        duplicates: SeriesData::new("DUPLICATE_IN", DataValue::Boolean(false)),
+    };
+
+    let mut unwrapper = SeqUnwrapper::default();
+    let mut highest_end: Option<i64> = None;
+    // Start of every segment received so far, counts the packets a late segment was overtaken by
+    let mut received: BTreeSet<i64> = BTreeSet::new();
+    let mut seen: HashSet<(i64, i64)> = HashSet::new();
+
+    for (timestamp, value) in payload_len.timestamps.iter().zip(payload_len.data.iter()) {
+        let len = numeric_value(value).unwrap_or(0.0) as i64;
+        // Pure ACKs do not carry data that can be reordered
+        if len <= 0 {
+            continue;
+        }
+        // SEQ_NUM of the same packet, missing if it was 0
+        let Ok(pos) = seq_num
+            .timestamps
+            .binary_search_by(|ts| ts.total_cmp(timestamp))
+        else {
+            continue;
+        };
+        let Some(seq) = numeric_value(&seq_num.data[pos]) else {
+            continue;
+        };
+        let start = unwrapper.unwrap(seq as u32);
+        let end = start + len;
+
+        if !seen.insert((start, len)) {
+            analysis
+                .duplicates
+                .push(*timestamp, DataValue::Boolean(true));
+            continue;
+        }
+
+        if let Some(highest) = highest_end.filter(|highest| end <= *highest) {
+            let overtaken = received.range(start + 1..).count();
+            analysis
+                .extent_bytes
+                .push(*timestamp, DataValue::Int(highest - end));
+            analysis
+                .extent_packets
+                .push(*timestamp, DataValue::Int(overtaken as i64));
+        }
+
+        received.insert(start);
+        highest_end = Some(highest_end.map_or(end, |highest| highest.max(end)));
+    }
+
+    analysis
+}
+
+fn max_int(series: &SeriesData) -> i64 {
+    series
+        .data
+        .iter()
+        .filter_map(numeric_value)
+        .fold(0.0, f64::max) as i64
+}
+
+impl PreProcessor for Reordering {
+    fn receive_name(&self) -> String {
+        "Reordering".to_string()
+    }
+
+    fn receive_description(&self) -> String {
+        "
+        Detects out-of-order and duplicate data segments received by the local host.
+        REORDER_BYTES_IN and REORDER_PACKETS_IN hold the extent of every out-of-order segment:
+        bytes and packets of later data that were received before it.
+        DUPLICATE_IN marks segments that were received before.
+        Counts, maxima and the reordering estimate of the kernel (sock trace) are stored in the flow attributes.
+        "
+        .to_string()
+    }
+
+    fn create_new_time_series(&self, series: &Vec<SeriesData>) -> Result<Vec<SeriesData>, String> {
+        // Get required series from data vector
+        let seq_num = series.first().ok_or("No SEQ_NUM_IN series!")?;
+        let payload_len = series.get(1).ok_or("No PAYLOAD_LEN_IN series!")?;
+
+        let analysis = analyze(seq_num, payload_len);
+
+        Ok(Vec::from([
+            analysis.extent_bytes,
+            analysis.extent_packets,
+            analysis.duplicates,
+        ]))
+    }
+
+    fn create_flow_attributes(
+        &self,
+        series: &Vec<SeriesData>,
+        created: &Vec<SeriesData>,
+    ) -> Result<Vec<FlowAttribute>, String> {
+        let payload_len = series.get(1).ok_or("No PAYLOAD_LEN_IN series!")?;
+        let extent_bytes = created.first().ok_or("No REORDER_BYTES_IN series!")?;
+        let extent_packets = created.get(1).ok_or("No REORDER_PACKETS_IN series!")?;
+        let duplicates = created.get(2).ok_or("No DUPLICATE_IN series!")?;
+
+        let data_segments = payload_len
+            .data
+            .iter()
+            .filter(|len| numeric_value(len).unwrap_or(0.0) > 0.0)
+            .count();
+        let reordered = extent_bytes.len() as i64;
+        let max_packets = max_int(extent_packets);
+
+        let mut values: Vec<(&str, DataValue)> = Vec::from([
+            ("REORDERED_SEGMENTS_IN", DataValue::Int(reordered)),
+            (
+                "DUPLICATE_SEGMENTS_IN",
+                DataValue::Int(duplicates.len() as i64),
+            ),
+            (
+                "REORDER_RATIO_IN",
+                DataValue::Float(if data_segments > 0 {
+                    reordered as f64 / data_segments as f64
+                } else {
+                    0.0
+                }),
+            ),
+            (
+                "MAX_REORDER_BYTES_IN",
+                DataValue::Int(max_int(extent_bytes)),
+            ),
+            ("MAX_REORDER_PACKETS_IN", DataValue::Int(max_packets)),
+        ]);
+
+        // Reordering degree the kernel assumed in packets, estimated from the ACKs of the peer
+        // It describes the data sent by the local host, which the peer receives,
+        // so it is not compared with the analysis of the received data
+        let kernel: Vec<&SeriesData> = series
+            .iter()
+            .skip(2)
+            .filter(|kernel| !kernel.is_empty())
+            .collect();
+        if !kernel.is_empty() {
+            let kernel_max = kernel.iter().map(|kernel| max_int(kernel)).max().unwrap_or(0);
+            values.push(("KERNEL_REORDERING", DataValue::Int(kernel_max)));
+        }
+
+        Ok(values
+            .into_iter()
+            .map(|(name, value)| FlowAttribute {
+                name: name.to_string(),
+                value,
+            })
+            .collect())
+    }
+
+    fn receive_required_timeseries(&self) -> Vec<String> {
+        self.required_timeseries_as_string.clone()
+    }
+
+    fn receive_optional_timeseries(&self) -> Vec<String> {
+        self.optional_timeseries_as_string.clone()
+    }
+}
@@ -0,0 +1,71 @@
+use ts_storage::DataValue;
+
+use crate::{processor::PreProcessor, series_data::SeriesData};
+
+// Calculate upper windows based on SND_UNA + SND_WND
+pub struct UpperWindow {
+    required_timeseries_as_string: Vec<String>,
+}
+
+impl Default for UpperWindow {
+    fn default() -> Self {
+        UpperWindow {
+            required_timeseries_as_string: Vec::from([
+                "SND_UNA".to_string(),
+                "SND_WND".to_string(),
+            ]),
+        }
+    }
+}
+
+impl PreProcessor for UpperWindow {
+    fn receive_name(&self) -> String {
+        "Upper TCP Window Number".to_string()
+    }
+
+    fn receive_description(&self) -> String {
+        "
+        Calculates the upper bound of the sliding TCP window based on SND_UNA + SND_WND
+        "
+        .to_string()
+    }
+
+    fn create_new_time_series(&self, series: &Vec<SeriesData>) -> Result<Vec<SeriesData>, String> {
+        // Get required series from data vector
+        let snd_una = series.first().ok_or("No SND_UNA series!")?;
+        let snd_wnd = series.get(1).ok_or("No SND_WND series!")?;
+
+        if !snd_una.data_val_type.type_equal(&snd_wnd.data_val_type) {
+            return Err("Mismatch in TS types!".to_string());
+        }
+
+        let mut upper_window = SeriesData::new("UPPER_WND", snd_una.data_val_type.clone());
+
+        for ((una, wnd), timestamp) in snd_una
+            .data
+            .iter()
+            .zip(snd_wnd.data.iter())
+            .zip(snd_una.timestamps.iter())
+        {
+            let new_val = match (una, wnd) {
+                (DataValue::Int(una_val), DataValue::Int(wnd_val)) => {
+                    DataValue::Int(una_val + wnd_val)
+                }
+                (DataValue::Float(una_val), DataValue::Float(wnd_val)) => {
+                    DataValue::Float(una_val + wnd_val)
+                }
+                _ => {
+                    return Err("Series invalid type: Are not INT or FLOAT!".to_string());
+                }
+            };
+
+            upper_window.push(*timestamp, new_val);
+        }
+
+        Ok(Vec::from([upper_window]))
+    }
+
+    fn receive_required_timeseries(&self) -> Vec<String> {
+        self.required_timeseries_as_string.clone()
+    }
+}
@@ -0,0 +1,294 @@
+use ts_storage::{DataValue, FlowAttribute};
+
+use crate::{
+    processor::PreProcessor,
+    series_data::{numeric_value, SeriesData},
+};
+
+// Detects periods in which a receiver advertised a zero window
+// _IN is the window of the peer that stalls the local sender,
+// _OUT the window of the local receiver, together with RCV_WND of tcp_probe.
+// Packets of an import without WINDOW are skipped.
+pub struct ZeroWindow {
+    optional_timeseries_as_string: Vec<String>,
+}
+
+impl Default for ZeroWindow {
+    fn default() -> Self {
+        ZeroWindow {
+            optional_timeseries_as_string: Vec::from([
+                "WINDOW_SCALED_IN".to_string(),
+                "WINDOW_IN".to_string(),
+                "PAYLOAD_LEN_IN".to_string(),
+                "FLAG_RST_IN".to_string(),
+                "SEQ_NUM_IN".to_string(),
+                "ACK_NUM_IN".to_string(),
+                "WINDOW_SCALED_OUT".to_string(),
+                "WINDOW_OUT".to_string(),
+                "PAYLOAD_LEN_OUT".to_string(),
+                "FLAG_RST_OUT".to_string(),
+                "SEQ_NUM_OUT".to_string(),
+                "ACK_NUM_OUT".to_string(),
+                "RCV_WND".to_string(),
+                "probes_OUT".to_string(),
+                "probes_IN".to_string(),
+            ]),
+        }
+    }
+}
+
+// Packets of one direction
+struct Side<'a> {
+    suffix: &'static str,
+    scaled: &'a SeriesData,
+    window: &'a SeriesData,
+    // Every packet has a PAYLOAD_LEN
+    packets: &'a SeriesData,
+    rst: &'a SeriesData,
+    // Missing for packets with a value of 0
+    seq: &'a SeriesData,
+    ack: &'a SeriesData,
+}
+
+// Zero window period, the window update is missing if the period lasts until the end
+struct Stall {
+    start: f64,
+    end: f64,
+    update: Option<f64>,
+    probes: Vec<f64>,
+}
+
+fn exact(series: &SeriesData, timestamp: f64) -> Option<f64> {
+    let pos = series
+        .timestamps
+        .binary_search_by(|ts| ts.total_cmp(&timestamp))
+        .ok()?;
+    numeric_value(&series.data[pos])
+}
+
+impl Side<'_> {
+    /// advertised window per packet, resets are ignored as they carry no window
+    fn windows(&self) -> Vec<(f64, f64)> {
+        self.packets
+            .timestamps
+            .iter()
+            .filter(|ts| exact(self.rst, **ts).is_none())
+            .filter_map(|ts| {
+                let window = exact(self.scaled, *ts).or(exact(self.window, *ts))?;
+                Some((*ts, window))
+            })
+            .collect()
+    }
+
+    /// last acknowledged sequence number at the given time, snd_una of the other side
+    fn acked_at(&self, timestamp: f64) -> Option<u32> {
+        let pos = self.ack.timestamps.partition_point(|ts| *ts <= timestamp);
+        let value = numeric_value(&self.ack.data[pos.checked_sub(1)?])?;
+        Some(value as u32)
+    }
+
+    /// window probe sent to a receiver with a zero window
+    /// Linux probes with an empty segment before snd_una that forces an ACK,
+    /// other stacks send one byte of new data at snd_una. Other packets are pure ACKs or data.
+    fn is_probe(&self, timestamp: f64, receiver: &Side) -> bool {
+        let Some(snd_una) = receiver.acked_at(timestamp) else {
+            return false;
+        };
+        let seq = exact(self.seq, timestamp).unwrap_or(0.0) as u32;
+        match exact(self.packets, timestamp).map(|len| len as i64) {
+            Some(0) => seq == snd_una.wrapping_sub(1),
+            Some(1) => seq == snd_una,
+            _ => false,
+        }
+    }
+}
+
+// Finds zero window periods in windows sorted by time
+// Window probes of the sender are counted per period
+fn stalls(windows: &[(f64, f64)], sender: &Side, receiver: &Side) -> Vec<Stall> {
+    let mut stalls: Vec<Stall> = Vec::new();
+    let mut start: Option<f64> = None;
+
+    for (timestamp, window) in windows.iter() {
+        match start {
+            None if *window <= 0.0 => start = Some(*timestamp),
+            Some(begin) if *window > 0.0 => {
+                stalls.push(Stall {
+                    start: begin,
+                    end: *timestamp,
+                    update: Some(*window),
+                    probes: Vec::new(),
+                });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let (Some(begin), Some((last, _))) = (start, windows.last()) {
+        stalls.push(Stall {
+            start: begin,
+            end: *last,
+            update: None,
+            probes: Vec::new(),
+        });
+    }
+
+    for stall in stalls.iter_mut() {
+        stall.probes = sender
+            .packets
+            .timestamps
+            .iter()
+            .filter(|ts| **ts > stall.start && **ts < stall.end)
+            .filter(|ts| sender.is_probe(**ts, receiver))
+            .cloned()
+            .collect();
+    }
+    stalls
+}
+
+impl PreProcessor for ZeroWindow {
+    fn receive_name(&self) -> String {
+        "Zero Window".to_string()
+    }
+
+    fn receive_description(&self) -> String {
+        "
+        Detects periods with a zero receive window, _IN for the peer and _OUT for the local host (also RCV_WND).
+        ZERO_WINDOW holds the duration of every period at its start,
+        WINDOW_PROBE the window probes of the sender during a period with the time since its start,
+        WINDOW_UPDATE the packet that ended a period with its duration.
+        The stalled time per direction and in total is stored in the flow attributes.
+        "
+        .to_string()
+    }
+
+    fn create_new_time_series(&self, series: &Vec<SeriesData>) -> Result<Vec<SeriesData>, String> {
+        let mut created: Vec<SeriesData> = Vec::new();
+
+        for (suffix, stalls) in analyze(series)? {
+            let mut zero_window =
+                SeriesData::new(&format!("ZERO_WINDOW{}", suffix), DataValue::Float(0.0));
+            let mut probes =
+                SeriesData::new(&format!("WINDOW_PROBE{}", suffix), DataValue::Float(0.0));
+            let mut updates =
+                SeriesData::new(&format!("WINDOW_UPDATE{}", suffix), DataValue::Float(0.0));
+
+            for stall in stalls.iter() {
+                let duration = stall.end - stall.start;
+                zero_window.push(stall.start, DataValue::Float(duration));
+                for probe in stall.probes.iter() {
+                    probes.push(*probe, DataValue::Float(probe - stall.start));
+                }
+                if stall.update.is_some() {
+                    updates.push(stall.end, DataValue::Float(duration));
+                }
+            }
+
+            created.extend([zero_window, probes, updates]);
+        }
+
+        Ok(created)
+    }
+
+    fn create_flow_attributes(
+        &self,
+        series: &Vec<SeriesData>,
+        _created: &Vec<SeriesData>,
+    ) -> Result<Vec<FlowAttribute>, String> {
+        let mut values: Vec<(String, DataValue)> = Vec::new();
+        let mut total = 0.0;
+
+        for (suffix, stalls) in analyze(series)? {
+            let stalled: f64 = stalls.iter().map(|stall| stall.end - stall.start).sum();
+            let probes: usize = stalls.iter().map(|stall| stall.probes.len()).sum();
+            total += stalled;
+
+            values.push((
+                format!("ZERO_WINDOW_PERIODS{}", suffix),
+                DataValue::Int(stalls.len() as i64),
+            ));
+            values.push((format!("STALLED_TIME{}", suffix), DataValue::Float(stalled)));
+            values.push((
+                format!("WINDOW_PROBES{}", suffix),
+                DataValue::Int(probes as i64),
+            ));
+        }
+        values.push(("STALLED_TIME".to_string(), DataValue::Float(total)));
+
+        // Zero window probes the local socket sent, counter is reset by every answer
+        // Both sock traces sample the same socket
+        let kernel: Vec<&SeriesData> = series
+            .iter()
+            .skip(13)
+            .filter(|kernel| !kernel.is_empty())
+            .collect();
+        if !kernel.is_empty() {
+            let max = kernel
+                .iter()
+                .flat_map(|kernel| kernel.data.iter())
+                .filter_map(numeric_value)
+                .fold(0.0, f64::max);
+            values.push(("KERNEL_MAX_PROBES".to_string(), DataValue::Int(max as i64)));
+        }
+
+        Ok(values
+            .into_iter()
+            .map(|(name, value)| FlowAttribute { name, value })
+            .collect())
+    }
+
+    fn receive_required_timeseries(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn receive_optional_timeseries(&self) -> Vec<String> {
+        self.optional_timeseries_as_string.clone()
+    }
+}
+
+// Zero window periods of each direction that has packets
+fn analyze(series: &Vec<SeriesData>) -> Result<Vec<(&'static str, Vec<Stall>)>, String> {
+    if series.len() < 15 {
+        return Err("Missing series!".to_string());
+    }
+    let side = move |suffix: &'static str, offset: usize| Side {
+        suffix,
+        scaled: &series[offset],
+        window: &series[offset + 1],
+        packets: &series[offset + 2],
+        rst: &series[offset + 3],
+        seq: &series[offset + 4],
+        ack: &series[offset + 5],
+    };
+    let (incoming, outgoing) = (side("_IN", 0), side("_OUT", 6));
+    let rcv_wnd = &series[12];
+
+    if incoming.packets.is_empty() && outgoing.packets.is_empty() && rcv_wnd.is_empty() {
+        return Err("Flow contains no packets or RCV_WND!".to_string());
+    }
+
+    let mut result: Vec<(&'static str, Vec<Stall>)> = Vec::new();
+
+    if !incoming.packets.is_empty() {
+        result.push((
+            incoming.suffix,
+            stalls(&incoming.windows(), &outgoing, &incoming),
+        ));
+    }
+
+    // Local window is also known from tcp_probe
+    let mut windows = outgoing.windows();
+    windows.extend(
+        rcv_wnd
+            .timestamps
+            .iter()
+            .zip(rcv_wnd.data.iter())
+            .filter_map(|(ts, value)| Some((*ts, numeric_value(value)?))),
+    );
+    windows.sort_by(|a, b| a.0.total_cmp(&b.0));
+    if !windows.is_empty() {
+        result.push((outgoing.suffix, stalls(&windows, &incoming, &outgoing)));
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,165 @@
+//  contains trait for implementing a processor for data from Flows and their series-data
+// a processor can:
+//  - create new time series based on given series of a single flow
+//  - summarize the flow in flow attributes
+// Writing results to the database is handled by the caller, see pipeline.rs
+
+use std::sync::Arc;
+
+use ts_storage::FlowAttribute;
+
+use crate::{
+    plugin_cc_state::CcState,
+    plugin_ce_fraction::CeFraction,
+    plugin_lifecycle::Lifecycle,
+    plugin_limiter::Limiter,
+    plugin_reordering::Reordering,
+    plugin_upper_window::UpperWindow,
+    plugin_zero_window::ZeroWindow,
+    processor_dummy::DummyProcessor,
+    series_data::SeriesData,
+    wasm::{WasmPlugin, WasmProcessor},
+};
+
+pub trait PreProcessor {
+    fn receive_name(&self) -> String;
+
+    /// returns description of module
+    fn receive_description(&self) -> String;
+
+    /// reteurns a Vector rcontaining string representation of the names for each time series required
+    /// REQUIREMENT: must contain a valid name for a timeseries!
+    fn receive_required_timeseries(&self) -> Vec<String>;
+
+    /// returns names of time series that are passed after the required ones if the flow contains them
+    /// missing series are passed as empty series, e.g. flags that were never set
+    fn receive_optional_timeseries(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn receive_required_series_formatted(&self, collection_of_names: Vec<String>) -> String {
+        let mut formatted_string_collection = String::new();
+
+        for entry in collection_of_names {
+            let formatted_line = format!("name of timeseries: {:?}", entry);
+            formatted_string_collection =
+                format!("{formatted_string_collection}\n{formatted_line}");
+        }
+        formatted_string_collection
+    }
+
+    /// takes the required series of a single flow, in the order of receive_required_timeseries
+    /// followed by the optional series in the order of receive_optional_timeseries
+    /// returns a vector of the newly generated Time-Series for the given Flow
+    /// ASSUMPTION:the supplied database has not been modified yet
+    fn create_new_time_series(&self, series: &Vec<SeriesData>) -> Result<Vec<SeriesData>, String>;
+
+    /// takes the required series and the series returned by create_new_time_series
+    /// returns attributes that summarize the flow, none by default
+    fn create_flow_attributes(
+        &self,
+        _series: &Vec<SeriesData>,
+        _created: &Vec<SeriesData>,
+    ) -> Result<Vec<FlowAttribute>, String> {
+        Ok(Vec::new())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProcessorImplementation {
+    // None,
+    DummyProcessor,
+    UpperWindow,
+    CeFraction,
+    CcState,
+    Limiter,
+    Lifecycle,
+    Reordering,
+    ZeroWindow,
+    // Loaded at runtime, not part of ALL
+    Wasm(Arc<WasmPlugin>),
+}
+impl ToString for ProcessorImplementation {
+    fn to_string(&self) -> String {
+        match self {
+            ProcessorImplementation::DummyProcessor => "Dummy Processor".to_string(),
+            ProcessorImplementation::UpperWindow => "Upper TCP Window Number".to_string(),
+            ProcessorImplementation::CeFraction => "CE Mark Fraction per RTT".to_string(),
+            ProcessorImplementation::CcState => "Congestion Control State".to_string(),
+            ProcessorImplementation::Limiter => "Throughput Limiter".to_string(),
+            ProcessorImplementation::Lifecycle => "Connection Lifecycle".to_string(),
+            ProcessorImplementation::Reordering => "Reordering".to_string(),
+            ProcessorImplementation::ZeroWindow => "Zero Window".to_string(),
+            ProcessorImplementation::Wasm(plugin) => format!("{} (wasm)", plugin.name()),
+        }
+    }
+}
+impl ProcessorImplementation {
+    pub const ALL: &'static [Self] = &[
+        Self::DummyProcessor,
+        Self::UpperWindow,
+        Self::CeFraction,
+        Self::CcState,
+        Self::Limiter,
+        Self::Lifecycle,
+        Self::Reordering,
+        Self::ZeroWindow,
+    ];
+
+    pub fn create_processor(&self) -> Box<dyn PreProcessor> {
+        match self {
+            Self::DummyProcessor => Box::new(DummyProcessor::default()),
+            Self::UpperWindow => Box::new(UpperWindow::default()),
+            Self::CeFraction => Box::new(CeFraction::default()),
+            Self::CcState => Box::new(CcState::default()),
+            Self::Limiter => Box::new(Limiter::default()),
+            Self::Lifecycle => Box::new(Lifecycle::default()),
+            Self::Reordering => Box::new(Reordering::default()),
+            Self::ZeroWindow => Box::new(ZeroWindow::default()),
+            Self::Wasm(plugin) => Box::new(WasmProcessor::new(plugin.clone())),
+        }
+    }
+
+    /// short name used to select the processor on the command line
+    pub fn cli_name(&self) -> String {
+        match self {
+            Self::DummyProcessor => "dummy".to_string(),
+            Self::UpperWindow => "upper_window".to_string(),
+            Self::CeFraction => "ce_fraction".to_string(),
+            Self::CcState => "cc_state".to_string(),
+            Self::Limiter => "limiter".to_string(),
+            Self::Lifecycle => "lifecycle".to_string(),
+            Self::Reordering => "reordering".to_string(),
+            Self::ZeroWindow => "zero_window".to_string(),
+            Self::Wasm(plugin) => plugin.path.clone(),
+        }
+    }
+
+    pub fn from_cli_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|processor| processor.cli_name() == name)
+            .cloned()
+    }
+
+    /// loads and validates a WebAssembly plugin
+    pub fn load_wasm(path: &str) -> Result<Self, String> {
+        Ok(Self::Wasm(Arc::new(WasmPlugin::load(path)?)))
+    }
+
+    /// resolves a command line argument, either a built-in name or the path of a .wasm file
+    pub fn from_argument(argument: &str) -> Result<Self, String> {
+        if let Some(processor) = Self::from_cli_name(argument) {
+            return Ok(processor);
+        }
+        if argument.ends_with(".wasm") {
+            return Self::load_wasm(argument);
+        }
+        let available: Vec<String> = Self::ALL.iter().map(|p| p.cli_name()).collect();
+        Err(format!(
+            "Unknown plugin {}! Available: {} or a path to a .wasm file",
+            argument,
+            available.join(", ")
+        ))
+    }
+}
@@ -2,19 +2,13 @@
 // primarily used to provide means to debug and test
 // operation of the module system
 
-use iced::widget::canvas::Cache;
 use ts_storage::DataValue;
 
 // used to generate random data
 use rand::distr::{Distribution, Uniform};
 use rand::rng;
 
-use crate::modules::{
-    backend::database_processor::trait_database_processor::PreProcessor,
-    ui::lib_widgets::lib_graphs::{
-        struct_flow_series_data::FlowSeriesData, struct_processed_plot_data::ProcessedPlotData,
-    },
-};
+use crate::{processor::PreProcessor, series_data::SeriesData};
 
 pub struct DummyProcessor {
     required_timeseries_as_string: Vec<String>,
@@ -24,8 +18,8 @@ impl Default for DummyProcessor {
     fn default() -> Self {
         DummyProcessor {
             required_timeseries_as_string: Vec::from([
-                "ack_num".to_string(),
-                "seq_num".to_string(),
+                "ACK_NUM_OUT".to_string(),
+                "SEQ_NUM_OUT".to_string(),
                 // "retransmission".to_string(),
             ]),
         }
@@ -39,20 +33,14 @@ impl PreProcessor for DummyProcessor {
 
     fn receive_description(&self) -> String {
         "
-This simple implementation does not much but add a new entry thats a line with a constant value throughout the whole timeline.
+This simple implementation does not much but add a new entry thats a line with random values throughout the whole timeline.
 Its written to provide means to understand and implement new modules
-    ".to_string()
+    "
+        .to_string()
     }
 
-    fn create_new_time_series_from_plot_data(
-        &self,
-        plot_data: &ProcessedPlotData,
-    ) -> Result<Vec<FlowSeriesData>, String> {
-        // TODO: add constant value for each timestamp given by plot_data
-        // create given FlowSeriesData from that
-        let collection_of_series = &plot_data.point_collection;
-        let maybe_first_series = collection_of_series.first();
-        let first_series = match maybe_first_series {
+    fn create_new_time_series(&self, series: &Vec<SeriesData>) -> Result<Vec<SeriesData>, String> {
+        let first_series = match series.first() {
             None => return Err("no series received".to_string()),
             Some(series) => series,
         };
@@ -68,21 +56,13 @@ Its written to provide means to understand and implement new modules
                 DataValue::Float(random_value)
             })
             .collect();
-        let new_flow_series = FlowSeriesData {
-            data: new_values.clone(),
+        let new_series = SeriesData {
             name: "tst_rndm".to_string(),
-            timestamps: timestamps,
-            max_timestamp: first_series.max_timestamp,
-            min_timestamp: first_series.min_timestamp,
-            min_val: Some(DataValue::Float(30.0)),
-            max_val: Some(DataValue::Float(0.0)),
+            timestamps,
+            data: new_values,
             data_val_type: DataValue::Float(0.0),
-            zoom_bounds: first_series.zoom_bounds.clone(),
-            chart_height: first_series.chart_height,
-            line_color: first_series.line_color,
-            cache: Cache::new(),
         };
-        Ok(Vec::from([new_flow_series]))
+        Ok(Vec::from([new_series]))
     }
 
     fn receive_required_timeseries(&self) -> Vec<String> {
@@ -0,0 +1,86 @@
+// contains the UI independent representation of a time series
+// that is passed to and returned from plugins
+use std::collections::HashMap;
+
+use ts_storage::DataValue;
+
+#[derive(Debug, Clone)]
+pub struct SeriesData {
+    pub name: String,
+    pub timestamps: Vec<f64>,
+    pub data: Vec<DataValue>,
+    // Only the type of this value is relevant
+    pub data_val_type: DataValue,
+}
+
+impl SeriesData {
+    pub fn new(name: &str, data_val_type: DataValue) -> SeriesData {
+        SeriesData {
+            name: name.to_string(),
+            timestamps: Vec::new(),
+            data: Vec::new(),
+            data_val_type,
+        }
+    }
+
+    pub fn push(&mut self, timestamp: f64, value: DataValue) {
+        self.timestamps.push(timestamp);
+        self.data.push(value);
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// returns how long each value of a state series was held, the last one until end
+    /// every point starts a state that holds until the next point
+    pub fn state_durations(&self, end: f64) -> HashMap<String, f64> {
+        let mut durations: HashMap<String, f64> = HashMap::new();
+        let ends = self.timestamps.iter().skip(1).chain(std::iter::once(&end));
+
+        for ((start, value), end) in self.timestamps.iter().zip(self.data.iter()).zip(ends) {
+            *durations.entry(value.as_string()).or_insert(0.0) += (end - start).max(0.0);
+        }
+        durations
+    }
+
+    /// returns the last value at or before timestamp, the first value if the series starts later
+    /// assumes that timestamps are sorted
+    pub fn value_at(&self, timestamp: f64) -> Option<&DataValue> {
+        let pos = self.timestamps.partition_point(|ts| *ts <= timestamp);
+        self.data.get(pos.saturating_sub(1))
+    }
+
+    /// returns (min, max) of the series, only defined for INT and FLOAT series
+    pub fn value_bounds(&self) -> Option<(DataValue, DataValue)> {
+        match self.data_val_type {
+            DataValue::Int(_) => {
+                let values = self.data.iter().filter_map(|val| val.as_int());
+                let min = values.clone().min()?;
+                let max = values.max()?;
+                Some((DataValue::Int(min), DataValue::Int(max)))
+            }
+            DataValue::Float(_) => {
+                let values = self.data.iter().filter_map(|val| val.as_float());
+                let min = values.clone().reduce(f64::min)?;
+                let max = values.reduce(f64::max)?;
+                Some((DataValue::Float(min), DataValue::Float(max)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Numeric view on a value, booleans are mapped to 0 and 1
+pub fn numeric_value(value: &DataValue) -> Option<f64> {
+    match value {
+        DataValue::Int(val) => Some(*val as f64),
+        DataValue::Float(val) => Some(*val),
+        DataValue::Boolean(val) => Some(if *val { 1.0 } else { 0.0 }),
+        DataValue::String(_) => None,
+    }
+}
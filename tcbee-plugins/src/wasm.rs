@@ -0,0 +1,389 @@
+// contains the host side of plugins that are loaded at runtime from WebAssembly modules
+//
+// A plugin module has to export its linear memory as "memory" and the functions
+//  tcbee_name() -> i64         name of the plugin
+//  tcbee_description() -> i64  description of the plugin
+//  tcbee_required() -> i64     comma separated names of the required time series
+//  tcbee_run() -> i32          calculates new series, 0 on success
+// Strings are returned as (pointer << 32) | length into the exported memory.
+//
+// The host provides the following functions in the module "tcbee"
+//  series_count() -> i32
+//  series_find(name_ptr: i32, name_len: i32) -> i32               index of a required series or -1
+//  series_len(series: i32) -> i32                                  number of points or -1
+//  series_timestamp(series: i32, pos: i32) -> f64                  NaN if out of bounds
+//  series_value(series: i32, pos: i32) -> f64                      NaN if out of bounds or not numeric
+//  emit_series(name_ptr: i32, name_len: i32, type: i32) -> i32     handle of a new series or -1
+//  emit_point(series: i32, timestamp: f64, value: f64) -> i32      0 on success
+//  emit_string(series: i32, timestamp: f64, ptr: i32, len: i32) -> i32
+// Types of emitted series follow the DataValue numbering: 0 INT, 1 FLOAT, 2 BOOLEAN, 3 STRING
+//
+// Plugins run in an interpreter without access to the host.
+// They only see copies of their required series, run with a fuel and memory limit
+// and emitted series are only handed to the caller after the plugin finished successfully.
+
+use std::{fmt::Debug, fs, sync::Arc};
+
+use ts_storage::DataValue;
+use wasmi::{
+    Caller, Config, Engine, Extern, Instance, Linker, Module, Store, StoreLimits,
+    StoreLimitsBuilder,
+};
+
+use crate::{
+    processor::PreProcessor,
+    series_data::{numeric_value, SeriesData},
+};
+
+// Limits for a single plugin call
+// Metadata is read on load, e.g. from the UI thread, and only returns constant strings
+const METADATA_FUEL: u64 = 10_000_000;
+// Fuel of a run per input point, between a minimum for short series and a maximum
+const FUEL_PER_INPUT_POINT: u64 = 100_000;
+const MIN_RUN_FUEL: u64 = 1_000_000_000;
+const MAX_RUN_FUEL: u64 = 20_000_000_000;
+const MEMORY_LIMIT: usize = 512 * 1024 * 1024;
+// Emitted points per input point, further points are rejected
+const EMITTED_POINTS_PER_INPUT: usize = 4;
+// Emitted points allowed for any input, e.g. for flows with short series
+const MIN_EMITTED_POINTS: usize = 100_000;
+const MAX_STRING_LENGTH: usize = 4096;
+
+const HOST_MODULE: &str = "tcbee";
+
+struct HostState {
+    input: Vec<SeriesData>,
+    output: Vec<SeriesData>,
+    emitted_points: usize,
+    max_emitted_points: usize,
+    limits: StoreLimits,
+}
+
+impl HostState {
+    fn new(input: Vec<SeriesData>) -> HostState {
+        // Output is kept on the host until the run finished, bound it by the input
+        let input_points: usize = input.iter().map(|series| series.len()).sum();
+        HostState {
+            input,
+            output: Vec::new(),
+            emitted_points: 0,
+            max_emitted_points: (input_points * EMITTED_POINTS_PER_INPUT).max(MIN_EMITTED_POINTS),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(MEMORY_LIMIT)
+                .instances(1)
+                .build(),
+        }
+    }
+
+    fn point(&self, series: i32, pos: i32) -> Option<(&f64, &DataValue)> {
+        let series = self.input.get(usize::try_from(series).ok()?)?;
+        let pos = usize::try_from(pos).ok()?;
+        Some((series.timestamps.get(pos)?, series.data.get(pos)?))
+    }
+
+    fn emit(&mut self, series: i32, timestamp: f64, value: DataValue) -> i32 {
+        if self.emitted_points >= self.max_emitted_points || !timestamp.is_finite() {
+            return -1;
+        }
+        let Some(target) = usize::try_from(series)
+            .ok()
+            .and_then(|index| self.output.get_mut(index))
+        else {
+            return -1;
+        };
+        if !target.data_val_type.type_equal(&value) {
+            return -1;
+        }
+        target.push(timestamp, value);
+        self.emitted_points += 1;
+        0
+    }
+}
+
+// Reads a string from the memory exported by the calling plugin
+fn read_string(caller: &Caller<'_, HostState>, ptr: i32, len: i32) -> Option<String> {
+    let Some(Extern::Memory(memory)) = caller.get_export("memory") else {
+        return None;
+    };
+    let ptr = usize::try_from(ptr).ok()?;
+    let len = usize::try_from(len).ok()?;
+    if len > MAX_STRING_LENGTH {
+        return None;
+    }
+    let mut buffer = vec![0u8; len];
+    memory.read(caller, ptr, &mut buffer).ok()?;
+    String::from_utf8(buffer).ok()
+}
+
+fn define_host_functions(linker: &mut Linker<HostState>) -> Result<(), wasmi::Error> {
+    linker.func_wrap(
+        HOST_MODULE,
+        "series_count",
+        |caller: Caller<'_, HostState>| -> i32 { caller.data().input.len() as i32 },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "series_find",
+        |caller: Caller<'_, HostState>, ptr: i32, len: i32| -> i32 {
+            let Some(name) = read_string(&caller, ptr, len) else {
+                return -1;
+            };
+            caller
+                .data()
+                .input
+                .iter()
+                .position(|series| series.name == name)
+                .map(|index| index as i32)
+                .unwrap_or(-1)
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "series_len",
+        |caller: Caller<'_, HostState>, series: i32| -> i32 {
+            usize::try_from(series)
+                .ok()
+                .and_then(|index| caller.data().input.get(index))
+                .map(|series| series.len() as i32)
+                .unwrap_or(-1)
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "series_timestamp",
+        |caller: Caller<'_, HostState>, series: i32, pos: i32| -> f64 {
+            match caller.data().point(series, pos) {
+                Some((timestamp, _)) => *timestamp,
+                None => f64::NAN,
+            }
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "series_value",
+        |caller: Caller<'_, HostState>, series: i32, pos: i32| -> f64 {
+            caller
+                .data()
+                .point(series, pos)
+                .and_then(|(_, value)| numeric_value(value))
+                .unwrap_or(f64::NAN)
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "emit_series",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32, ts_type: i32| -> i32 {
+            let Some(name) = read_string(&caller, ptr, len) else {
+                return -1;
+            };
+            let Ok(ts_type) = DataValue::type_from_int(ts_type as i16) else {
+                return -1;
+            };
+            let state = caller.data_mut();
+            if name.is_empty() || state.output.iter().any(|series| series.name == name) {
+                return -1;
+            }
+            state.output.push(SeriesData::new(&name, ts_type));
+            (state.output.len() - 1) as i32
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "emit_point",
+        |mut caller: Caller<'_, HostState>, series: i32, timestamp: f64, value: f64| -> i32 {
+            let state = caller.data_mut();
+            let Some(target) = usize::try_from(series)
+                .ok()
+                .and_then(|index| state.output.get(index))
+            else {
+                return -1;
+            };
+            let value = match target.data_val_type {
+                DataValue::Int(_) => DataValue::Int(value as i64),
+                DataValue::Float(_) => DataValue::Float(value),
+                DataValue::Boolean(_) => DataValue::Boolean(value != 0.0),
+                DataValue::String(_) => return -1,
+            };
+            state.emit(series, timestamp, value)
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "emit_string",
+        |mut caller: Caller<'_, HostState>, series: i32, timestamp: f64, ptr: i32, len: i32| -> i32 {
+            let Some(value) = read_string(&caller, ptr, len) else {
+                return -1;
+            };
+            caller
+                .data_mut()
+                .emit(series, timestamp, DataValue::String(value))
+        },
+    )?;
+    Ok(())
+}
+
+/// A validated WebAssembly plugin, shared between all processors created from it
+pub struct WasmPlugin {
+    pub path: String,
+    name: String,
+    description: String,
+    required: Vec<String>,
+    engine: Engine,
+    module: Module,
+}
+
+impl Debug for WasmPlugin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmPlugin")
+            .field("path", &self.path)
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl PartialEq for WasmPlugin {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
+
+impl WasmPlugin {
+    pub fn load(path: &str) -> Result<WasmPlugin, String> {
+        let bytes = fs::read(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+        WasmPlugin::from_bytes(path, &bytes)
+    }
+
+    pub fn from_bytes(path: &str, bytes: &[u8]) -> Result<WasmPlugin, String> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module =
+            Module::new(&engine, bytes).map_err(|e| format!("Invalid plugin {}: {}", path, e))?;
+
+        let mut plugin = WasmPlugin {
+            path: path.to_string(),
+            name: String::new(),
+            description: String::new(),
+            required: Vec::new(),
+            engine,
+            module,
+        };
+
+        // Read plugin information once, also validates imports and exports
+        let (mut store, instance) = plugin.instantiate(Vec::new(), METADATA_FUEL)?;
+        plugin.name = plugin.call_string_export(&mut store, &instance, "tcbee_name")?;
+        plugin.description =
+            plugin.call_string_export(&mut store, &instance, "tcbee_description")?;
+        plugin.required = plugin
+            .call_string_export(&mut store, &instance, "tcbee_required")?
+            .split(',')
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .collect();
+
+        Ok(plugin)
+    }
+
+    // Every call gets a fresh instance so that no state is kept between flows
+    fn instantiate(
+        &self,
+        input: Vec<SeriesData>,
+        fuel: u64,
+    ) -> Result<(Store<HostState>, Instance), String> {
+        let mut store = Store::new(&self.engine, HostState::new(input));
+        store.limiter(|state| &mut state.limits);
+        store
+            .set_fuel(fuel)
+            .map_err(|e| format!("Could not limit plugin: {}", e))?;
+
+        let mut linker = Linker::new(&self.engine);
+        define_host_functions(&mut linker).map_err(|e| e.to_string())?;
+
+        let instance = linker
+            .instantiate(&mut store, &self.module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(|e| format!("Could not instantiate plugin {}: {}", self.path, e))?;
+
+        Ok((store, instance))
+    }
+
+    fn call_string_export(
+        &self,
+        store: &mut Store<HostState>,
+        instance: &Instance,
+        export: &str,
+    ) -> Result<String, String> {
+        let func = instance
+            .get_typed_func::<(), i64>(&mut *store, export)
+            .map_err(|e| format!("Plugin {} has no valid {}: {}", self.path, export, e))?;
+        let packed = func
+            .call(&mut *store, ())
+            .map_err(|e| format!("Plugin {} failed in {}: {}", self.path, export, e))?;
+
+        let memory = instance
+            .get_memory(&*store, "memory")
+            .ok_or(format!("Plugin {} does not export its memory!", self.path))?;
+
+        let ptr = (packed >> 32) as u32 as usize;
+        let len = packed as u32 as usize;
+        if len > MAX_STRING_LENGTH {
+            return Err(format!("Plugin {} returned a too long {}", self.path, export));
+        }
+        let mut buffer = vec![0u8; len];
+        memory
+            .read(&*store, ptr, &mut buffer)
+            .map_err(|e| format!("Plugin {} returned an invalid {}: {}", self.path, export, e))?;
+        String::from_utf8(buffer).map_err(|e| e.to_string())
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+pub struct WasmProcessor {
+    plugin: Arc<WasmPlugin>,
+}
+
+impl WasmProcessor {
+    pub fn new(plugin: Arc<WasmPlugin>) -> WasmProcessor {
+        WasmProcessor { plugin }
+    }
+}
+
+impl PreProcessor for WasmProcessor {
+    fn receive_name(&self) -> String {
+        self.plugin.name.clone()
+    }
+
+    fn receive_description(&self) -> String {
+        format!("{}\n\nLoaded from {}", self.plugin.description, self.plugin.path)
+    }
+
+    fn receive_required_timeseries(&self) -> Vec<String> {
+        self.plugin.required.clone()
+    }
+
+    fn create_new_time_series(&self, series: &Vec<SeriesData>) -> Result<Vec<SeriesData>, String> {
+        let input_points: u64 = series.iter().map(|series| series.len() as u64).sum();
+        let fuel = (input_points * FUEL_PER_INPUT_POINT).clamp(MIN_RUN_FUEL, MAX_RUN_FUEL);
+        let (mut store, instance) = self.plugin.instantiate(series.clone(), fuel)?;
+
+        let run = instance
+            .get_typed_func::<(), i32>(&store, "tcbee_run")
+            .map_err(|e| format!("Plugin {} has no valid tcbee_run: {}", self.plugin.path, e))?;
+
+        let status = run
+            .call(&mut store, ())
+            .map_err(|e| format!("Plugin {} aborted: {}", self.plugin.path, e))?;
+
+        if status != 0 {
+            return Err(format!(
+                "Plugin {} failed with status {}",
+                self.plugin.path, status
+            ));
+        }
+
+        Ok(store.into_data().output)
+    }
+}
@@ -0,0 +1,28 @@
+// Helpers shared by the integration tests
+use ts_storage::sqlite::SQLiteTSDB;
+
+// Database file in the temp directory, removed when the test ends, also if it fails
+pub struct TempDb {
+    pub path: String,
+}
+
+impl TempDb {
+    pub fn new(name: &str) -> TempDb {
+        let path = std::env::temp_dir().join(format!("tcbee-{}-{}.sqlite", name, std::process::id()));
+        let db = TempDb {
+            path: path.to_string_lossy().to_string(),
+        };
+        let _ = std::fs::remove_file(&db.path);
+        db
+    }
+
+    pub fn open(&self) -> SQLiteTSDB {
+        SQLiteTSDB::new(self.path.clone()).expect("Failed to open database!")
+    }
+}
+
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use common::TempDb;
+use tcbee_plugins::{run_processor_on_flow, PreProcessor, SeriesData};
+use ts_storage::{DataPoint, DataValue, IpTuple, TSDBInterface};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+// Copies SND_CWND into the given series
+struct Copy {
+    names: Vec<&'static str>,
+}
+
+impl PreProcessor for Copy {
+    fn receive_name(&self) -> String {
+        "Copy".to_string()
+    }
+    fn receive_description(&self) -> String {
+        "Copies SND_CWND".to_string()
+    }
+    fn receive_required_timeseries(&self) -> Vec<String> {
+        vec!["SND_CWND".to_string()]
+    }
+    fn create_new_time_series(&self, series: &Vec<SeriesData>) -> Result<Vec<SeriesData>, String> {
+        Ok(self
+            .names
+            .iter()
+            .map(|name| {
+                let mut copy = series[0].clone();
+                copy.name = name.to_string();
+                copy
+            })
+            .collect())
+    }
+}
+
+#[test]
+fn failed_runs_store_nothing() {
+    let file = TempDb::new("pipeline");
+    let db = file.open();
+
+    let tuple = IpTuple {
+        src: IpAddr::from_str("10.0.0.1").unwrap(),
+        dst: IpAddr::from_str("10.0.0.2").unwrap(),
+        sport: 100,
+        dport: 200,
+        l4proto: 6,
+    };
+    let flow = db.create_flow(&tuple).expect("Failed to create flow!");
+    let cwnd = db
+        .create_time_series(&flow, "SND_CWND", DataValue::Int(0))
+        .expect("Failed to create time series!");
+    let points = vec![
+        DataPoint {
+            timestamp: 0.0,
+            value: DataValue::Int(10),
+        },
+        DataPoint {
+            timestamp: 1.0,
+            value: DataValue::Int(20),
+        },
+    ];
+    db.insert_multiple_points(&cwnd, &points)
+        .expect("Failed to add points!");
+
+    // The second series exists, the first one must not be kept
+    let failing = Copy {
+        names: vec!["CWND_COPY", "SND_CWND"],
+    };
+    assert!(run_processor_on_flow(&db, &flow, &failing).is_err());
+    let names: Vec<String> = db
+        .list_time_series(&flow)
+        .expect("Failed to list time series!")
+        .map(|ts| ts.name)
+        .collect();
+    assert_eq!(names, vec!["SND_CWND".to_string()]);
+
+    // The database is usable after the rollback
+    let copy = Copy {
+        names: vec!["CWND_COPY"],
+    };
+    let created = run_processor_on_flow(&db, &flow, &copy).expect("Processor failed!");
+    assert_eq!(created, vec!["CWND_COPY".to_string()]);
+}
@@ -0,0 +1,414 @@
+use tcbee_plugins::{PreProcessor, ProcessorImplementation, SeriesData};
+use ts_storage::DataValue;
+
+fn series(name: &str, points: &[(f64, DataValue)]) -> SeriesData {
+    let mut series = SeriesData::new(name, points[0].1.clone());
+    for (timestamp, value) in points.iter() {
+        series.push(*timestamp, value.clone());
+    }
+    series
+}
+
+fn flags(name: &str, timestamps: &[f64]) -> SeriesData {
+    let points: Vec<(f64, DataValue)> = timestamps
+        .iter()
+        .map(|ts| (*ts, DataValue::Boolean(true)))
+        .collect();
+    series(name, &points)
+}
+
+fn floats(series: &SeriesData) -> Vec<f64> {
+    series
+        .data
+        .iter()
+        .filter_map(|val| val.as_float())
+        .collect()
+}
+
+fn ints(name: &str, points: &[(f64, i64)]) -> SeriesData {
+    let points: Vec<(f64, DataValue)> = points
+        .iter()
+        .map(|(ts, val)| (*ts, DataValue::Int(*val)))
+        .collect();
+    series(name, &points)
+}
+
+// Series with one value per sample timestamp
+fn with_samples(name: &str, samples: &[f64], values: &[i64]) -> SeriesData {
+    let points: Vec<(f64, i64)> = samples
+        .iter()
+        .cloned()
+        .zip(values.iter().cloned())
+        .collect();
+    ints(name, &points)
+}
+
+// Flow attributes of a processor run, values as strings
+fn string_attributes(
+    processor: &dyn PreProcessor,
+    input: &Vec<SeriesData>,
+    output: &Vec<SeriesData>,
+) -> Vec<(String, String)> {
+    processor
+        .create_flow_attributes(input, output)
+        .expect("Plugin failed!")
+        .into_iter()
+        .map(|attr| (attr.name, attr.value.as_string()))
+        .collect()
+}
+
+fn attribute<'a>(attributes: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|(attr, _)| attr == name)
+        .map(|(_, value)| value.as_str())
+}
+
+#[test]
+fn ce_fraction() {
+    let processor = ProcessorImplementation::from_cli_name("ce_fraction")
+        .expect("Plugin not registered!")
+        .create_processor();
+
+    // SRTT of 1 µs, ACKs in two RTTs, half of the first RTT is marked
+    let input = vec![
+        flags("FLAG_ACK_IN", &[0.0, 200.0, 400.0, 600.0, 1000.0, 1500.0]),
+        flags("FLAG_ECE_IN", &[200.0, 600.0]),
+        series("SRTT", &[(0.0, DataValue::Int(1))]),
+        series(
+            "SND_CWND",
+            &[(0.0, DataValue::Int(10)), (1200.0, DataValue::Int(5))],
+        ),
+    ];
+
+    let output = processor
+        .create_new_time_series(&input)
+        .expect("Plugin failed!");
+
+    assert_eq!(output.len(), 2);
+    assert_eq!(output[0].name, "CE_FRACTION");
+    assert_eq!(output[0].timestamps, vec![1000.0, 2000.0]);
+    assert_eq!(floats(&output[0]), vec![0.5, 0.0]);
+
+    // Cwnd is halved in the second RTT
+    assert_eq!(output[1].name, "CWND_CHANGE");
+    assert_eq!(floats(&output[1]), vec![0.0, -0.5]);
+}
+
+#[test]
+fn cc_state() {
+    let processor = ProcessorImplementation::from_cli_name("cc_state")
+        .expect("Plugin not registered!")
+        .create_processor();
+
+    let samples = [0.0, 1.0, 2.0, 3.0, 4.0, 5.0];
+    let input = vec![
+        with_samples("SND_CWND", &samples, &[10, 20, 14, 14, 1, 2]),
+        with_samples(
+            "SSTRESH",
+            &samples,
+            &[i32::MAX as i64, i32::MAX as i64, 14, 14, 7, 7],
+        ),
+        with_samples("SND_NXT", &samples, &[1000, 3000, 5000, 6000, 7000, 7000]),
+        with_samples("SND_UNA", &samples, &[0, 1000, 2000, 5000, 5500, 7000]),
+        // Segment 2000 is sent again after 4000
+        ints(
+            "SEQ_NUM_OUT",
+            &[(0.5, 0), (1.0, 2000), (1.2, 4000), (1.5, 2000), (3.5, 6000)],
+        ),
+    ];
+
+    let output = processor
+        .create_new_time_series(&input)
+        .expect("Plugin failed!");
+
+    assert_eq!(output.len(), 1);
+    assert_eq!(output[0].name, "CC_STATE");
+    assert_eq!(output[0].timestamps, vec![0.0, 2.0, 3.0, 4.0, 5.0]);
+    let states: Vec<String> = output[0].data.iter().map(|val| val.as_string()).collect();
+    assert_eq!(
+        states,
+        vec![
+            "SLOW_START",
+            "FAST_RECOVERY",
+            "CONGESTION_AVOIDANCE",
+            "LOSS",
+            "APP_LIMITED"
+        ]
+    );
+}
+
+#[test]
+fn limiter() {
+    let processor = ProcessorImplementation::from_cli_name("limiter")
+        .expect("Plugin not registered!")
+        .create_processor();
+
+    let samples = [0.0, 1.0, 2.0, 3.0, 5.0];
+    let input = vec![
+        // SND_NXT wraps around in the first sample
+        with_samples("SND_NXT", &samples, &[9000, 20000, 30000, 40000, 40000]),
+        with_samples(
+            "SND_UNA",
+            &samples,
+            &[u32::MAX as i64 - 499, 12500, 25500, 40000, 40000],
+        ),
+        with_samples("SND_CWND", &samples, &[10, 20, 20, 20, 20]),
+        with_samples("SND_WND", &samples, &[50000, 8000, 50000, 50000, 50000]),
+        // 1 MB/s over 5 ms allows 5000 bytes per RTT
+        ints("SRTT", &[(0.0, 5000)]),
+        // Socket series of a merged import are named after the sending socket
+        ints("pacing_rate_OUT", &[(0.0, 1_000_000)]),
+        ints("advmss_OUT", &[(0.0, 1000)]),
+    ];
+    let names: Vec<&str> = input.iter().map(|series| series.name.as_str()).collect();
+    assert_eq!(processor.receive_required_timeseries(), names);
+
+    let output = processor
+        .create_new_time_series(&input)
+        .expect("Plugin failed!");
+
+    assert_eq!(output.len(), 1);
+    assert_eq!(output[0].name, "LIMITER");
+    assert_eq!(output[0].timestamps, vec![0.0, 1.0, 2.0, 3.0]);
+    let states: Vec<String> = output[0].data.iter().map(|val| val.as_string()).collect();
+    assert_eq!(
+        states,
+        vec![
+            "CWND_LIMITED",
+            "RWND_LIMITED",
+            "PACING_LIMITED",
+            "APP_LIMITED"
+        ]
+    );
+
+    // Last state holds until the last SND_CWND sample
+    let attributes = processor
+        .create_flow_attributes(&input, &output)
+        .expect("Plugin failed!");
+    let shares: Vec<(String, f64)> = attributes
+        .iter()
+        .map(|attr| (attr.name.clone(), attr.value.as_float().unwrap()))
+        .collect();
+    assert_eq!(
+        shares,
+        vec![
+            ("CWND_LIMITED_SHARE".to_string(), 0.2),
+            ("RWND_LIMITED_SHARE".to_string(), 0.2),
+            ("PACING_LIMITED_SHARE".to_string(), 0.2),
+            ("APP_LIMITED_SHARE".to_string(), 0.4),
+        ]
+    );
+}
+
+#[test]
+fn lifecycle() {
+    let processor = ProcessorImplementation::from_cli_name("lifecycle")
+        .expect("Plugin not registered!")
+        .create_processor();
+
+    // Local host opens, retransmits its SYN and closes first
+    let input = vec![
+        flags("FLAG_SYN_OUT", &[0.0, 100.0]),
+        flags("FLAG_SYN_IN", &[200.0]),
+        flags("FLAG_ACK_OUT", &[300.0, 500.0, 600.0]),
+        flags("FLAG_ACK_IN", &[200.0, 400.0, 700.0]),
+        flags("FLAG_FIN_OUT", &[600.0]),
+        flags("FLAG_FIN_IN", &[700.0]),
+        // Flags that were never set are passed empty
+        SeriesData::new("FLAG_RST_OUT", DataValue::Boolean(false)),
+        SeriesData::new("FLAG_RST_IN", DataValue::Boolean(false)),
+        ints(
+            "PAYLOAD_LEN_OUT",
+            &[(0.0, 0), (100.0, 0), (300.0, 0), (500.0, 100), (600.0, 0)],
+        ),
+        ints("PAYLOAD_LEN_IN", &[(200.0, 0), (400.0, 1000), (700.0, 0)]),
+        SeriesData::new("RETRANSMIT_SYNACK", DataValue::Boolean(false)),
+    ];
+
+    let output = processor
+        .create_new_time_series(&input)
+        .expect("Plugin failed!");
+
+    assert_eq!(output.len(), 1);
+    assert_eq!(output[0].name, "LIFECYCLE");
+    assert_eq!(
+        output[0].timestamps,
+        vec![0.0, 100.0, 200.0, 300.0, 400.0, 600.0, 700.0]
+    );
+    let events: Vec<String> = output[0].data.iter().map(|val| val.as_string()).collect();
+    assert_eq!(
+        events,
+        vec![
+            "SYN",
+            "SYN_RETRANSMIT",
+            "SYN_ACK",
+            "ESTABLISHED",
+            "FIRST_BYTE",
+            "FIN_OUT",
+            "FIN_IN"
+        ]
+    );
+
+    let attributes = string_attributes(processor.as_ref(), &input, &output);
+
+    assert_eq!(attribute(&attributes, "HANDSHAKE_ROLE"), Some("CLIENT"));
+    assert_eq!(attribute(&attributes, "SYN_RETRANSMISSIONS"), Some("1"));
+    assert_eq!(attribute(&attributes, "SYN_ACK_RETRANSMISSIONS"), Some("0"));
+    assert_eq!(attribute(&attributes, "SYN_TO_SYN_ACK"), Some("200"));
+    assert_eq!(attribute(&attributes, "SYN_ACK_TO_ACK"), Some("100"));
+    // Measured from the retransmitted SYN
+    assert_eq!(attribute(&attributes, "HANDSHAKE_RTT"), Some("100"));
+    assert_eq!(attribute(&attributes, "TIME_TO_FIRST_BYTE"), Some("400"));
+    assert_eq!(attribute(&attributes, "TEARDOWN"), Some("FIN"));
+    assert_eq!(attribute(&attributes, "TEARDOWN_BY"), Some("LOCAL"));
+    assert_eq!(attribute(&attributes, "TEARDOWN_TIME"), Some("600"));
+    assert_eq!(attribute(&attributes, "TEARDOWN_DURATION"), Some("100"));
+}
+
+#[test]
+fn reordering() {
+    let processor = ProcessorImplementation::from_cli_name("reordering")
+        .expect("Plugin not registered!")
+        .create_processor();
+
+    // Segments of 100 bytes, the second one arrives after two later ones and is duplicated
+    // Sequence numbers wrap around after the first segment
+    let start = u32::MAX as i64 - 99;
+    let input = vec![
+        ints(
+            "SEQ_NUM_IN",
+            &[
+                (1.0, start),
+                (2.0, 100),
+                (3.0, 200),
+                (4.0, 0),
+                (6.0, 0),
+                (7.0, 300),
+            ],
+        ),
+        // Pure ACK at 5 has no sequence number
+        ints(
+            "PAYLOAD_LEN_IN",
+            &[
+                (1.0, 100),
+                (2.0, 100),
+                (3.0, 100),
+                (4.0, 100),
+                (5.0, 0),
+                (6.0, 100),
+                (7.0, 100),
+            ],
+        ),
+        ints("reordering_OUT", &[(0.0, 3)]),
+        ints("reordering_IN", &[(0.5, 2)]),
+    ];
+
+    let output = processor
+        .create_new_time_series(&input)
+        .expect("Plugin failed!");
+
+    assert_eq!(output.len(), 3);
+    assert_eq!(output[0].name, "REORDER_BYTES_IN");
+    assert_eq!(output[0].timestamps, vec![4.0]);
+    assert_eq!(output[0].data[0].as_int(), Some(200));
+    assert_eq!(output[1].name, "REORDER_PACKETS_IN");
+    assert_eq!(output[1].data[0].as_int(), Some(2));
+    assert_eq!(output[2].name, "DUPLICATE_IN");
+    assert_eq!(output[2].timestamps, vec![6.0]);
+
+    let attributes = string_attributes(processor.as_ref(), &input, &output);
+
+    assert_eq!(attribute(&attributes, "REORDERED_SEGMENTS_IN"), Some("1"));
+    assert_eq!(attribute(&attributes, "DUPLICATE_SEGMENTS_IN"), Some("1"));
+    assert_eq!(
+        attribute(&attributes, "REORDER_RATIO_IN"),
+        Some("0.16666666666666666")
+    );
+    assert_eq!(attribute(&attributes, "MAX_REORDER_BYTES_IN"), Some("200"));
+    assert_eq!(attribute(&attributes, "MAX_REORDER_PACKETS_IN"), Some("2"));
+    assert_eq!(attribute(&attributes, "KERNEL_REORDERING"), Some("3"));
+}
+
+#[test]
+fn zero_window() {
+    let processor = ProcessorImplementation::from_cli_name("zero_window")
+        .expect("Plugin not registered!")
+        .create_processor();
+
+    // Peer closes its window at 2 and opens it at 5, local RCV_WND is 0 from 6
+    // Local host probes with an empty segment before and one byte at snd_una, 3.5 is a pure ACK
+    let input = vec![
+        ints("WINDOW_SCALED_IN", &[(7.0, 2000)]),
+        ints(
+            "WINDOW_IN",
+            &[(1.0, 1000), (2.0, 0), (5.0, 2000), (7.0, 500)],
+        ),
+        ints(
+            "PAYLOAD_LEN_IN",
+            &[(1.0, 100), (2.0, 100), (5.0, 0), (7.0, 0)],
+        ),
+        SeriesData::new("FLAG_RST_IN", DataValue::Boolean(false)),
+        ints(
+            "SEQ_NUM_IN",
+            &[(1.0, 2800), (2.0, 2900), (5.0, 3000), (7.0, 2999)],
+        ),
+        ints("ACK_NUM_IN", &[(1.0, 4000), (2.0, 5000), (5.0, 5001)]),
+        SeriesData::new("WINDOW_SCALED_OUT", DataValue::Int(0)),
+        ints(
+            "WINDOW_OUT",
+            &[(1.5, 1000), (3.0, 1000), (3.5, 1000), (4.0, 1000)],
+        ),
+        ints("PAYLOAD_LEN_OUT", &[(1.5, 0), (3.0, 0), (3.5, 0), (4.0, 1)]),
+        SeriesData::new("FLAG_RST_OUT", DataValue::Boolean(false)),
+        ints(
+            "SEQ_NUM_OUT",
+            &[(1.5, 4000), (3.0, 4999), (3.5, 5000), (4.0, 5000)],
+        ),
+        ints(
+            "ACK_NUM_OUT",
+            &[(1.5, 2900), (3.0, 3000), (3.5, 3000), (4.0, 3000)],
+        ),
+        ints("RCV_WND", &[(0.0, 65535), (6.0, 0), (8.0, 0)]),
+        ints("probes_OUT", &[(3.0, 1), (4.0, 2), (5.0, 0)]),
+        ints("probes_IN", &[(3.5, 1)]),
+    ];
+
+    let output = processor
+        .create_new_time_series(&input)
+        .expect("Plugin failed!");
+
+    let names: Vec<&str> = output.iter().map(|series| series.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec![
+            "ZERO_WINDOW_IN",
+            "WINDOW_PROBE_IN",
+            "WINDOW_UPDATE_IN",
+            "ZERO_WINDOW_OUT",
+            "WINDOW_PROBE_OUT",
+            "WINDOW_UPDATE_OUT"
+        ]
+    );
+    assert_eq!(output[0].timestamps, vec![2.0]);
+    assert_eq!(floats(&output[0]), vec![3.0]);
+    assert_eq!(output[1].timestamps, vec![3.0, 4.0]);
+    assert_eq!(floats(&output[1]), vec![1.0, 2.0]);
+    assert_eq!(output[2].timestamps, vec![5.0]);
+    assert_eq!(floats(&output[2]), vec![3.0]);
+    // Local window stays closed until the end
+    assert_eq!(output[3].timestamps, vec![6.0]);
+    assert_eq!(output[4].timestamps, vec![7.0]);
+    assert!(output[5].is_empty());
+
+    let attributes = string_attributes(processor.as_ref(), &input, &output);
+
+    assert_eq!(attribute(&attributes, "ZERO_WINDOW_PERIODS_IN"), Some("1"));
+    assert_eq!(attribute(&attributes, "STALLED_TIME_IN"), Some("3"));
+    assert_eq!(attribute(&attributes, "WINDOW_PROBES_IN"), Some("2"));
+    assert_eq!(attribute(&attributes, "ZERO_WINDOW_PERIODS_OUT"), Some("1"));
+    assert_eq!(attribute(&attributes, "STALLED_TIME_OUT"), Some("2"));
+    assert_eq!(attribute(&attributes, "WINDOW_PROBES_OUT"), Some("1"));
+    assert_eq!(attribute(&attributes, "STALLED_TIME"), Some("5"));
+    assert_eq!(attribute(&attributes, "KERNEL_MAX_PROBES"), Some("2"));
+}
@@ -0,0 +1,126 @@
+use tcbee_plugins::{PreProcessor, SeriesData, WasmPlugin, WasmProcessor};
+use ts_storage::DataValue;
+use std::sync::Arc;
+
+fn int_series(name: &str, values: &[i64]) -> SeriesData {
+    let mut series = SeriesData::new(name, DataValue::Int(0));
+    for (i, val) in values.iter().enumerate() {
+        series.push(i as f64, DataValue::Int(*val));
+    }
+    series
+}
+
+#[test]
+fn example_plugin() {
+    // Compile example plugin from text format
+    let wasm = wat::parse_file("examples/bytes_in_flight.wat").expect("Failed to compile example!");
+    let plugin = WasmPlugin::from_bytes("bytes_in_flight.wasm", &wasm).expect("Failed to load plugin!");
+
+    let processor = WasmProcessor::new(Arc::new(plugin));
+    assert_eq!(processor.receive_name(), "Bytes in flight");
+    assert_eq!(
+        processor.receive_required_timeseries(),
+        vec!["SND_NXT".to_string(), "SND_UNA".to_string()]
+    );
+
+    let input = vec![
+        int_series("SND_NXT", &[1000, 2000, 3000]),
+        int_series("SND_UNA", &[500, 1500]),
+    ];
+
+    let output = processor
+        .create_new_time_series(&input)
+        .expect("Plugin failed!");
+
+    // Shorter series limits the result
+    assert_eq!(output.len(), 1);
+    assert_eq!(output[0].name, "BYTES_IN_FLIGHT");
+    assert_eq!(output[0].timestamps, vec![0.0, 1.0]);
+    let values: Vec<i64> = output[0].data.iter().filter_map(|val| val.as_int()).collect();
+    assert_eq!(values, vec![500, 500]);
+}
+
+#[test]
+fn faulty_plugins() {
+    // Missing exports are rejected on load
+    let wasm = wat::parse_str("(module (memory (export \"memory\") 1))").unwrap();
+    assert!(WasmPlugin::from_bytes("empty.wasm", &wasm).is_err());
+
+    // Imports outside of the host ABI are rejected on load
+    let wasm = wat::parse_str(
+        "(module (import \"env\" \"open\" (func (param i32))) (memory (export \"memory\") 1))",
+    )
+    .unwrap();
+    assert!(WasmPlugin::from_bytes("import.wasm", &wasm).is_err());
+
+    // Metadata calls run out of fuel instead of blocking the caller
+    let wasm = wat::parse_str(
+        r#"(module
+            (memory (export "memory") 1)
+            (func (export "tcbee_name") (result i64) (loop $spin (br $spin)) (i64.const 0))
+            (func (export "tcbee_description") (result i64) (i64.const 0))
+            (func (export "tcbee_required") (result i64) (i64.const 0))
+            (func (export "tcbee_run") (result i32) (i32.const 0)))"#,
+    )
+    .unwrap();
+    assert!(WasmPlugin::from_bytes("spin.wasm", &wasm).is_err());
+
+    // Traps during a run return an error instead of partial series
+    let wasm = wat::parse_str(
+        r#"(module
+            (import "tcbee" "emit_series" (func $emit_series (param i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "TRAP")
+            (func (export "tcbee_name") (result i64) (i64.const 4))
+            (func (export "tcbee_description") (result i64) (i64.const 4))
+            (func (export "tcbee_required") (result i64) (i64.const 0))
+            (func (export "tcbee_run") (result i32)
+                (drop (call $emit_series (i32.const 0) (i32.const 4) (i32.const 1)))
+                unreachable))"#,
+    )
+    .unwrap();
+    let plugin = WasmPlugin::from_bytes("trap.wasm", &wasm).expect("Failed to load plugin!");
+    let processor = WasmProcessor::new(Arc::new(plugin));
+    assert!(processor.create_new_time_series(&Vec::new()).is_err());
+}
+
+#[test]
+fn emitted_points_are_limited() {
+    // Emits points until the host rejects one, the number of points is the result
+    let wasm = wat::parse_str(
+        r#"(module
+            (import "tcbee" "emit_series" (func $emit_series (param i32 i32 i32) (result i32)))
+            (import "tcbee" "emit_point" (func $emit_point (param i32 f64 f64) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "FLOOD")
+            (func (export "tcbee_name") (result i64) (i64.const 5))
+            (func (export "tcbee_description") (result i64) (i64.const 5))
+            (func (export "tcbee_required") (result i64) (i64.const 0))
+            (func (export "tcbee_run") (result i32)
+                (local $series i32)
+                (local $time f64)
+                (local.set $series (call $emit_series (i32.const 0) (i32.const 5) (i32.const 0)))
+                (block $full
+                    (loop $emit
+                        (br_if $full (call $emit_point (local.get $series) (local.get $time) (f64.const 1)))
+                        (local.set $time (f64.add (local.get $time) (f64.const 1)))
+                        (br $emit)))
+                (i32.const 0)))"#,
+    )
+    .unwrap();
+    let plugin = WasmPlugin::from_bytes("flood.wasm", &wasm).expect("Failed to load plugin!");
+    let processor = WasmProcessor::new(Arc::new(plugin));
+
+    // Small inputs may still emit the minimum
+    let output = processor
+        .create_new_time_series(&vec![int_series("SND_NXT", &[1, 2, 3])])
+        .expect("Plugin failed!");
+    assert_eq!(output[0].len(), 100_000);
+
+    // Larger inputs allow a multiple of their points
+    let values: Vec<i64> = (0..50_000).collect();
+    let output = processor
+        .create_new_time_series(&vec![int_series("SND_NXT", &values)])
+        .expect("Plugin failed!");
+    assert_eq!(output[0].len(), 200_000);
+}
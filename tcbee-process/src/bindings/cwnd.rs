@@ -3,7 +3,8 @@ use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use serde::Deserialize;
 use ts_storage::{DataValue, IpTuple};
 
-use crate::{db_writer::DBOperation, flow_tracker::{EventIndexer, AF_INET}, reader::FromBuffer};
+use crate::{db_writer::DBOperation, flow_tracker::{Direction, EventIndexer, AF_INET}, reader::FromBuffer};
+use crate::format::{insert_legacy_cookie, RecordFormat, RECORD_CWND};
 use arrayref::array_ref;
 
 #[repr(C)]
@@ -15,8 +16,8 @@ pub struct cwnd_trace_entry {
     pub dst_v6: [u8; 16usize],
     pub ports: u32,
     pub family: u16,
+    pub sock_cookie: u64,
     pub snd_cwnd: u32,
-    pub div: [u8; 4usize],
 }
 impl EventIndexer for cwnd_trace_entry {
     fn get_field(&self, index: usize) -> Option<DataValue> {
@@ -85,11 +86,11 @@ impl EventIndexer for cwnd_trace_entry {
     fn get_timestamp(&self) -> f64 {
         self.time as f64
     }
-    fn as_db_op(self) -> DBOperation {
-        DBOperation::Cwnd(self)
+    fn as_db_op(self, direction: Direction) -> DBOperation {
+        DBOperation::Cwnd(self, direction)
     }
-    fn get_struct_length(&self) -> usize {
-        62
+    fn get_sock_cookie(&self) -> Option<u64> {
+        Some(self.sock_cookie)
     }
 }
 
@@ -106,6 +107,15 @@ impl FromBuffer for cwnd_trace_entry {
         }
 
     }
-    const ENTRY_SIZE: usize = 62;
+    const ENTRY_SIZE: usize = 66;
 }
 
+impl RecordFormat for cwnd_trace_entry {
+    const RECORD_TYPE: u16 = RECORD_CWND;
+    const LAYOUT: &'static str = "time:u64,addr_v4:u64,src_v6:[u8;16],dst_v6:[u8;16],ports:u32,family:u16,sock_cookie:u64,snd_cwnd:u32";
+    const LEGACY_SIZE: Option<usize> = Some(58);
+
+    fn from_legacy(entry: &[u8]) -> Vec<u8> {
+        insert_legacy_cookie(entry)
+    }
+}
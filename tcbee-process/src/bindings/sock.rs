@@ -3,7 +3,8 @@ use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use serde::Deserialize;
 use ts_storage::{DataValue, IpTuple};
 
-use crate::{db_writer::DBOperation, flow_tracker::{EventIndexer, AF_INET}, reader::FromBuffer};
+use crate::{db_writer::DBOperation, flow_tracker::{Direction, EventIndexer, AF_INET}, reader::FromBuffer};
+use crate::format::{insert_legacy_cookie, RecordFormat, RECORD_SOCK};
 
 use arrayref::array_ref;
 #[repr(C)]
@@ -15,6 +16,7 @@ pub struct sock_trace_entry {
     pub dst_v6: [u8; 16usize],
     pub ports: u32,
     pub family: u16,
+    pub sock_cookie: u64,
     // SOCK Stats
     pub pacing_rate: u64,
     pub max_pacing_rate: u64,
@@ -45,7 +47,6 @@ pub struct sock_trace_entry {
     // TCP_SOCK -> tcp_options_received
     pub snd_wscale: u16,
     pub rcv_wscale: u16,
-    pub div: [u8; 4usize],
 }
 
 impl FromBuffer for sock_trace_entry {
@@ -61,7 +62,7 @@ impl FromBuffer for sock_trace_entry {
         }
 
     }
-    const ENTRY_SIZE: usize = 160;
+    const ENTRY_SIZE: usize = 164;
 }
 
 impl EventIndexer for sock_trace_entry {
@@ -203,10 +204,20 @@ impl EventIndexer for sock_trace_entry {
     fn get_timestamp(&self) -> f64 {
         self.time as f64
     }
-    fn as_db_op(self) -> DBOperation {
-        DBOperation::Socket(self)
+    fn as_db_op(self, direction: Direction) -> DBOperation {
+        DBOperation::Socket(self, direction)
     }
-    fn get_struct_length(&self) -> usize {
-        160
+    fn get_sock_cookie(&self) -> Option<u64> {
+        Some(self.sock_cookie)
+    }
+}
+
+impl RecordFormat for sock_trace_entry {
+    const RECORD_TYPE: u16 = RECORD_SOCK;
+    const LAYOUT: &'static str = "time:u64,addr_v4:u64,src_v6:[u8;16],dst_v6:[u8;16],ports:u32,family:u16,sock_cookie:u64,pacing_rate:u64,max_pacing_rate:u64,backoff:u8,rto:u32,ato:u32,rcv_mss:u16,snd_cwnd:u32,bytes_acked:u64,snd_ssthresh:u32,total_retrans:u32,probes:u8,lost:u32,sacked_out:u32,retrans:u32,rcv_ssthresh:u32,rttvar:u32,advmss:u16,reordering:u32,rcv_rtt:u32,rcv_space:u32,bytes_received:u64,segs_out:u32,segs_in:u32,snd_wscale:u16,rcv_wscale:u16";
+    const LEGACY_SIZE: Option<usize> = Some(156);
+
+    fn from_legacy(entry: &[u8]) -> Vec<u8> {
+        insert_legacy_cookie(entry)
     }
 }
@@ -0,0 +1,155 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use serde::Deserialize;
+use ts_storage::{DataValue, IpTuple};
+
+use crate::{
+    db_writer::DBOperation, flow_tracker::{Direction, EventIndexer, AF_INET}, reader::FromBuffer, shorten_to_ipv4, shorten_to_ipv6
+};
+use crate::format::{RecordFormat, RECORD_BAD_CSUM};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct tcp_bad_csum_entry {
+    pub time: u64,
+    // sockaddr_in6 of the segment, contains family, port and address
+    pub saddr: [u8; 28usize],
+    pub daddr: [u8; 28usize],
+}
+
+// Family is stored in host byte order, the port in network byte order
+fn sockaddr_family(addr: &[u8; 28]) -> u16 {
+    u16::from_le_bytes([addr[0], addr[1]])
+}
+fn sockaddr_port(addr: &[u8; 28]) -> i64 {
+    u16::from_be_bytes([addr[2], addr[3]]) as i64
+}
+
+impl FromBuffer for tcp_bad_csum_entry {
+    fn from_buffer(buf: &Vec<u8>) -> Self {
+        let try_deserialize = bincode::deserialize::<'_, tcp_bad_csum_entry>(buf);
+
+        if try_deserialize.is_err() {
+            tcp_bad_csum_entry::default()
+        } else {
+            try_deserialize.unwrap()
+        }
+    }
+    const ENTRY_SIZE: usize = 64;
+}
+
+impl EventIndexer for tcp_bad_csum_entry {
+    fn get_field(&self, index: usize) -> Option<DataValue> {
+        match index {
+            // Event series, every entry is a received segment with a bad checksum
+            0 => Some(DataValue::Boolean(true)),
+            _ => None, // TODO: better error handling
+        }
+    }
+    fn get_default_field(&self, index: usize) -> DataValue {
+        match index {
+            0 => DataValue::Boolean(false),
+            _ => panic!("Tried to access out of bounds index!"), // TODO: better error handling
+        }
+    }
+    fn get_field_name(&self, index: usize) -> &str {
+        match index {
+            0 => "BAD_CSUM",
+            _ => panic!("Tried to access out of bounds index!"), // TODO: better error handling
+        }
+    }
+    // The tracepoint has no socket, DBWriter maps the event to the latest stream of its tuple
+    fn get_ip_tuple(&self) -> IpTuple {
+        let src: IpAddr;
+        let dst: IpAddr;
+
+        if sockaddr_family(&self.saddr) == AF_INET {
+            src = IpAddr::V4(Ipv4Addr::from(shorten_to_ipv4(self.saddr)));
+            dst = IpAddr::V4(Ipv4Addr::from(shorten_to_ipv4(self.daddr)));
+        } else {
+            src = IpAddr::V6(Ipv6Addr::from(shorten_to_ipv6(self.saddr)));
+            dst = IpAddr::V6(Ipv6Addr::from(shorten_to_ipv6(self.daddr)));
+        }
+        IpTuple {
+            src,
+            dst,
+            sport: sockaddr_port(&self.saddr),
+            dport: sockaddr_port(&self.daddr),
+            l4proto: 6,
+        }
+    }
+    fn get_max_index(&self) -> usize {
+        0
+    }
+    fn get_timestamp(&self) -> f64 {
+        self.time as f64
+    }
+    fn as_db_op(self, direction: Direction) -> DBOperation {
+        DBOperation::BadCsum(self, direction)
+    }
+}
+
+impl RecordFormat for tcp_bad_csum_entry {
+    const RECORD_TYPE: u16 = RECORD_BAD_CSUM;
+    const LAYOUT: &'static str = "time:u64,saddr:[u8;28],daddr:[u8;28]";
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // sockaddr_in as filled by the tracepoint
+    fn sockaddr(addr: Ipv4Addr, port: u16) -> Vec<u8> {
+        let mut sockaddr = AF_INET.to_le_bytes().to_vec();
+        sockaddr.extend_from_slice(&port.to_be_bytes());
+        sockaddr.extend_from_slice(&addr.octets());
+        sockaddr.resize(28, 0);
+        sockaddr
+    }
+
+    // Serialized entry of a segment from 10.0.0.2:80 to 10.0.0.1:1000
+    pub(crate) fn payload(time: u64) -> Vec<u8> {
+        let mut payload = time.to_le_bytes().to_vec();
+        payload.extend_from_slice(&sockaddr(Ipv4Addr::new(10, 0, 0, 2), 80));
+        payload.extend_from_slice(&sockaddr(Ipv4Addr::new(10, 0, 0, 1), 1000));
+        payload
+    }
+
+    #[test]
+    fn tuple_contains_ports() {
+        let payload = payload(42);
+        assert_eq!(payload.len(), tcp_bad_csum_entry::ENTRY_SIZE);
+
+        let entry = tcp_bad_csum_entry::from_buffer(&payload);
+        assert_eq!(entry.get_timestamp(), 42.0);
+        assert_eq!(
+            entry.get_ip_tuple(),
+            IpTuple {
+                src: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)),
+                dst: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+                sport: 80,
+                dport: 1000,
+                l4proto: 6,
+            }
+        );
+    }
+
+    #[test]
+    fn ipv6_addresses() {
+        let addr = "fd00::1".parse::<Ipv6Addr>().unwrap();
+        let mut sockaddr = 10u16.to_le_bytes().to_vec();
+        sockaddr.extend_from_slice(&443u16.to_be_bytes());
+        sockaddr.extend_from_slice(&[0; 4]);
+        sockaddr.extend_from_slice(&addr.octets());
+        sockaddr.resize(28, 0);
+
+        let entry = tcp_bad_csum_entry {
+            time: 0,
+            saddr: sockaddr.clone().try_into().unwrap(),
+            daddr: sockaddr.try_into().unwrap(),
+        };
+        let tuple = entry.get_ip_tuple();
+        assert_eq!(tuple.src, IpAddr::V6(addr));
+        assert_eq!(tuple.sport, 443);
+    }
+}
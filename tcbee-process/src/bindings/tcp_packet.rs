@@ -3,7 +3,8 @@ use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use serde::Deserialize;
 use ts_storage::{DataValue, IpTuple};
 
-use crate::{db_writer::DBOperation, flow_tracker::EventIndexer, reader::FromBuffer};
+use crate::{db_writer::DBOperation, flow_tracker::{Direction, EventIndexer, HandshakeOptions}, reader::FromBuffer};
+use crate::format::{RecordFormat, RECORD_TCP_PACKET};
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Default, Deserialize)]
@@ -25,7 +26,149 @@ pub struct TcpPacket {
     pub flag_syn: bool,
     pub flag_fin: bool,
     pub checksum: u16,
-    pub div: [u8; 4usize],
+    // TCP options, fields are only valid if their bit in options is set
+    pub options: u8,
+    pub mss: u16,
+    pub wscale: u8,
+    // Number of valid SACK blocks, start and end of every block
+    pub sack_blocks: u8,
+    pub sack: [u32; 8usize],
+    pub tsval: u32,
+    pub tsecr: u32,
+    // Data bytes of the segment, IP payload without the TCP header
+    pub payload_len: u16,
+    // IPv4 identification, 0 for IPv6
+    pub ip_id: u16,
+    // TTL or hop limit
+    pub ttl: u8,
+    // DSCP of the IPv4 TOS or IPv6 traffic class
+    pub dscp: u8,
+    // TCP header length including options
+    pub tcp_hdr_len: u8,
+    // ECN codepoint of the IP header, 0 Not-ECT, 1 ECT(1), 2 ECT(0), 3 CE
+    pub ecn: u8,
+    pub flag_ece: bool,
+    pub flag_cwr: bool,
+}
+
+// Bits of TcpPacket.options, has to match tcbee-record/tcbee-common/src/bindings/tcp_header.rs
+pub const TCP_OPTION_MSS: u8 = 1;
+pub const TCP_OPTION_WSCALE: u8 = 2;
+pub const TCP_OPTION_SACK_PERMITTED: u8 = 4;
+pub const TCP_OPTION_SACK: u8 = 8;
+pub const TCP_OPTION_TIMESTAMP: u8 = 16;
+
+pub const MAX_SACK_BLOCKS: usize = 4;
+
+// Option kinds
+const TCPOPT_EOL: u8 = 0;
+const TCPOPT_NOP: u8 = 1;
+const TCPOPT_MSS: u8 = 2;
+const TCPOPT_WSCALE: u8 = 3;
+const TCPOPT_SACK_PERMITTED: u8 = 4;
+const TCPOPT_SACK: u8 = 5;
+const TCPOPT_TIMESTAMP: u8 = 8;
+
+impl TcpPacket {
+    // IPv6 packets have no IPv4 addresses
+    pub fn is_ipv4(&self) -> bool {
+        self.saddr != 0 && self.daddr != 0
+    }
+
+    pub fn has_option(&self, option: u8) -> bool {
+        self.options & option != 0
+    }
+
+    /// Parses the options of a TCP header, same as the XDP and TC probes
+    pub fn parse_options(&mut self, options: &[u8]) {
+        let mut offset = 0;
+        while offset < options.len() {
+            let kind = options[offset];
+            if kind == TCPOPT_EOL {
+                break;
+            }
+            if kind == TCPOPT_NOP {
+                offset += 1;
+                continue;
+            }
+
+            let Some(&size) = options.get(offset + 1) else {
+                break;
+            };
+            let size = size as usize;
+            if size < 2 || offset + size > options.len() {
+                break;
+            }
+            let value = &options[offset + 2..offset + size];
+            let u32_at = |i: usize| u32::from_be_bytes(value[i..i + 4].try_into().unwrap());
+
+            match kind {
+                TCPOPT_MSS if size == 4 => {
+                    self.mss = u16::from_be_bytes([value[0], value[1]]);
+                    self.options |= TCP_OPTION_MSS;
+                }
+                TCPOPT_WSCALE if size == 3 => {
+                    self.wscale = value[0];
+                    self.options |= TCP_OPTION_WSCALE;
+                }
+                TCPOPT_SACK_PERMITTED if size == 2 => self.options |= TCP_OPTION_SACK_PERMITTED,
+                TCPOPT_SACK if size >= 10 => {
+                    let blocks = ((size - 2) / 8).min(MAX_SACK_BLOCKS);
+                    for block in 0..blocks {
+                        self.sack[block * 2] = u32_at(block * 8);
+                        self.sack[block * 2 + 1] = u32_at(block * 8 + 4);
+                    }
+                    self.sack_blocks = blocks as u8;
+                    self.options |= TCP_OPTION_SACK;
+                }
+                TCPOPT_TIMESTAMP if size == 10 => {
+                    self.tsval = u32_at(0);
+                    self.tsecr = u32_at(4);
+                    self.options |= TCP_OPTION_TIMESTAMP;
+                }
+                _ => {}
+            }
+
+            offset += size;
+        }
+    }
+
+    /// Encodes the recorded options, padded to a multiple of 4 bytes
+    pub fn options_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        if self.has_option(TCP_OPTION_MSS) {
+            bytes.extend_from_slice(&[TCPOPT_MSS, 4]);
+            bytes.extend_from_slice(&self.mss.to_be_bytes());
+        }
+        if self.has_option(TCP_OPTION_SACK_PERMITTED) {
+            bytes.extend_from_slice(&[TCPOPT_SACK_PERMITTED, 2]);
+        }
+        if self.has_option(TCP_OPTION_TIMESTAMP) {
+            bytes.extend_from_slice(&[TCPOPT_TIMESTAMP, 10]);
+            bytes.extend_from_slice(&self.tsval.to_be_bytes());
+            bytes.extend_from_slice(&self.tsecr.to_be_bytes());
+        }
+        if self.has_option(TCP_OPTION_WSCALE) {
+            bytes.extend_from_slice(&[TCPOPT_WSCALE, 3, self.wscale]);
+        }
+        if self.has_option(TCP_OPTION_SACK) {
+            // All options together may not exceed 40 bytes
+            let space = (40 - bytes.len()).saturating_sub(2) / 8;
+            let blocks = (self.sack_blocks as usize).min(MAX_SACK_BLOCKS).min(space);
+            if blocks > 0 {
+                bytes.extend_from_slice(&[TCPOPT_SACK, (2 + blocks * 8) as u8]);
+                for value in self.sack[..blocks * 2].iter() {
+                    bytes.extend_from_slice(&value.to_be_bytes());
+                }
+            }
+        }
+
+        while bytes.len() % 4 != 0 {
+            bytes.push(TCPOPT_NOP);
+        }
+        bytes
+    }
 }
 
 impl FromBuffer for TcpPacket {
@@ -39,7 +182,7 @@ impl FromBuffer for TcpPacket {
         }
 
     }
-    const ENTRY_SIZE: usize = 74;
+    const ENTRY_SIZE: usize = 125;
 }
 
 impl EventIndexer for TcpPacket {
@@ -47,7 +190,8 @@ impl EventIndexer for TcpPacket {
         match index {
             0 => if self.seq > 0 {Some(DataValue::Int(self.seq as i64))} else {None},
             1 => if self.ack > 0 {Some(DataValue::Int(self.ack as i64))} else {None},
-            2 => if self.window > 0 {Some(DataValue::Int(self.window as i64))} else {None},
+            // Zero windows are stored, they stall the sender
+            2 => Some(DataValue::Int(self.window as i64)),
             // Only add Flags when true to save space
             // TODO: 
             3 => if self.flag_urg { Some(DataValue::Boolean(true)) } else {None},
@@ -57,6 +201,22 @@ impl EventIndexer for TcpPacket {
             7 => if self.flag_syn { Some(DataValue::Boolean(true)) } else {None},
             8 => if self.flag_fin { Some(DataValue::Boolean(true)) } else {None},
             9 => if self.checksum > 0 {Some(DataValue::Int(self.checksum as i64))} else {None},
+            // Options are only added if the packet carried them
+            10 => if self.has_option(TCP_OPTION_TIMESTAMP) {Some(DataValue::Int(self.tsval as i64))} else {None},
+            11 => if self.has_option(TCP_OPTION_TIMESTAMP) {Some(DataValue::Int(self.tsecr as i64))} else {None},
+            12 => if self.has_option(TCP_OPTION_SACK) {Some(DataValue::Int(self.sack_blocks as i64))} else {None},
+            // First block holds the most recently received data
+            13 => if self.has_option(TCP_OPTION_SACK) {Some(DataValue::Int(self.sack[0] as i64))} else {None},
+            14 => if self.has_option(TCP_OPTION_SACK) {Some(DataValue::Int(self.sack[1] as i64))} else {None},
+            // Also added for pure ACKs, tells them apart from data segments
+            15 => Some(DataValue::Int(self.payload_len as i64)),
+            16 => if self.is_ipv4() {Some(DataValue::Int(self.ip_id as i64))} else {None},
+            17 => Some(DataValue::Int(self.ttl as i64)),
+            18 => Some(DataValue::Int(self.dscp as i64)),
+            19 => Some(DataValue::Int(self.tcp_hdr_len as i64)),
+            20 => Some(DataValue::Int(self.ecn as i64)),
+            21 => if self.flag_ece { Some(DataValue::Boolean(true)) } else {None},
+            22 => if self.flag_cwr { Some(DataValue::Boolean(true)) } else {None},
             _ => None, // TODO: better error handling
         }
     }
@@ -72,6 +232,19 @@ impl EventIndexer for TcpPacket {
             7 => DataValue::Boolean(false),
             8 => DataValue::Boolean(false),
             9 => DataValue::Int(0),
+            10 => DataValue::Int(0),
+            11 => DataValue::Int(0),
+            12 => DataValue::Int(0),
+            13 => DataValue::Int(0),
+            14 => DataValue::Int(0),
+            15 => DataValue::Int(0),
+            16 => DataValue::Int(0),
+            17 => DataValue::Int(0),
+            18 => DataValue::Int(0),
+            19 => DataValue::Int(0),
+            20 => DataValue::Int(0),
+            21 => DataValue::Boolean(false),
+            22 => DataValue::Boolean(false),
             _ => panic!("Tried to access out of bounds index!"), // TODO: better error handling
         }
     }
@@ -87,6 +260,19 @@ impl EventIndexer for TcpPacket {
             7 => "FLAG_SYN",
             8 => "FLAG_FIN",
             9 => "CHECKSUM",
+            10 => "TSVAL",
+            11 => "TSECR",
+            12 => "SACK_BLOCKS",
+            13 => "SACK_START",
+            14 => "SACK_END",
+            15 => "PAYLOAD_LEN",
+            16 => "IP_ID",
+            17 => "TTL",
+            18 => "DSCP",
+            19 => "TCP_HDR_LEN",
+            20 => "ECN",
+            21 => "FLAG_ECE",
+            22 => "FLAG_CWR",
             _ => panic!("Tried to access out of bounds index!"), // TODO: better error handling
         }
     }
@@ -94,7 +280,7 @@ impl EventIndexer for TcpPacket {
         let src: IpAddr;
         let dst: IpAddr;
 
-        if self.saddr != 0 && self.daddr != 0 {
+        if self.is_ipv4() {
             src = IpAddr::V4(Ipv4Addr::from(self.saddr));
             dst = IpAddr::V4(Ipv4Addr::from(self.daddr));
         } else {
@@ -110,15 +296,40 @@ impl EventIndexer for TcpPacket {
         }
     }
     fn get_max_index(&self) -> usize {
-        9
+        22
     }
     fn get_timestamp(&self) -> f64 {
         self.time as f64
     }
-    fn as_db_op(self) -> DBOperation {
-        DBOperation::Packet(self)
+    fn as_db_op(self, direction: Direction) -> DBOperation {
+        DBOperation::Packet(self, direction)
+    }
+    fn get_handshake_options(&self) -> Option<HandshakeOptions> {
+        if !self.flag_syn {
+            return None;
+        }
+        Some(HandshakeOptions {
+            mss: self.has_option(TCP_OPTION_MSS).then_some(self.mss),
+            wscale: self.has_option(TCP_OPTION_WSCALE).then_some(self.wscale),
+            sack_permitted: self.has_option(TCP_OPTION_SACK_PERMITTED),
+        })
+    }
+    fn get_scaled_window(&self, scale: u8) -> Option<DataValue> {
+        // Windows of SYN packets are never scaled, scale is at most 14
+        let scale = if self.flag_syn { 0 } else { scale.min(14) };
+        Some(DataValue::Int((self.window as i64) << scale))
     }
-    fn get_struct_length(&self) -> usize {
-        74
+}
+
+impl RecordFormat for TcpPacket {
+    const RECORD_TYPE: u16 = RECORD_TCP_PACKET;
+    const LAYOUT: &'static str = "time:u64,saddr:u32,daddr:u32,saddr_v6:[u8;16],daddr_v6:[u8;16],sport:u16,dport:u16,seq:u32,ack:u32,window:u16,flag_urg:bool,flag_ack:bool,flag_psh:bool,flag_rst:bool,flag_syn:bool,flag_fin:bool,checksum:u16,options:u8,mss:u16,wscale:u8,sack_blocks:u8,sack:[u32;8],tsval:u32,tsecr:u32,payload_len:u16,ip_id:u16,ttl:u8,dscp:u8,tcp_hdr_len:u8,ecn:u8,flag_ece:bool,flag_cwr:bool";
+    const LEGACY_SIZE: Option<usize> = Some(70);
+
+    // Fields after checksum were added later, legacy packets have no options and payload length
+    fn from_legacy(entry: &[u8]) -> Vec<u8> {
+        let mut converted = entry.to_vec();
+        converted.resize(TcpPacket::ENTRY_SIZE, 0);
+        converted
     }
 }
@@ -4,8 +4,9 @@ use serde::Deserialize;
 use ts_storage::{DataValue, IpTuple};
 
 use crate::{
-    db_writer::DBOperation, flow_tracker::{EventIndexer, AF_INET}, reader::FromBuffer, shorten_to_ipv4, shorten_to_ipv6
+    db_writer::DBOperation, flow_tracker::{Direction, EventIndexer, AF_INET}, reader::FromBuffer, shorten_to_ipv4, shorten_to_ipv6
 };
+use crate::format::{RecordFormat, RECORD_TCP_PROBE};
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Default, Deserialize)]
@@ -26,7 +27,6 @@ pub struct TcpProbe {
     pub srtt: u32,
     pub rcv_wnd: u32,
     pub sock_cookie: u64,
-    pub div: [u8; 4usize],
 }
 
 impl FromBuffer for TcpProbe {
@@ -39,7 +39,7 @@ impl FromBuffer for TcpProbe {
             try_deserialize.unwrap()
         }
     }
-    const ENTRY_SIZE: usize = 116;
+    const ENTRY_SIZE: usize = 112;
 }
 
 impl EventIndexer for TcpProbe {
@@ -114,10 +114,17 @@ impl EventIndexer for TcpProbe {
     fn get_timestamp(&self) -> f64 {
         self.time as f64
     }
-    fn as_db_op(self) -> DBOperation {
-        DBOperation::Probe(self)
+    fn as_db_op(self, direction: Direction) -> DBOperation {
+        DBOperation::Probe(self, direction)
     }
-    fn get_struct_length(&self) -> usize {
-        116
+    fn get_sock_cookie(&self) -> Option<u64> {
+        Some(self.sock_cookie)
     }
 }
+
+impl RecordFormat for TcpProbe {
+    const RECORD_TYPE: u16 = RECORD_TCP_PROBE;
+    const LAYOUT: &'static str = "time:u64,saddr:[u8;28],daddr:[u8;28],sport:u16,dport:u16,family:u16,mark:u32,data_len:u16,snd_nxt:u32,snd_una:u32,snd_cwnd:u32,ssthresh:u32,snd_wnd:u32,srtt:u32,rcv_wnd:u32,sock_cookie:u64";
+    // Layout did not change
+    const LEGACY_SIZE: Option<usize> = Some(112);
+}
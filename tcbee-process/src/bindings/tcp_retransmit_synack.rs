@@ -0,0 +1,88 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use serde::Deserialize;
+use ts_storage::{DataValue, IpTuple};
+
+use crate::{db_writer::DBOperation, flow_tracker::{Direction, EventIndexer, AF_INET}, reader::FromBuffer};
+use crate::format::{RecordFormat, RECORD_RETRANSMIT_SYNACK};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct tcp_retransmit_synack_entry {
+    pub time: u64,
+    pub sport: u16,
+    pub dport: u16,
+    pub family: u16,
+    pub saddr: [u8; 4usize],
+    pub daddr: [u8; 4usize],
+    pub saddr_v6: [u8; 16usize],
+    pub daddr_v6: [u8; 16usize],
+}
+
+impl FromBuffer for tcp_retransmit_synack_entry {
+    fn from_buffer(buf: &Vec<u8>) -> Self {
+        let try_deserialize = bincode::deserialize::<'_, tcp_retransmit_synack_entry>(buf);
+
+        if try_deserialize.is_err() {
+            tcp_retransmit_synack_entry::default()
+        } else {
+            try_deserialize.unwrap()
+        }
+    }
+    const ENTRY_SIZE: usize = 54;
+}
+
+impl EventIndexer for tcp_retransmit_synack_entry {
+    fn get_field(&self, index: usize) -> Option<DataValue> {
+        match index {
+            // Event series, every entry is a retransmitted SYN-ACK
+            0 => Some(DataValue::Boolean(true)),
+            _ => None, // TODO: better error handling
+        }
+    }
+    fn get_default_field(&self, index: usize) -> DataValue {
+        match index {
+            0 => DataValue::Boolean(false),
+            _ => panic!("Tried to access out of bounds index!"), // TODO: better error handling
+        }
+    }
+    fn get_field_name(&self, index: usize) -> &str {
+        match index {
+            0 => "RETRANSMIT_SYNACK",
+            _ => panic!("Tried to access out of bounds index!"), // TODO: better error handling
+        }
+    }
+    fn get_ip_tuple(&self) -> IpTuple {
+        let src: IpAddr;
+        let dst: IpAddr;
+
+        if self.family == AF_INET {
+            src = IpAddr::V4(Ipv4Addr::from(self.saddr));
+            dst = IpAddr::V4(Ipv4Addr::from(self.daddr));
+        } else {
+            src = IpAddr::V6(Ipv6Addr::from(self.saddr_v6));
+            dst = IpAddr::V6(Ipv6Addr::from(self.daddr_v6));
+        }
+        IpTuple {
+            src: src,
+            dst: dst,
+            sport: self.sport as i64,
+            dport: self.dport as i64,
+            l4proto: 6,
+        }
+    }
+    fn get_max_index(&self) -> usize {
+        0
+    }
+    fn get_timestamp(&self) -> f64 {
+        self.time as f64
+    }
+    fn as_db_op(self, direction: Direction) -> DBOperation {
+        DBOperation::RetransmitSynack(self, direction)
+    }
+}
+
+impl RecordFormat for tcp_retransmit_synack_entry {
+    const RECORD_TYPE: u16 = RECORD_RETRANSMIT_SYNACK;
+    const LAYOUT: &'static str = "time:u64,sport:u16,dport:u16,family:u16,saddr:[u8;4],daddr:[u8;4],saddr_v6:[u8;16],daddr_v6:[u8;16]";
+}
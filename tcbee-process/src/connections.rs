@@ -0,0 +1,152 @@
+// Socket cookies of all recorded connections
+// Kernel socket events carry the cookie of their socket, packets and other events do not.
+// The index maps a connection tuple to the lifetime of every socket that used it,
+// so that packets of port-reused connections can be assigned to the right socket.
+use std::collections::HashMap;
+
+use tokio::sync::mpsc::Receiver;
+use ts_storage::IpTuple;
+
+use crate::{db_writer::DBOperation, flow_tracker::EventIndexer};
+
+#[derive(Debug, Clone, Copy)]
+struct Connection {
+    cookie: u64,
+    // First and last recorded timestamp of the socket
+    start: f64,
+    end: f64,
+}
+
+impl Connection {
+    // Distance of time to the lifetime of the socket, 0 if inside
+    fn distance(&self, time: f64) -> f64 {
+        if time < self.start {
+            self.start - time
+        } else if time > self.end {
+            time - self.end
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ConnectionIndex {
+    // Tuples are oriented from the local to the remote host
+    connections: HashMap<IpTuple, Vec<Connection>>,
+}
+
+impl ConnectionIndex {
+    pub fn new() -> ConnectionIndex {
+        ConnectionIndex::default()
+    }
+
+    /// Builds the index from all socket events received until the channel is closed
+    pub fn build(mut rx: Receiver<DBOperation>) -> ConnectionIndex {
+        let mut index = ConnectionIndex::new();
+
+        while let Some(operation) = rx.blocking_recv() {
+            match operation {
+                DBOperation::Probe(data, direction) => {
+                    index.add_event(&data, direction.connection_tuple(data.get_ip_tuple()))
+                }
+                DBOperation::Socket(data, direction) => {
+                    index.add_event(&data, direction.connection_tuple(data.get_ip_tuple()))
+                }
+                DBOperation::Cwnd(data, direction) => {
+                    index.add_event(&data, direction.connection_tuple(data.get_ip_tuple()))
+                }
+                // Other events carry no cookie
+                _ => {}
+            }
+        }
+
+        index
+    }
+
+    fn add_event<T: EventIndexer>(&mut self, event: &T, tuple: IpTuple) {
+        if let Some(cookie) = event.get_sock_cookie() {
+            self.add(tuple, cookie, event.get_timestamp());
+        }
+    }
+
+    pub fn add(&mut self, tuple: IpTuple, cookie: u64, time: f64) {
+        // Cookie 0 means the recording has no cookie for this socket
+        if cookie == 0 {
+            return;
+        }
+
+        let connections = self.connections.entry(tuple).or_default();
+
+        match connections.iter_mut().find(|conn| conn.cookie == cookie) {
+            Some(conn) => {
+                conn.start = conn.start.min(time);
+                conn.end = conn.end.max(time);
+            }
+            None => connections.push(Connection {
+                cookie,
+                start: time,
+                end: time,
+            }),
+        }
+    }
+
+    /// Returns the cookie of the socket that used the tuple closest to time
+    pub fn lookup(&self, tuple: &IpTuple, time: f64) -> Option<u64> {
+        let connections = self.connections.get(tuple)?;
+
+        connections
+            .iter()
+            .min_by(|a, b| a.distance(time).total_cmp(&b.distance(time)))
+            .map(|conn| conn.cookie)
+    }
+
+    pub fn num_connections(&self) -> usize {
+        self.connections.values().map(|conns| conns.len()).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn tuple(sport: i64) -> IpTuple {
+        IpTuple {
+            src: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            dst: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)),
+            sport,
+            dport: 80,
+            l4proto: 6,
+        }
+    }
+
+    #[test]
+    fn reused_ports() {
+        let mut index = ConnectionIndex::new();
+        // Two sockets use the same tuple one after another
+        index.add(tuple(1000), 1, 100.0);
+        index.add(tuple(1000), 1, 200.0);
+        index.add(tuple(1000), 2, 500.0);
+        index.add(tuple(1000), 2, 600.0);
+        assert_eq!(index.num_connections(), 2);
+
+        assert_eq!(index.lookup(&tuple(1000), 150.0), Some(1));
+        assert_eq!(index.lookup(&tuple(1000), 550.0), Some(2));
+        // Outside of both lifetimes, the closer socket wins
+        assert_eq!(index.lookup(&tuple(1000), 50.0), Some(1));
+        assert_eq!(index.lookup(&tuple(1000), 320.0), Some(1));
+        assert_eq!(index.lookup(&tuple(1000), 420.0), Some(2));
+        assert_eq!(index.lookup(&tuple(1000), 900.0), Some(2));
+
+        assert_eq!(index.lookup(&tuple(1001), 150.0), None);
+    }
+
+    #[test]
+    fn events_without_cookie() {
+        let mut index = ConnectionIndex::new();
+        index.add(tuple(1000), 0, 100.0);
+        assert_eq!(index.num_connections(), 0);
+        assert_eq!(index.lookup(&tuple(1000), 100.0), None);
+    }
+}
@@ -1,169 +1,295 @@
-use std::{collections::HashMap, error::Error};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    error::Error,
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::Instant,
+};
 
 use indicatif::ProgressBar;
 use log::error;
-use tokio::sync::mpsc::Receiver;
-use ts_storage::{database_factory, sqlite::SQLiteTSDB, DBBackend, IpTuple, TSDBInterface};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use ts_storage::{IpTuple, TSDBInterface};
 
 use crate::{
-    bindings::{sock::sock_trace_entry, cwnd::cwnd_trace_entry, tcp_packet::TcpPacket, tcp_probe::TcpProbe},
-    flow_tracker::{EventIndexer, EventType, FlowTracker},
+    bindings::{
+        cwnd::cwnd_trace_entry, sock::sock_trace_entry, tcp_bad_csum::tcp_bad_csum_entry,
+        tcp_packet::TcpPacket, tcp_probe::TcpProbe,
+        tcp_retransmit_synack::tcp_retransmit_synack_entry,
+    },
+    connections::ConnectionIndex,
+    filter::FlowFilter,
+    flow_tracker::{Direction, EventIndexer, EventType},
+    timestamps::TimeConverter,
+    writer_shard::{ShardOperation, WriterShard},
 };
 
+// Number of operations buffered per shard
+const SHARD_CHANNEL_SIZE: usize = 10000;
+
 #[derive(Debug)]
 pub enum DBOperation {
-    Packet(TcpPacket),
-    Probe(TcpProbe),
-    Socket(sock_trace_entry),
-    Cwnd(cwnd_trace_entry)
+    Packet(TcpPacket, Direction),
+    Probe(TcpProbe, Direction),
+    Socket(sock_trace_entry, Direction),
+    Cwnd(cwnd_trace_entry, Direction),
+    RetransmitSynack(tcp_retransmit_synack_entry, Direction),
+    BadCsum(tcp_bad_csum_entry, Direction),
+    // Write all buffered events to the database
+    Flush,
 }
 
+// Streams are identified by tuple and socket cookie, 0 if the socket is unknown
+pub type StreamKey = (IpTuple, u64);
+
+// Assigns events to flows and passes them to the shard that writes the flow
 pub struct DBWriter {
-    db: Box<dyn TSDBInterface + Send>,
-    streams: HashMap<IpTuple, FlowTracker>,
+    // Known streams, every stream is written by exactly one shard
+    streams: HashSet<StreamKey>,
+    // Cookie of the latest stream per tuple, for events without a socket
+    latest_streams: HashMap<IpTuple, u64>,
     rx: Receiver<DBOperation>,
     status: ProgressBar,
     num_flows: i32,
+    // Store ingress and egress of a connection as separate flows
+    per_direction: bool,
+    // Sockets of all connections, assigns events without a cookie to a socket
+    connections: ConnectionIndex,
+    // Events of other flows or outside the time window are not imported
+    filter: FlowFilter,
+    // Shards are moved to their threads on run
+    shards: Vec<WriterShard>,
+    shard_tx: Vec<Sender<ShardOperation>>,
+    // Points written by all shards
+    rows: Arc<AtomicU64>,
+    last_report: Instant,
+    last_rows: u64,
 }
 
 impl DBWriter {
     pub fn new(
-        backend: DBBackend,
+        db: Box<dyn TSDBInterface + Send>,
         rx: Receiver<DBOperation>,
         status: ProgressBar,
+        time: TimeConverter,
+        per_direction: bool,
+        connections: ConnectionIndex,
+        filter: FlowFilter,
+        num_shards: usize,
     ) -> Result<DBWriter, Box<dyn Error>> {
-        let db: Box<dyn TSDBInterface + Send> =
-            database_factory::<SQLiteTSDB>(backend)?;
+        let rows = Arc::new(AtomicU64::new(0));
 
-        let streams: HashMap<IpTuple, FlowTracker> = HashMap::new();
+        // Every shard writes over its own connection
+        let mut shard_dbs = Vec::with_capacity(num_shards.max(1));
+        for _ in 1..num_shards.max(1) {
+            shard_dbs.push(db.new_connection()?);
+        }
+        shard_dbs.push(db);
+
+        let mut shards = Vec::new();
+        let mut shard_tx = Vec::new();
+        for shard_db in shard_dbs {
+            let (tx, shard_rx) = mpsc::channel::<ShardOperation>(SHARD_CHANNEL_SIZE);
+            shards.push(WriterShard::new(
+                shard_db,
+                shard_rx,
+                time,
+                filter.clone(),
+                rows.clone(),
+            ));
+            shard_tx.push(tx);
+        }
 
         status.set_message(format!("Tracking {} Flows",0));
 
         Ok(DBWriter {
-            db,
-            streams,
+            streams: HashSet::new(),
+            latest_streams: HashMap::new(),
             rx,
             status,
-            num_flows: 0
+            num_flows: 0,
+            per_direction,
+            connections,
+            filter,
+            shards,
+            shard_tx,
+            rows,
+            last_report: Instant::now(),
+            last_rows: 0,
         })
     }
 
-    pub fn setup_new_stream(&mut self, tuple: &IpTuple) -> Result<(), Box<dyn Error>>  {
-        // Insert stream if not known
-        if !self.streams.contains_key(tuple) {
-            let new_tracker = FlowTracker::new(&self.db, tuple);
-
-            // TODO: remove unwrap, error handling!
-            self.streams.insert(tuple.clone(), new_tracker);
-
-            // Update progress message!
+    pub fn setup_new_stream(&mut self, key: &StreamKey) {
+        // Insert stream if not known, the flow is created by its shard
+        if self.streams.insert(key.clone()) {
+            self.latest_streams.insert(key.0.clone(), key.1);
             self.num_flows += 1;
-            self.status.set_message(format!("Tracking {} Flows",self.num_flows));
         }
-
-        Ok(())
     }
 
-    pub fn run(&mut self) -> Result<(), Box<dyn Error>> {
-        // Time of first entry, will be used to normalize all other times
-        // TODO: if order is scrambled, does this fail?
-        let time_base: f64 = 0.0;
-
-        while let Some(event) = self.rx.blocking_recv() {
-            self.status.inc(1);
-            match event {
-                DBOperation::Packet(data) => {
-                    if data.div != 0xFFFFFFFFu32.to_be_bytes() {
-                        panic!("Misaligned PACKET: {:?}. Something went horribly wrong during recording!",data);
-                    }
-
-                    let tuple = data.get_ip_tuple();
-
-                    self.setup_new_stream(&tuple)?;
+    // Bad checksum events carry no cookie, use the socket open at their time or the latest stream of the tuple
+    fn find_stream_of_bad_csum<T: EventIndexer>(
+        &mut self,
+        event: &T,
+        direction: Direction,
+        tuple: IpTuple,
+    ) -> StreamKey {
+        let cookie = match self.find_cookie(event, direction) {
+            0 => self.latest_streams.get(&tuple).copied().unwrap_or(0),
+            cookie => cookie,
+        };
+        (tuple, cookie)
+    }
 
-                    let tracker = self.streams.get_mut(&tuple).unwrap();
+    // Socket events carry their cookie, all other events are matched by tuple and time
+    fn find_cookie<T: EventIndexer>(&mut self, event: &T, direction: Direction) -> u64 {
+        let connection = direction.connection_tuple(event.get_ip_tuple());
 
-                    let res = tracker.add_event(&self.db, EventType::Packet, &data);
+        if let Some(cookie) = event.get_sock_cookie().filter(|cookie| *cookie != 0) {
+            // Sockets are only known from their events when following a recording
+            self.connections.add(connection, cookie, event.get_timestamp());
+            return cookie;
+        }
 
-                    if res.is_err() {
-                        error!(
-                            "Failed to trace packet: {:?}. Error: {}",
-                            data,
-                            res.err().unwrap()
-                        );
-                    }
-                },
-                DBOperation::Probe(data) => {
-                    if data.div != 0xFFFFFFFFu32.to_be_bytes() {
-                        panic!("Misaligned PROBE: {:?}. Something went horribly wrong during recording!",data);
-                    }
+        self.connections
+            .lookup(&connection, event.get_timestamp())
+            .unwrap_or(0)
+    }
 
-                    let tuple = data.get_ip_tuple();
-                    self.setup_new_stream(&tuple)?;
+    // Returns the stream of the event and the suffix of its series names, None if it is filtered
+    fn route<T: EventIndexer>(
+        &mut self,
+        etype: EventType,
+        event: &T,
+        direction: Direction,
+    ) -> Option<(StreamKey, &'static str)> {
+        self.status.inc(1);
+
+        // Either keep the recorded orientation or merge both directions into one flow
+        let (tuple, suffix) = if self.per_direction {
+            (event.get_ip_tuple(), "")
+        } else {
+            (
+                direction.connection_tuple(event.get_ip_tuple()),
+                direction.suffix(),
+            )
+        };
+
+        let key = match etype {
+            EventType::BadCsum => self.find_stream_of_bad_csum(event, direction, tuple),
+            _ => (tuple, self.find_cookie(event, direction)),
+        };
+
+        // Checked after the cookie lookup, sockets are still indexed when following a recording
+        if !self.filter.matches_tuple(&key.0) || !self.filter.matches_time(event.get_timestamp()) {
+            return None;
+        }
 
-                    let tracker = self.streams.get_mut(&tuple).unwrap();
+        self.setup_new_stream(&key);
 
-                    let res = tracker.add_event(&self.db, EventType::TcpProbe, &data);
+        Some((key, suffix))
+    }
 
-                    if res.is_err() {
-                        error!(
-                            "Failed to trace packet: {:?}. Error: {}",
-                            data,
-                            res.err().unwrap()
-                        );
-                    }
+    fn shard_of(&self, key: &StreamKey) -> usize {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        (hasher.finish() % self.shard_tx.len() as u64) as usize
+    }
 
-                    //println!("Probe {:?}",data.ssthresh);
-                },
-                DBOperation::Socket(sock) => {
-                    if sock.div != 0xFFFFFFFFu32.to_be_bytes() {
-                        panic!("Misaligned SOCKET: {:?}. Something went horribly wrong during recording!",sock);
-                    }
+    // Updates the progress message at most once per second
+    fn update_status(&mut self) {
+        let elapsed = self.last_report.elapsed().as_secs_f64();
+        if elapsed < 1.0 {
+            return;
+        }
 
-                    let tuple = sock.get_ip_tuple();
-                    self.setup_new_stream(&tuple)?;
+        let rows = self.rows.load(Ordering::Relaxed);
+        let rate = (rows - self.last_rows) as f64 / elapsed;
+        self.status.set_message(format!(
+            "Tracking {} Flows, {:.0} rows/s",
+            self.num_flows, rate
+        ));
 
-                    let tracker = self.streams.get_mut(&tuple).unwrap();
+        self.last_rows = rows;
+        self.last_report = Instant::now();
+    }
 
-                    let res = tracker.add_event(&self.db, EventType::Socket, &sock);
+    /// Returns an error if any shard failed, its flows are missing events in the database
+    pub fn run(&mut self) -> Result<(), Box<dyn Error>> {
+        let threads: Vec<thread::JoinHandle<Result<(), String>>> = self
+            .shards
+            .drain(..)
+            .map(|mut shard| {
+                thread::spawn(move || {
+                    shard.run().map_err(|err| {
+                        error!("DB shard stopping on error! Error: {}", err);
+                        err.to_string()
+                    })
+                })
+            })
+            .collect();
 
-                    if res.is_err() {
-                        error!(
-                            "Failed to trace socket: {:?}. Error: {}",
-                            sock,
-                            res.err().unwrap()
-                        );
-                    }
-                },
-                DBOperation::Cwnd(cwnd) => {
-                    if cwnd.div != 0xFFFFFFFFu32.to_be_bytes() {
-                        panic!("Misaligned CWND: {:?}. Something went horribly wrong during recording!",cwnd);
+        while let Some(event) = self.rx.blocking_recv() {
+            let routed = match &event {
+                DBOperation::Packet(data, direction) => {
+                    self.route(EventType::Packet, data, *direction)
+                }
+                DBOperation::Probe(data, direction) => {
+                    self.route(EventType::TcpProbe, data, *direction)
+                }
+                DBOperation::Socket(sock, direction) => {
+                    self.route(EventType::Socket, sock, *direction)
+                }
+                DBOperation::Cwnd(cwnd, direction) => {
+                    self.route(EventType::Cwnd, cwnd, *direction)
+                }
+                DBOperation::RetransmitSynack(synack, direction) => {
+                    self.route(EventType::RetransmitSynack, synack, *direction)
+                }
+                DBOperation::BadCsum(csum, direction) => {
+                    self.route(EventType::BadCsum, csum, *direction)
+                }
+                DBOperation::Flush => {
+                    for tx in self.shard_tx.iter() {
+                        let _ = tx.blocking_send(ShardOperation::Flush);
                     }
+                    self.update_status();
+                    continue;
+                }
+            };
+            let Some((key, suffix)) = routed else {
+                self.update_status();
+                continue;
+            };
+
+            let shard = self.shard_of(&key);
+            if self.shard_tx[shard]
+                .blocking_send(ShardOperation::Event(key, suffix, event))
+                .is_err()
+            {
+                error!("DB shard {} stopped, dropping remaining events!", shard);
+                break;
+            }
 
-                    let tuple = cwnd.get_ip_tuple();
-                    self.setup_new_stream(&tuple)?;
-
-                    let tracker = self.streams.get_mut(&tuple).unwrap();
-
-                    let res = tracker.add_event(&self.db, EventType::Cwnd, &cwnd);
+            self.update_status();
+        }
 
-                    if res.is_err() {
-                        error!(
-                            "Failed to trace socket: {:?}. Error: {}",
-                            cwnd,
-                            res.err().unwrap()
-                        );
-                    }
-                },
-                 //let a = sock.get_ip_tuple();
-                  //println!("Socket: {:?}",sock.get_ip_tuple());
+        // This is reached when all tx channels are dropped, stop shards to flush files!
+        self.shard_tx.clear();
+        let mut failed = Vec::new();
+        for (shard, t) in threads.into_iter().enumerate() {
+            match t.join() {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => failed.push(format!("shard {}: {}", shard, err)),
+                Err(_) => failed.push(format!("shard {} panicked", shard)),
             }
         }
-
-        // This is reached when all tx channels are dropped, flush files!
-        for (tuple, tracker) in self.streams.iter_mut() {
-            tracker.flush(&self.db);
-            //println!("Stream: {:?} - Tracker: {:?}",tuple,tracker);
+        if !failed.is_empty() {
+            return Err(format!("Database writer failed, {}", failed.join(", ")).into());
         }
         Ok(())
     }
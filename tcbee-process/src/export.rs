@@ -0,0 +1,346 @@
+// Export of packet headers to pcapng files, e.g. to open them in Wireshark
+// Packets are either read from the xdp.tcp/tc.tcp files of a recording
+// or reconstructed from the packet series of a processed database.
+use std::{collections::BTreeMap, error::Error, net::IpAddr};
+
+use log::info;
+use tokio::sync::mpsc::Receiver;
+use ts_storage::{DataValue, Flow, TSDBInterface};
+
+use crate::{
+    bindings::tcp_packet::{TcpPacket, TCP_OPTION_SACK, TCP_OPTION_TIMESTAMP},
+    db_writer::DBOperation,
+    flow_tracker::{Direction, EventIndexer},
+    pcap::PcapNgWriter,
+    timestamps::TimeConverter,
+};
+
+// Only packets with one of these ports as source or destination are exported, all if empty
+fn port_selected(ports: &[i64], packet: &TcpPacket) -> bool {
+    ports.is_empty()
+        || ports.contains(&(packet.sport as i64))
+        || ports.contains(&(packet.dport as i64))
+}
+
+/// Writes the packets read from a recording, returns the number of exported packets
+/// Packets are sorted by time as every probe writes its own file
+pub fn export_recording(
+    mut rx: Receiver<DBOperation>,
+    output: &str,
+    ports: &[i64],
+    time: TimeConverter,
+) -> Result<u64, Box<dyn Error>> {
+    let mut packets: Vec<(u64, Direction, TcpPacket)> = Vec::new();
+
+    while let Some(operation) = rx.blocking_recv() {
+        if let DBOperation::Packet(packet, direction) = operation {
+            if port_selected(ports, &packet) {
+                let timestamp = time.convert(packet.get_timestamp(), 0.0) as u64;
+                packets.push((timestamp, direction, packet));
+            }
+        }
+    }
+    packets.sort_by_key(|(timestamp, _, _)| *timestamp);
+
+    let mut writer = PcapNgWriter::create(output)?;
+    for (timestamp, direction, packet) in packets.iter() {
+        writer.write_packet(packet, Some(*direction), *timestamp)?;
+    }
+    Ok(writer.finish()?)
+}
+
+// Series name suffixes of merged flows, see Direction::suffix
+fn split_direction(name: &str) -> (&str, Option<Direction>) {
+    for direction in [Direction::Ingress, Direction::Egress] {
+        if let Some(field) = name.strip_suffix(direction.suffix()) {
+            return (field, Some(direction));
+        }
+    }
+    (name, None)
+}
+
+// Sets the packet field with the given index of TcpPacket::get_field
+fn set_packet_field(packet: &mut TcpPacket, index: usize, value: &DataValue) {
+    let int = value.as_int().unwrap_or(0);
+    let flag = matches!(value, DataValue::Boolean(true));
+    match index {
+        0 => packet.seq = int as u32,
+        1 => packet.ack = int as u32,
+        2 => packet.window = int as u16,
+        3 => packet.flag_urg = flag,
+        4 => packet.flag_ack = flag,
+        5 => packet.flag_psh = flag,
+        6 => packet.flag_rst = flag,
+        7 => packet.flag_syn = flag,
+        8 => packet.flag_fin = flag,
+        9 => packet.checksum = int as u16,
+        10 => {
+            packet.tsval = int as u32;
+            packet.options |= TCP_OPTION_TIMESTAMP;
+        }
+        11 => {
+            packet.tsecr = int as u32;
+            packet.options |= TCP_OPTION_TIMESTAMP;
+        }
+        // Only the first SACK block is stored
+        12 => {}
+        13 => {
+            packet.sack[0] = int as u32;
+            packet.sack_blocks = 1;
+            packet.options |= TCP_OPTION_SACK;
+        }
+        14 => {
+            packet.sack[1] = int as u32;
+            packet.sack_blocks = 1;
+            packet.options |= TCP_OPTION_SACK;
+        }
+        15 => packet.payload_len = int as u16,
+        16 => packet.ip_id = int as u16,
+        17 => packet.ttl = int as u8,
+        18 => packet.dscp = int as u8,
+        // Options are rebuilt from their own series
+        19 => {}
+        20 => packet.ecn = int as u8,
+        21 => packet.flag_ece = flag,
+        22 => packet.flag_cwr = flag,
+        _ => {}
+    }
+}
+
+// Packet with the addresses of the flow, oriented in the direction it was sent
+fn packet_template(flow: &Flow, direction: Option<Direction>) -> TcpPacket {
+    // Merged flows are stored local -> remote, ingress packets were sent by the remote host
+    let tuple = match direction {
+        Some(Direction::Ingress) => Direction::Ingress.connection_tuple(flow.tuple.clone()),
+        _ => flow.tuple.clone(),
+    };
+
+    let mut packet = TcpPacket {
+        sport: tuple.sport as u16,
+        dport: tuple.dport as u16,
+        ..Default::default()
+    };
+    match (tuple.src, tuple.dst) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            packet.saddr = u32::from(src);
+            packet.daddr = u32::from(dst);
+        }
+        (src, dst) => {
+            packet.saddr_v6 = match src {
+                IpAddr::V4(src) => src.to_ipv6_mapped().octets(),
+                IpAddr::V6(src) => src.octets(),
+            };
+            packet.daddr_v6 = match dst {
+                IpAddr::V4(dst) => dst.to_ipv6_mapped().octets(),
+                IpAddr::V6(dst) => dst.octets(),
+            };
+        }
+    }
+    packet
+}
+
+// Rebuilds the packets of a flow from its series, points with the same timestamp belong to one packet
+pub fn flow_packets(
+    db: &dyn TSDBInterface,
+    flow: &Flow,
+) -> Result<Vec<(u64, Option<Direction>, TcpPacket)>, Box<dyn Error>> {
+    let names: Vec<String> = (0..=TcpPacket::default().get_max_index())
+        .map(|i| TcpPacket::default().get_field_name(i).to_string())
+        .collect();
+
+    // Ordered by time, then by direction
+    // Keyed by the bits of the stored timestamp, repeated nanoseconds were made unique with next_up.
+    // Timestamps are not negative, so their bits sort like the values.
+    let mut packets: BTreeMap<(u64, u8), (Option<Direction>, TcpPacket)> = BTreeMap::new();
+
+    let series: Vec<_> = db.list_time_series(flow)?.collect();
+    for ts in series.iter() {
+        let (field, direction) = split_direction(&ts.name);
+        let Some(index) = names.iter().position(|name| name == field) else {
+            continue;
+        };
+
+        let direction_key = match direction {
+            Some(Direction::Ingress) => 0,
+            Some(Direction::Egress) => 1,
+            _ => 2,
+        };
+
+        for point in db.get_data_points(ts)? {
+            let (_, packet) = packets
+                .entry((point.timestamp.to_bits(), direction_key))
+                .or_insert_with(|| (direction, packet_template(flow, direction)));
+            set_packet_field(packet, index, &point.value);
+        }
+    }
+
+    Ok(packets
+        .into_iter()
+        .map(|((bits, _), (direction, packet))| (f64::from_bits(bits) as u64, direction, packet))
+        .collect())
+}
+
+/// Writes the packets stored in a database, returns the number of exported packets
+/// Timestamps are written as stored, use a database imported with --time utc for wall clock times
+pub fn export_database(
+    db: &dyn TSDBInterface,
+    output: &str,
+    ports: &[i64],
+    flow_ids: &[i64],
+) -> Result<u64, Box<dyn Error>> {
+    let flows: Vec<Flow> = db
+        .list_flows()?
+        .filter(|flow| flow_ids.is_empty() || flow.id.is_some_and(|id| flow_ids.contains(&id)))
+        .filter(|flow| {
+            ports.is_empty()
+                || ports.contains(&flow.tuple.sport)
+                || ports.contains(&flow.tuple.dport)
+        })
+        .collect();
+
+    let mut packets = Vec::new();
+    for flow in flows.iter() {
+        packets.extend(flow_packets(db, flow)?);
+    }
+    packets.sort_by_key(|(timestamp, _, _)| *timestamp);
+
+    info!(
+        "Exporting {} packets of {} flows",
+        packets.len(),
+        flows.len()
+    );
+
+    let mut writer = PcapNgWriter::create(output)?;
+    for (timestamp, direction, packet) in packets.iter() {
+        writer.write_packet(packet, *direction, *timestamp)?;
+    }
+    Ok(writer.finish()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use tokio::sync::mpsc;
+    use ts_storage::{DataPoint, IpTuple};
+
+    use super::*;
+    use crate::{
+        pcap::CaptureReader,
+        test_util::{temp_db, TempPath},
+        timestamps::TimeMode,
+    };
+
+    fn add_series(db: &dyn TSDBInterface, flow: &Flow, name: &str, points: &[(f64, i64)]) {
+        let series = db
+            .create_time_series(flow, name, DataValue::Int(0))
+            .unwrap();
+        let points: Vec<DataPoint> = points
+            .iter()
+            .map(|(timestamp, value)| DataPoint {
+                timestamp: *timestamp,
+                value: DataValue::Int(*value),
+            })
+            .collect();
+        db.insert_multiple_points(&series, &points).unwrap();
+    }
+
+    #[test]
+    fn packets_of_a_merged_flow() {
+        let (_file, db) = temp_db("export-flow");
+        let flow = db
+            .create_flow(&IpTuple {
+                src: "10.0.0.1".parse().unwrap(),
+                dst: "10.0.0.2".parse().unwrap(),
+                sport: 1000,
+                dport: 80,
+                l4proto: 6,
+            })
+            .unwrap();
+
+        // Two outgoing packets in the same nanosecond, the second one was moved by next_up
+        let repeated = 100.0_f64.next_up();
+        add_series(
+            &db,
+            &flow,
+            "SEQ_NUM_OUT",
+            &[(100.0, 1), (repeated, 2), (300.0, 3)],
+        );
+        add_series(&db, &flow, "WINDOW_OUT", &[(100.0, 10), (repeated, 20)]);
+        add_series(&db, &flow, "ACK_NUM_IN", &[(100.0, 5)]);
+        // Not a packet field
+        add_series(&db, &flow, "SND_CWND", &[(200.0, 7)]);
+
+        let packets = flow_packets(&db, &flow).unwrap();
+        let summary: Vec<(u64, Option<Direction>, u32, u32, u16)> = packets
+            .iter()
+            .map(|(timestamp, direction, packet)| {
+                (
+                    *timestamp,
+                    *direction,
+                    packet.seq,
+                    packet.ack,
+                    packet.window,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (100, Some(Direction::Ingress), 0, 5, 0),
+                (100, Some(Direction::Egress), 1, 0, 10),
+                (100, Some(Direction::Egress), 2, 0, 20),
+                (300, Some(Direction::Egress), 3, 0, 0),
+            ]
+        );
+
+        // Ingress packets were sent by the remote host
+        let (_, _, incoming) = &packets[0];
+        assert_eq!((incoming.sport, incoming.dport), (80, 1000));
+        assert_eq!(incoming.saddr, u32::from(Ipv4Addr::new(10, 0, 0, 2)));
+    }
+
+    #[test]
+    fn recording_is_sorted_and_filtered() {
+        let packet = |time: u64, sport: u16| TcpPacket {
+            time,
+            saddr: u32::from(Ipv4Addr::new(10, 0, 0, 1)),
+            daddr: u32::from(Ipv4Addr::new(10, 0, 0, 2)),
+            sport,
+            dport: 80,
+            ..Default::default()
+        };
+
+        // Every probe writes its own file, their packets arrive out of order
+        let (tx, rx) = mpsc::channel(10);
+        for (time, sport, direction) in [
+            (300, 1000, Direction::Egress),
+            (100, 1000, Direction::Ingress),
+            (200, 2000, Direction::Egress),
+            (150, 1000, Direction::Egress),
+        ] {
+            tx.try_send(DBOperation::Packet(packet(time, sport), direction))
+                .unwrap();
+        }
+        drop(tx);
+
+        let output = TempPath::new("export-recording.pcapng");
+        let converter = TimeConverter::for_capture(TimeMode::Boot, 0);
+        let exported = export_recording(rx, &output.as_string(), &[1000], converter).unwrap();
+        assert_eq!(exported, 3);
+
+        let mut capture = CaptureReader::open(&output.as_string()).unwrap();
+        let mut frames = Vec::new();
+        while let Some(frame) = capture.next_frame().unwrap() {
+            frames.push((frame.timestamp, frame.direction));
+        }
+        assert_eq!(
+            frames,
+            vec![
+                (100, Some(Direction::Ingress)),
+                (150, Some(Direction::Egress)),
+                (300, Some(Direction::Egress)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,418 @@
+// Metrics of flows that compete for the same bottleneck
+// Throughput of every flow is the PAYLOAD_LEN of its packets per window, both directions summed up.
+// Results are stored in a synthetic group flow without addresses and with protocol 0,
+// marked by the FAIRNESS_GROUP attribute. Its socket cookie is the next one that is free
+// for this tuple, so captured flows with the same tuple are never reused as a group.
+// Timestamps of all flows have to use the same clock, i.e. not --time flow.
+use std::{
+    collections::HashSet,
+    error::Error,
+    net::{IpAddr, Ipv4Addr},
+};
+
+use log::info;
+use tcbee_plugins::{load_required_series, store_series, SeriesData};
+use ts_storage::{DataValue, Flow, FlowAttribute, IpTuple, TSDBInterface};
+
+// Flows are converged once the fairness index stays above this for CONVERGED_WINDOWS windows
+const CONVERGED_JAIN_INDEX: f64 = 0.9;
+const CONVERGED_WINDOWS: usize = 5;
+
+// Bytes per window of a single flow
+struct FlowThroughput {
+    id: i64,
+    first: f64,
+    last: f64,
+    bytes: Vec<f64>,
+}
+
+/// Jain's fairness index, 1 if all values are equal and 1/n if one gets everything
+pub fn jain_index(values: &[f64]) -> Option<f64> {
+    let sum: f64 = values.iter().sum();
+    let squares: f64 = values.iter().map(|value| value * value).sum();
+    if values.is_empty() || squares <= 0.0 {
+        return None;
+    }
+    Some(sum * sum / (values.len() as f64 * squares))
+}
+
+// Loads the packets of a flow, every packet has a PAYLOAD_LEN
+fn flow_payload(db: &dyn TSDBInterface, flow: &Flow) -> Result<Vec<(f64, f64)>, Box<dyn Error>> {
+    let names: Vec<String> = db
+        .list_time_series(flow)?
+        .map(|ts| ts.name)
+        .filter(|name| name.starts_with("PAYLOAD_LEN"))
+        .collect();
+
+    let mut payload: Vec<(f64, f64)> = Vec::new();
+    for series in load_required_series(db, flow, &names)? {
+        for (timestamp, value) in series.timestamps.iter().zip(series.data.iter()) {
+            payload.push((*timestamp, value.as_int().unwrap_or(0) as f64));
+        }
+    }
+    payload.sort_by(|a, b| a.0.total_cmp(&b.0));
+    Ok(payload)
+}
+
+// Tuple of all group flows
+fn group_tuple() -> IpTuple {
+    IpTuple {
+        src: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        dst: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        sport: 0,
+        dport: 0,
+        l4proto: 0,
+    }
+}
+
+// Next socket cookie that no flow with the group tuple uses, groups or not
+fn next_group(db: &dyn TSDBInterface) -> Result<i64, Box<dyn Error>> {
+    let tuple = group_tuple();
+    Ok(db
+        .list_flows()?
+        .filter(|flow| flow.tuple == tuple)
+        .filter_map(|flow| flow.sock_cookie)
+        .max()
+        .unwrap_or(0)
+        + 1)
+}
+
+/// Stores the fairness metrics of the flows with the given ids in a new group flow
+/// window is the length of a throughput window in nanoseconds, capacity of the link in bit/s if known
+pub fn compute_fairness(
+    db: &dyn TSDBInterface,
+    flow_ids: &[i64],
+    window: f64,
+    capacity: Option<f64>,
+) -> Result<Flow, Box<dyn Error>> {
+    if flow_ids.len() < 2 {
+        return Err("Select at least two flows!".into());
+    }
+    if window <= 0.0 {
+        return Err("Window has to be longer than 0!".into());
+    }
+    let mut selected = HashSet::new();
+    if let Some(id) = flow_ids.iter().find(|id| !selected.insert(**id)) {
+        return Err(format!("Flow {} is selected more than once!", id).into());
+    }
+
+    let mut payloads: Vec<(i64, Vec<(f64, f64)>)> = Vec::new();
+    for id in flow_ids.iter() {
+        let flow = db
+            .get_flow_by_id(*id)?
+            .ok_or(format!("No flow with id {}!", id))?;
+        let payload = flow_payload(db, &flow)?;
+        if payload.is_empty() {
+            return Err(format!("Flow {} contains no packets!", id).into());
+        }
+        payloads.push((*id, payload));
+    }
+
+    let start = payloads
+        .iter()
+        .map(|(_, payload)| payload[0].0)
+        .reduce(f64::min)
+        .unwrap_or(0.0);
+    let end = payloads
+        .iter()
+        .filter_map(|(_, payload)| payload.last().map(|point| point.0))
+        .reduce(f64::max)
+        .unwrap_or(0.0);
+    let windows = ((end - start) / window).floor() as usize + 1;
+
+    let flows: Vec<FlowThroughput> = payloads
+        .into_iter()
+        .map(|(id, payload)| {
+            let mut bytes = vec![0.0; windows];
+            for (timestamp, len) in payload.iter() {
+                bytes[((timestamp - start) / window) as usize] += len;
+            }
+            FlowThroughput {
+                id,
+                first: payload[0].0,
+                last: payload[payload.len() - 1].0,
+                bytes,
+            }
+        })
+        .collect();
+
+    let seconds = window / 1e9;
+    let mut shares: Vec<SeriesData> = flows
+        .iter()
+        .map(|flow| SeriesData::new(&format!("SHARE_{}", flow.id), DataValue::Float(0.0)))
+        .collect();
+    let mut throughputs: Vec<SeriesData> = flows
+        .iter()
+        .map(|flow| SeriesData::new(&format!("THROUGHPUT_{}", flow.id), DataValue::Float(0.0)))
+        .collect();
+    let mut aggregate = SeriesData::new("AGGREGATE_THROUGHPUT", DataValue::Float(0.0));
+    let mut utilization = SeriesData::new("UTILIZATION", DataValue::Float(0.0));
+    let mut jain = SeriesData::new("JAIN_INDEX", DataValue::Float(0.0));
+    // Index of every window, None if less than two flows were active
+    let mut jain_per_window: Vec<Option<f64>> = Vec::with_capacity(windows);
+
+    for i in 0..windows {
+        let (window_start, window_end) =
+            (start + i as f64 * window, start + (i + 1) as f64 * window);
+        // Points are stored at the end of their window, the last one may end after the last packet
+        let timestamp = window_end;
+        let total: f64 = flows.iter().map(|flow| flow.bytes[i]).sum();
+
+        let mut active: Vec<f64> = Vec::new();
+        for (pos, flow) in flows.iter().enumerate() {
+            if flow.first >= window_end || flow.last < window_start {
+                continue;
+            }
+            active.push(flow.bytes[i]);
+            throughputs[pos].push(timestamp, DataValue::Float(flow.bytes[i] * 8.0 / seconds));
+            if total > 0.0 {
+                shares[pos].push(timestamp, DataValue::Float(flow.bytes[i] / total));
+            }
+        }
+
+        aggregate.push(timestamp, DataValue::Float(total * 8.0 / seconds));
+        if let Some(capacity) = capacity {
+            utilization.push(
+                timestamp,
+                DataValue::Float(total * 8.0 / seconds / capacity),
+            );
+        }
+
+        let index = if active.len() >= 2 {
+            jain_index(&active)
+        } else {
+            None
+        };
+        if let Some(index) = index {
+            jain.push(timestamp, DataValue::Float(index));
+        }
+        jain_per_window.push(index);
+    }
+
+    // Flows that joined after the first one
+    let mut convergence = SeriesData::new("CONVERGENCE_TIME", DataValue::Float(0.0));
+    let mut joins: Vec<f64> = flows
+        .iter()
+        .map(|flow| flow.first)
+        .filter(|first| *first > start)
+        .collect();
+    joins.sort_by(|a, b| a.total_cmp(b));
+    for join in joins.iter() {
+        // Runs at the end of the group that are shorter than CONVERGED_WINDOWS do not count
+        let first_window = ((join - start) / window) as usize;
+        let converged = jain_per_window
+            .windows(CONVERGED_WINDOWS)
+            .enumerate()
+            .skip(first_window)
+            .find(|(_, run)| {
+                run.iter()
+                    .all(|index| index.is_some_and(|index| index >= CONVERGED_JAIN_INDEX))
+            });
+        if let Some((i, _)) = converged {
+            let window_end = start + (i + 1) as f64 * window;
+            convergence.push(*join, DataValue::Float(window_end - join));
+        }
+    }
+
+    let mut created: Vec<SeriesData> = Vec::new();
+    created.extend(shares);
+    created.extend(throughputs);
+    created.extend([aggregate, jain, convergence]);
+    if capacity.is_some() {
+        created.push(utilization);
+    }
+
+    let average = |series: &SeriesData| {
+        let values: Vec<f64> = series
+            .data
+            .iter()
+            .filter_map(|val| val.as_float())
+            .collect();
+        (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+    };
+    let mut attributes: Vec<(&str, DataValue)> = Vec::from([
+        (
+            "GROUP_FLOWS",
+            DataValue::String(
+                flow_ids
+                    .iter()
+                    .map(|id| id.to_string())
+                    .collect::<Vec<String>>()
+                    .join(","),
+            ),
+        ),
+        ("FAIRNESS_WINDOW", DataValue::Float(window)),
+    ]);
+    let jain_series = created.iter().find(|series| series.name == "JAIN_INDEX");
+    if let Some(index) = jain_series.and_then(average) {
+        attributes.push(("AVG_JAIN_INDEX", DataValue::Float(index)));
+    }
+    let utilization_series = created.iter().find(|series| series.name == "UTILIZATION");
+    if let Some(value) = utilization_series.and_then(average) {
+        attributes.push(("AVG_UTILIZATION", DataValue::Float(value)));
+    }
+
+    // The group flow is only kept if all of its series and attributes were stored
+    db.begin_transaction()?;
+    let group = match store_group(db, &created, attributes) {
+        Ok(group) => group,
+        Err(err) => {
+            db.rollback_transaction()?;
+            return Err(err);
+        }
+    };
+    db.commit_transaction()?;
+
+    info!(
+        "Stored fairness of flows {:?} in {} windows",
+        flow_ids, windows
+    );
+
+    Ok(group)
+}
+
+// Creates the next group flow with the given series and attributes
+fn store_group(
+    db: &dyn TSDBInterface,
+    series: &[SeriesData],
+    attributes: Vec<(&str, DataValue)>,
+) -> Result<Flow, Box<dyn Error>> {
+    let group = db.create_flow_with_cookie(&group_tuple(), next_group(db)?)?;
+
+    for series in series.iter() {
+        store_series(db, &group, series)?;
+    }
+    let marker = ("FAIRNESS_GROUP", DataValue::Boolean(true));
+    for (name, value) in attributes.into_iter().chain([marker]) {
+        db.set_flow_attribute(
+            &group,
+            &FlowAttribute {
+                name: name.to_string(),
+                value,
+            },
+        )?;
+    }
+    Ok(group)
+}
+
+#[cfg(test)]
+mod tests {
+    use ts_storage::{sqlite::SQLiteTSDB, DataPoint};
+
+    use super::*;
+    use crate::test_util::temp_db;
+
+    // Flow with a packet of 1000 bytes every second, starting at first seconds
+    fn add_flow(db: &SQLiteTSDB, sport: i64, first: usize) -> i64 {
+        let flow = db
+            .create_flow(&IpTuple {
+                src: "10.0.0.1".parse().unwrap(),
+                dst: "10.0.0.2".parse().unwrap(),
+                sport,
+                dport: 80,
+                l4proto: 6,
+            })
+            .unwrap();
+        let series = db
+            .create_time_series(&flow, "PAYLOAD_LEN_OUT", DataValue::Int(0))
+            .unwrap();
+        let points: Vec<DataPoint> = (first..10)
+            .map(|second| DataPoint {
+                timestamp: second as f64 * 1e9 + 0.5e9,
+                value: DataValue::Int(1000),
+            })
+            .collect();
+        db.insert_multiple_points(&series, &points).unwrap();
+        flow.id.unwrap()
+    }
+
+    fn values(db: &SQLiteTSDB, flow: &Flow, name: &str) -> Vec<f64> {
+        let series = db
+            .list_time_series(flow)
+            .unwrap()
+            .find(|series| series.name == name)
+            .unwrap();
+        db.get_data_points(&series)
+            .unwrap()
+            .filter_map(|point| point.value.as_float())
+            .collect()
+    }
+
+    #[test]
+    fn jain_index_bounds() {
+        assert_eq!(jain_index(&[5.0, 5.0, 5.0]), Some(1.0));
+        assert_eq!(jain_index(&[8.0, 0.0, 0.0, 0.0]), Some(0.25));
+        assert_eq!(jain_index(&[1.0, 3.0]), Some(0.8));
+        assert_eq!(jain_index(&[0.0, 0.0]), None);
+        assert_eq!(jain_index(&[]), None);
+    }
+
+    #[test]
+    fn group_of_competing_flows() {
+        let (_file, db) = temp_db("fairness");
+
+        // Second flow joins after two seconds and gets the same share right away
+        let first = add_flow(&db, 1000, 0);
+        let second = add_flow(&db, 1001, 2);
+
+        let group = compute_fairness(&db, &[first, second], 1e9, Some(32000.0)).unwrap();
+        assert_eq!(group.tuple.l4proto, 0);
+        assert_eq!(group.sock_cookie, Some(1));
+
+        assert_eq!(values(&db, &group, "JAIN_INDEX"), vec![1.0; 8]);
+        assert_eq!(values(&db, &group, "CONVERGENCE_TIME"), vec![1e9]);
+        assert_eq!(
+            values(&db, &group, &format!("SHARE_{}", second)),
+            vec![0.5; 8]
+        );
+        let utilization = values(&db, &group, "UTILIZATION");
+        assert_eq!(utilization[0], 0.25);
+        assert_eq!(utilization[9], 0.5);
+
+        let attribute = |name: &str| db.get_flow_attribute(&group, name).unwrap().value;
+        assert_eq!(attribute("AVG_JAIN_INDEX").as_float(), Some(1.0));
+        assert!(matches!(
+            attribute("GROUP_FLOWS"),
+            DataValue::String(ids) if ids == format!("{},{}", first, second)
+        ));
+
+        assert!(matches!(
+            attribute("FAIRNESS_GROUP"),
+            DataValue::Boolean(true)
+        ));
+
+        // Every group gets its own number
+        let next = compute_fairness(&db, &[second, first], 1e9, None).unwrap();
+        assert_eq!(next.sock_cookie, Some(2));
+    }
+
+    #[test]
+    fn groups_do_not_reuse_flows_with_their_tuple() {
+        let (_file, db) = temp_db("fairness-tuple");
+        let first = add_flow(&db, 1000, 0);
+        let second = add_flow(&db, 1001, 2);
+
+        // Captured flow without addresses, ports and a known protocol
+        let captured = db.create_flow_with_cookie(&group_tuple(), 1).unwrap();
+
+        let group = compute_fairness(&db, &[first, second], 1e9, None).unwrap();
+        assert_eq!(group.sock_cookie, Some(2));
+        assert_ne!(group.id, captured.id);
+        assert_eq!(db.list_time_series(&captured).unwrap().count(), 0);
+        assert!(db.get_flow_attribute(&captured, "FAIRNESS_GROUP").is_err());
+    }
+
+    #[test]
+    fn invalid_selections() {
+        let (_file, db) = temp_db("fairness-invalid");
+        let first = add_flow(&db, 1000, 0);
+        let second = add_flow(&db, 1001, 2);
+
+        assert!(compute_fairness(&db, &[first], 1e9, None).is_err());
+        assert!(compute_fairness(&db, &[first, first], 1e9, None).is_err());
+        assert!(compute_fairness(&db, &[first, second], 0.0, None).is_err());
+        assert!(compute_fairness(&db, &[first, second + 1], 1e9, None).is_err());
+        // No group flow was created
+        assert_eq!(db.list_flows().unwrap().count(), 2);
+    }
+}
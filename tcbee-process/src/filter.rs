@@ -0,0 +1,271 @@
+// Processing filters, select the flows and events that are imported
+// Address, port and time filters are applied by the DBWriter before an event is assigned to a flow.
+// Flows below the minimum number of packets and dropped fields never create a time series.
+use std::{collections::HashSet, net::IpAddr, sync::Arc};
+
+use ts_storage::IpTuple;
+
+use crate::timestamps::TimeConverter;
+
+// Address or network in CIDR notation, e.g. 10.0.0.0/8 or fd00::/64
+#[derive(Debug, Clone, Copy)]
+pub struct IpNetwork {
+    addr: IpAddr,
+    prefix: u32,
+}
+
+// IPv4 addresses may be recorded as IPv4-mapped IPv6 addresses
+fn unmap(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => IpAddr::V4(v4),
+            None => addr,
+        },
+        IpAddr::V4(_) => addr,
+    }
+}
+
+impl IpNetwork {
+    pub fn from_argument(argument: &str) -> Result<IpNetwork, String> {
+        let (addr, prefix) = match argument.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (argument, None),
+        };
+
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("Invalid address {} in filter {}!", addr, argument))?;
+        let addr = unmap(addr);
+        let bits = if addr.is_ipv4() { 32 } else { 128 };
+
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u32>()
+                .ok()
+                .filter(|prefix| *prefix <= bits)
+                .ok_or_else(|| {
+                    format!("Invalid prefix length {} in filter {}!", prefix, argument)
+                })?,
+            None => bits,
+        };
+
+        Ok(IpNetwork { addr, prefix })
+    }
+
+    pub fn contains(&self, addr: &IpAddr) -> bool {
+        match (self.addr, unmap(*addr)) {
+            (IpAddr::V4(net), IpAddr::V4(addr)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix).unwrap_or(0);
+                u32::from(net) & mask == u32::from(addr) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(addr)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix).unwrap_or(0);
+                u128::from(net) & mask == u128::from(addr) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+// Time range in nanoseconds since the start of the recording, open if a bound is missing
+#[derive(Debug, Clone, Copy)]
+pub struct TimeWindow {
+    start: Option<f64>,
+    end: Option<f64>,
+}
+
+impl TimeWindow {
+    /// Parses <start>:<end> in seconds since the start of the recording, e.g. 10:30, 10: or :30
+    pub fn from_argument(argument: &str) -> Result<TimeWindow, String> {
+        let Some((start, end)) = argument.split_once(':') else {
+            return Err(format!(
+                "Invalid time window {}! Expected <start>:<end> in seconds, e.g. 10:30",
+                argument
+            ));
+        };
+
+        let parse = |bound: &str| -> Result<Option<f64>, String> {
+            if bound.trim().is_empty() {
+                return Ok(None);
+            }
+            bound
+                .trim()
+                .parse::<f64>()
+                .map(|seconds| Some(seconds * 1e9))
+                .map_err(|_| format!("Invalid time {} in time window {}!", bound, argument))
+        };
+
+        let window = TimeWindow {
+            start: parse(start)?,
+            end: parse(end)?,
+        };
+
+        if let (Some(start), Some(end)) = (window.start, window.end) {
+            if end < start {
+                return Err(format!("Time window {} ends before it starts!", argument));
+            }
+        }
+        Ok(window)
+    }
+
+    fn contains(&self, time: f64) -> bool {
+        !self.start.is_some_and(|start| time < start) && !self.end.is_some_and(|end| time > end)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FlowFilter {
+    // Flows with an address in one of these networks, all if empty
+    networks: Vec<IpNetwork>,
+    // Flows with one of these ports as source or destination, all if empty
+    ports: Vec<i64>,
+    window: Option<TimeWindow>,
+    time: TimeConverter,
+    // Flows are only created once they have this many packets, in both directions
+    pub min_packets: usize,
+    // Series names without direction suffix that are not stored
+    pub drop_fields: Arc<HashSet<String>>,
+}
+
+impl FlowFilter {
+    pub fn new(
+        networks: Vec<IpNetwork>,
+        ports: Vec<i64>,
+        window: Option<TimeWindow>,
+        time: TimeConverter,
+        min_packets: usize,
+        drop_fields: Vec<String>,
+    ) -> FlowFilter {
+        FlowFilter {
+            networks,
+            ports,
+            window,
+            time,
+            min_packets,
+            drop_fields: Arc::new(drop_fields.into_iter().collect()),
+        }
+    }
+
+    /// Checks addresses and ports, either end of the connection may match
+    pub fn matches_tuple(&self, tuple: &IpTuple) -> bool {
+        let address = self.networks.is_empty()
+            || self
+                .networks
+                .iter()
+                .any(|network| network.contains(&tuple.src) || network.contains(&tuple.dst));
+        let port = self.ports.is_empty()
+            || self.ports.contains(&tuple.sport)
+            || self.ports.contains(&tuple.dport);
+
+        address && port
+    }
+
+    /// Checks a recorded timestamp against the time window
+    pub fn matches_time(&self, recorded: f64) -> bool {
+        match self.window {
+            Some(window) => window.contains(self.time.since_recording_start(recorded)),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timestamps::TimeMode;
+
+    fn network(argument: &str) -> IpNetwork {
+        IpNetwork::from_argument(argument).unwrap()
+    }
+
+    fn addr(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    fn tuple(src: &str, dst: &str, sport: i64, dport: i64) -> IpTuple {
+        IpTuple {
+            src: addr(src),
+            dst: addr(dst),
+            sport,
+            dport,
+            l4proto: 6,
+        }
+    }
+
+    #[test]
+    fn networks() {
+        assert!(network("10.0.0.0/8").contains(&addr("10.200.3.4")));
+        assert!(!network("10.0.0.0/8").contains(&addr("11.0.0.1")));
+        assert!(network("10.1.2.3").contains(&addr("10.1.2.3")));
+        assert!(!network("10.1.2.3").contains(&addr("10.1.2.4")));
+        assert!(network("0.0.0.0/0").contains(&addr("192.168.1.1")));
+
+        assert!(network("fd00::/64").contains(&addr("fd00::1:2")));
+        assert!(!network("fd00::/64").contains(&addr("fd00:0:0:1::1")));
+        assert!(network("::/0").contains(&addr("2001:db8::1")));
+
+        // IPv4-mapped addresses match IPv4 networks, families never mix otherwise
+        assert!(network("10.0.0.0/8").contains(&addr("::ffff:10.0.0.1")));
+        assert!(network("::ffff:10.0.0.0/8").contains(&addr("10.0.0.1")));
+        assert!(!network("::/0").contains(&addr("10.0.0.1")));
+        assert!(!network("0.0.0.0/0").contains(&addr("2001:db8::1")));
+    }
+
+    #[test]
+    fn invalid_networks() {
+        assert!(IpNetwork::from_argument("10.0.0.0/33").is_err());
+        assert!(IpNetwork::from_argument("fd00::/129").is_err());
+        assert!(IpNetwork::from_argument("10.0.0.0/x").is_err());
+        assert!(IpNetwork::from_argument("10.0.0").is_err());
+    }
+
+    #[test]
+    fn time_windows() {
+        let window = TimeWindow::from_argument("10:30").unwrap();
+        assert!(!window.contains(9e9));
+        assert!(window.contains(10e9));
+        assert!(window.contains(30e9));
+        assert!(!window.contains(31e9));
+
+        assert!(TimeWindow::from_argument("10:").unwrap().contains(1e12));
+        assert!(TimeWindow::from_argument(":30").unwrap().contains(0.0));
+
+        assert!(TimeWindow::from_argument("30:10").is_err());
+        assert!(TimeWindow::from_argument("30").is_err());
+        assert!(TimeWindow::from_argument("a:10").is_err());
+    }
+
+    #[test]
+    fn tuples_and_times() {
+        let filter = FlowFilter::new(
+            vec![network("10.0.0.0/24")],
+            vec![443],
+            Some(TimeWindow::from_argument("1:2").unwrap()),
+            TimeConverter::for_capture(TimeMode::Boot, 5_000_000_000),
+            0,
+            vec![],
+        );
+
+        // Either end of the connection matches
+        assert!(filter.matches_tuple(&tuple("10.0.0.1", "8.8.8.8", 50000, 443)));
+        assert!(filter.matches_tuple(&tuple("8.8.8.8", "10.0.0.1", 443, 50000)));
+        assert!(!filter.matches_tuple(&tuple("10.0.1.1", "8.8.8.8", 50000, 443)));
+        assert!(!filter.matches_tuple(&tuple("10.0.0.1", "8.8.8.8", 50000, 80)));
+
+        // Window is relative to the start of the recording
+        assert!(!filter.matches_time(5.5e9));
+        assert!(filter.matches_time(6.5e9));
+        assert!(!filter.matches_time(7.5e9));
+
+        let all = FlowFilter::new(
+            vec![],
+            vec![],
+            None,
+            TimeConverter::for_capture(TimeMode::Boot, 0),
+            0,
+            vec![],
+        );
+        assert!(all.matches_tuple(&tuple("8.8.8.8", "1.1.1.1", 1, 2)));
+        assert!(all.matches_time(0.0));
+    }
+}
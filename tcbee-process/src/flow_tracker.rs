@@ -1,10 +1,15 @@
 use log::{error, info};
-use std::error::Error;
-use ts_storage::{DataPoint, DataValue, Flow, IpTuple, TSDBInterface, TimeSeries};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    sync::Arc,
+};
+use ts_storage::{DataPoint, DataValue, Flow, FlowAttribute, IpTuple, TSDBInterface, TimeSeries};
 
 use crate::{
-    bindings::{sock::sock_trace_entry, cwnd::cwnd_trace_entry, tcp_packet::TcpPacket, tcp_probe::TcpProbe},
     db_writer::DBOperation,
+    summary::FlowSummary,
+    timestamps::{TimeConverter, TimeMode},
 };
 const BUFFER_SIZE: usize = 1000;
 
@@ -19,8 +24,27 @@ pub trait EventIndexer {
     fn get_ip_tuple(&self) -> IpTuple;
     fn get_max_index(&self) -> usize;
     fn get_timestamp(&self) -> f64;
-    fn as_db_op(self) -> DBOperation;
-    fn get_struct_length(&self) -> usize;
+    fn as_db_op(self, direction: Direction) -> DBOperation;
+    // Socket cookie of the connection, None for events recorded outside of the socket
+    fn get_sock_cookie(&self) -> Option<u64> {
+        None
+    }
+    // Options announced in a SYN, None for all other events
+    fn get_handshake_options(&self) -> Option<HandshakeOptions> {
+        None
+    }
+    // Receive window in bytes with the negotiated window scale applied
+    fn get_scaled_window(&self, _scale: u8) -> Option<DataValue> {
+        None
+    }
+}
+
+// Options a host announced in its SYN or SYN-ACK
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HandshakeOptions {
+    pub mss: Option<u16>,
+    pub wscale: Option<u8>,
+    pub sack_permitted: bool,
 }
 #[derive(Debug)]
 pub struct TsTracker {
@@ -28,6 +52,10 @@ pub struct TsTracker {
     events: Vec<DataPoint>,
     handled: usize,
     name: String,
+    // Last timestamp stored by a previous import, older points are already in the database
+    skip_until: Option<f64>,
+    // Recorded and stored timestamp of the last point, timestamps of a series have to be unique
+    last_timestamp: Option<(f64, f64)>,
 }
 
 impl TsTracker {
@@ -47,26 +75,117 @@ impl TsTracker {
             events: Vec::with_capacity(BUFFER_SIZE),
             handled: 0,
             name: name.to_string(),
+            skip_until: None,
+            last_timestamp: None,
         }
     }
 
+    // Continues a time series of a previous import
+    pub fn resume(db: &Box<dyn TSDBInterface + Send>, ts: TimeSeries) -> TsTracker {
+        // Series without points were created right before an import stopped
+        let skip_until = db.get_time_series_bounds(&ts).ok().map(|bounds| bounds.xmax);
+        let name = ts.name.clone();
+
+        TsTracker {
+            ts: ts,
+            events: Vec::with_capacity(BUFFER_SIZE),
+            handled: if skip_until.is_some() { 1 } else { 0 },
+            name: name,
+            skip_until,
+            last_timestamp: None,
+        }
+    }
+
+    // Returns the number of points written to the database
+    // True for timestamps written by a previous import of the series
+    fn is_stored(&self, timestamp: f64) -> bool {
+        self.skip_until.is_some_and(|last| timestamp <= last)
+    }
+
     pub fn add_entry(
         &mut self,
-        entry: DataPoint,
+        mut entry: DataPoint,
         db: &Box<dyn TSDBInterface + Send>,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<usize, Box<dyn Error>> {
         // Track number of entries handled
         self.handled = self.handled + 1;
 
+        // Written before the import was interrupted
+        if self.is_stored(entry.timestamp) {
+            return Ok(0);
+        }
+
+        // Captures with microsecond resolution stamp packets of a burst with the same time
+        // Repeated timestamps are moved to the next representable time after the stored one
+        let recorded = entry.timestamp;
+        if let Some((last_recorded, last_stored)) = self.last_timestamp {
+            if recorded == last_recorded {
+                entry.timestamp = last_stored.next_up();
+            }
+        }
+        self.last_timestamp = Some((recorded, entry.timestamp));
+
         // If space is left then push entry, else write to db, clear and then write entry
         if self.events.len() <= BUFFER_SIZE {
             self.events.push(entry);
+            Ok(0)
         } else {
-            // TODO: if one of these inserts fail, it reverts the entire write!
-            // Implement better error handling!
-            let result = db.insert_multiple_points(&self.ts, &self.events)?;
-            self.events.clear();
+            // A failed batch is dropped, the entry starts the next one
+            let written = self.write_buffered(db);
             self.events.push(entry);
+            written
+        }
+    }
+
+    // Writes buffered events without closing the time series, used while following a recording
+    pub fn write_buffered(&mut self, db: &Box<dyn TSDBInterface + Send>) -> Result<usize, Box<dyn Error>> {
+        if self.events.is_empty() {
+            return Ok(0);
+        }
+        // Taken before the insert, a batch that failed would fail again on every retry
+        let events = std::mem::take(&mut self.events);
+        self.events.reserve(BUFFER_SIZE);
+        match db.insert_multiple_points(&self.ts, &events) {
+            Ok(_) => Ok(events.len()),
+            Err(err) => Err(format!("Dropped {} points of {}: {}", events.len(), self.name, err).into()),
+        }
+    }
+
+    // Adds offset to all stored timestamps, the series is rewritten as the shifted points may collide
+    // with points that are not shifted yet
+    pub fn rebase(
+        &mut self,
+        flow: &Flow,
+        db: &Box<dyn TSDBInterface + Send>,
+        offset: f64,
+    ) -> Result<(), Box<dyn Error>> {
+        // Series without points were deleted on flush
+        if self.handled < 1 {
+            return Ok(());
+        }
+        let points: Vec<DataPoint> = db.get_data_points(&self.ts)?.collect();
+
+        db.delete_time_series(flow, &self.ts)?;
+        self.ts = db.create_time_series(flow, &self.name, self.ts.ts_type.clone())?;
+
+        let mut batch = Vec::with_capacity(BUFFER_SIZE);
+        let mut last: Option<f64> = None;
+        for mut point in points {
+            // Rounding of the sum may merge neighbouring timestamps
+            point.timestamp += offset;
+            if let Some(last) = last.filter(|last| point.timestamp <= *last) {
+                point.timestamp = last.next_up();
+            }
+            last = Some(point.timestamp);
+
+            batch.push(point);
+            if batch.len() == BUFFER_SIZE {
+                db.insert_multiple_points(&self.ts, &batch)?;
+                batch.clear();
+            }
+        }
+        if !batch.is_empty() {
+            db.insert_multiple_points(&self.ts, &batch)?;
         }
         Ok(())
     }
@@ -75,7 +194,7 @@ impl TsTracker {
         &mut self,
         flow: &Flow,
         db: &Box<dyn TSDBInterface + Send>,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<usize, Box<dyn Error>> {
         // Check if own buffer is currently empty
         if self.events.len() < 1 {
             // Special case, buffer was never filled
@@ -94,198 +213,469 @@ impl TsTracker {
                     info!("Done! {}",res.unwrap());
                 }
             }
-            return Ok(());
+            return Ok(0);
         }
         // Flush remaining events int oDB
         // TODO: error handling better
-        let result = db.insert_multiple_points(&self.ts, &self.events)?;
-        self.events.clear();
-        Ok(())
+        self.write_buffered(db)
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum EventType {
     Packet,
     TcpProbe,
     Socket,
-    Cwnd
+    Cwnd,
+    RetransmitSynack,
+    BadCsum,
+}
+
+impl EventType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            EventType::Packet => "packet",
+            EventType::TcpProbe => "probe",
+            EventType::Socket => "sock",
+            EventType::Cwnd => "cwnd",
+            EventType::RetransmitSynack => "retransmit_synack",
+            EventType::BadCsum => "bad_csum",
+        }
+    }
+}
+
+// Orientation of the tuple of a recorded event, depends on the probe that recorded it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    // Sent by the local host, tuple is local -> remote
+    Egress,
+    // Received by the local host, tuple is remote -> local
+    Ingress,
+    // State of the local socket, tuple is local -> remote
+    Socket,
+}
+
+impl Direction {
+    /// Suffix of series names when both directions are stored in one flow
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Direction::Egress => "_OUT",
+            Direction::Ingress => "_IN",
+            Direction::Socket => "",
+        }
+    }
+
+    /// Orients a tuple local -> remote, identifies a connection independent of direction
+    pub fn connection_tuple(&self, tuple: IpTuple) -> IpTuple {
+        match self {
+            Direction::Ingress => IpTuple {
+                src: tuple.dst,
+                dst: tuple.src,
+                sport: tuple.dport,
+                dport: tuple.sport,
+                l4proto: tuple.l4proto,
+            },
+            Direction::Egress | Direction::Socket => tuple,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct FlowTracker {
     flow: Flow,
-    packet_trackers: Vec<TsTracker>,
-    probe_trackers: Vec<TsTracker>,
-    sock_trackers: Vec<TsTracker>,
-    cwnd_trackers: Vec<TsTracker>
+    // Trackers per event type and series name suffix, created on the first event
+    // None for fields that are dropped
+    trackers: HashMap<(EventType, &'static str), Vec<Option<TsTracker>>>,
+    // First recorded timestamp, base for flow relative timestamps
+    first_timestamp: Option<f64>,
+    // Earliest recorded timestamp, events of different files arrive out of order
+    // Flow relative series are rebased to it on flush
+    min_timestamp: Option<f64>,
+    // Series stored by a previous import of the same flow, by name
+    existing: HashMap<String, TimeSeries>,
+    // Field names without suffix that are not stored
+    dropped: Arc<HashSet<String>>,
+    summary: FlowSummary,
+    // Handshake options per series name suffix, i.e. sender
+    handshakes: HashMap<&'static str, HandshakeOptions>,
+    // Windows with the window scale applied per series name suffix, None if dropped
+    scaled_windows: HashMap<&'static str, Option<TsTracker>>,
 }
 
 impl FlowTracker {
-    pub fn new(db: &Box<dyn TSDBInterface + Send>, tuple: &IpTuple) -> FlowTracker {
-        let flow = db.create_flow(tuple).expect("Failed to create flow entry!");
-        let packet = TcpPacket::default();
-        let probe = TcpProbe::default();
-        let sock = sock_trace_entry::default();
-        let cwnd = cwnd_trace_entry::default();
-
-        let packet_tracker = FlowTracker::create_time_series::<TcpPacket>(db, &flow, &packet);
-        let probe_tracker = FlowTracker::create_time_series::<TcpProbe>(db, &flow, &probe);
-        let sock_tracker = FlowTracker::create_time_series::<sock_trace_entry>(db, &flow, &sock);
-        let cwnd_tracker = FlowTracker::create_time_series::<cwnd_trace_entry>(db, &flow, &cwnd);
+    pub fn new(
+        db: &Box<dyn TSDBInterface + Send>,
+        tuple: &IpTuple,
+        sock_cookie: u64,
+        dropped: Arc<HashSet<String>>,
+    ) -> FlowTracker {
+        let flow = db
+            .create_flow_with_cookie(tuple, sock_cookie as i64)
+            .expect("Failed to create flow entry!");
+
+        // Flow may be continued from a previous import
+        let existing: HashMap<String, TimeSeries> = match db.list_time_series(&flow) {
+            Ok(series) => series.map(|ts| (ts.name.clone(), ts)).collect(),
+            Err(err) => {
+                error!("Failed to list series of flow {:?} - {}. Continuing...", flow.tuple, err);
+                HashMap::new()
+            }
+        };
+
+        // Flow relative timestamps have to keep their base
+        let mut first_timestamp = None;
+        let mut summary = FlowSummary::new();
+        let mut handshakes = HashMap::new();
+        if !existing.is_empty() {
+            summary = FlowSummary::restore(db, &flow);
+            handshakes = FlowTracker::restore_handshakes(db, &flow);
+            if let Ok(FlowAttribute { value: DataValue::Float(start), .. }) =
+                db.get_flow_attribute(&flow, "FLOW_START")
+            {
+                first_timestamp = Some(start);
+            }
+        }
 
         FlowTracker {
             flow: flow,
-            packet_trackers: packet_tracker,
-            probe_trackers: probe_tracker,
-            sock_trackers: sock_tracker,
-            cwnd_trackers: cwnd_tracker
+            trackers: HashMap::new(),
+            first_timestamp,
+            min_timestamp: first_timestamp,
+            existing,
+            dropped,
+            summary,
+            handshakes,
+            scaled_windows: HashMap::new(),
+        }
+    }
+
+    // Handshake options stored by a previous import, windows of later packets still need their scale
+    fn restore_handshakes(
+        db: &Box<dyn TSDBInterface + Send>,
+        flow: &Flow,
+    ) -> HashMap<&'static str, HandshakeOptions> {
+        let mut handshakes = HashMap::new();
+        for suffix in ["", "_IN", "_OUT"] {
+            // Stored for every SYN, other attributes only if the option was present
+            let Ok(FlowAttribute { value: DataValue::Boolean(sack_permitted), .. }) =
+                db.get_flow_attribute(flow, &format!("SACK_PERMITTED{}", suffix))
+            else {
+                continue;
+            };
+            let int = |name: &str| {
+                db.get_flow_attribute(flow, &format!("{}{}", name, suffix))
+                    .ok()
+                    .and_then(|attribute| attribute.value.as_int())
+            };
+
+            handshakes.insert(
+                suffix,
+                HandshakeOptions {
+                    mss: int("MSS").map(|mss| mss as u16),
+                    wscale: int("WSCALE").map(|wscale| wscale as u8),
+                    sack_permitted,
+                },
+            );
         }
+        handshakes
+    }
+
+    // Window scale of packets sent with the given suffix, None until the SYN of the sender was seen
+    // Scaling is only used if both hosts announced it, the peer is unknown for flows of one direction
+    fn window_scale(&self, suffix: &'static str) -> Option<u8> {
+        let own = self.handshakes.get(suffix)?;
+        let peer = match suffix {
+            "_IN" => self.handshakes.get("_OUT"),
+            "_OUT" => self.handshakes.get("_IN"),
+            _ => None,
+        };
+
+        if peer.is_some_and(|peer| peer.wscale.is_none()) {
+            return Some(0);
+        }
+        Some(own.wscale.unwrap_or(0))
     }
 
     pub fn add_event<T: EventIndexer>(
         &mut self,
         db: &Box<dyn TSDBInterface + Send>,
         etype: EventType,
+        suffix: &'static str,
         event: &T,
-    ) -> Result<(), Box<dyn Error>> {
-        match etype {
-            EventType::Packet => {
-                let time = event.get_timestamp();
-
-                for i in 0..=event.get_max_index() {
-
-                    if let Some(value) = event.get_field(i) {
-                        let entry = DataPoint {
-                            timestamp: time,
-                            value: value,
-                        };
-    
-                        self.packet_trackers[i].add_entry(entry, db)?;
-                    }
-
-                    
+        converter: &TimeConverter,
+    ) -> Result<usize, Box<dyn Error>> {
+        let recorded = event.get_timestamp();
+        let flow_start = *self.first_timestamp.get_or_insert(recorded);
+        self.min_timestamp = Some(self.min_timestamp.map_or(recorded, |min| min.min(recorded)));
+        let time = converter.convert(recorded, flow_start);
+
+        let flow = &self.flow;
+        let existing = &mut self.existing;
+        let dropped = &self.dropped;
+        let trackers = self.trackers.entry((etype, suffix)).or_insert_with(|| {
+            FlowTracker::create_time_series::<T>(db, flow, existing, dropped, event, suffix)
+        });
+
+        // Points written to the database, events are buffered per series
+        let mut written = 0;
+        // Series with a value of the event and those that already stored it
+        let (mut seen, mut skipped) = (0, 0);
+        for i in 0..=event.get_max_index() {
+            let Some(tracker) = trackers[i].as_mut() else {
+                continue;
+            };
+            if let Some(value) = event.get_field(i) {
+                seen += 1;
+                if tracker.is_stored(time) {
+                    skipped += 1;
                 }
-            }
+                let entry = DataPoint {
+                    timestamp: time,
+                    value: value,
+                };
 
-            EventType::TcpProbe => {
-                let time = event.get_timestamp();
+                written += tracker.add_entry(entry, db)?;
+            }
+        }
 
-                for i in 0..=event.get_max_index() {
-                    if let Some(value) = event.get_field(i) {
+        // Events stored by an interrupted import are part of the restored summary
+        if seen == 0 || skipped < seen {
+            self.summary.add_event(etype, suffix, event, time);
+        }
 
-                        let entry = DataPoint {
-                            timestamp: time,
-                            value: value,
-                        };
-    
-                        self.probe_trackers[i].add_entry(entry, db)?;
-                    }
+        if let Some(options) = event.get_handshake_options() {
+            self.handshakes.insert(suffix, options);
+        }
+        let scaled = self
+            .window_scale(suffix)
+            .and_then(|scale| event.get_scaled_window(scale));
+        if let Some(value) = scaled {
+            let tracker = self.scaled_windows.entry(suffix).or_insert_with(|| {
+                if self.dropped.contains("WINDOW_SCALED") {
+                    return None;
                 }
-            },
-
-            EventType::Socket => {
-                let time = event.get_timestamp();
-
-                for i in 0..=event.get_max_index() {
-                    if let Some(value) = event.get_field(i) {
-
-                        let entry = DataPoint {
-                            timestamp: time,
-                            value: value,
-                        };
-    
-                        self.sock_trackers[i].add_entry(entry, db)?;
-                    }
+                let name = format!("WINDOW_SCALED{}", suffix);
+                match self.existing.remove(&name) {
+                    Some(ts) => Some(TsTracker::resume(db, ts)),
+                    None => Some(TsTracker::new(db, &name, &self.flow, DataValue::Int(0))),
                 }
-            },
-
-            EventType::Cwnd => {
-                let time = event.get_timestamp();
-
-                for i in 0..=event.get_max_index() {
-                    if let Some(value) = event.get_field(i) {
-
-                        let entry = DataPoint {
-                            timestamp: time,
-                            value: value,
-                        };
-    
-                        self.cwnd_trackers[i].add_entry(entry, db)?;
-                    }
-                }
-            },
+            });
+            if let Some(tracker) = tracker.as_mut() {
+                written += tracker.add_entry(DataPoint { timestamp: time, value }, db)?;
+            }
         }
 
-        Ok(())
+        Ok(written)
     }
 
-    // TODO: this should use enum instead of the first event
     fn create_time_series<T: EventIndexer>(
         db: &Box<dyn TSDBInterface + Send>,
         flow: &Flow,
+        existing: &mut HashMap<String, TimeSeries>,
+        dropped: &HashSet<String>,
         event: &T,
-    ) -> Vec<TsTracker> {
+        suffix: &str,
+    ) -> Vec<Option<TsTracker>> {
         // Vector to hold created time series trackers
-        let mut trackers: Vec<TsTracker> = Vec::with_capacity(event.get_max_index() + 1);
+        let mut trackers: Vec<Option<TsTracker>> = Vec::with_capacity(event.get_max_index() + 1);
 
         // Loop over number of fields
         for i in 0..=event.get_max_index() {
-            trackers.push(TsTracker::new(
-                &db,
-                event.get_field_name(i),
-                &flow,
-                event.get_default_field(i),
-            ));
+            if dropped.contains(event.get_field_name(i)) {
+                trackers.push(None);
+                continue;
+            }
+
+            let name = format!("{}{}", event.get_field_name(i), suffix);
+            match existing.remove(&name) {
+                Some(ts) => trackers.push(Some(TsTracker::resume(&db, ts))),
+                None => trackers.push(Some(TsTracker::new(
+                    &db,
+                    &name,
+                    &flow,
+                    event.get_default_field(i),
+                ))),
+            }
         }
         trackers
     }
 
-    pub fn flush(&mut self, db: &Box<dyn TSDBInterface + Send>) {
-        for tracker in self.packet_trackers.iter_mut() {
-            let res = tracker.flush(&self.flow, &db);
-            if res.is_err() {
-                error!(
-                    "Failed flush packet trackers on {:?} - {}. Continuing...",
-                    tracker.ts,
-                    res.err().unwrap()
-                )
+    // Returns the number of points written, stops at the first series that failed
+    pub fn write_buffered(
+        &mut self,
+        db: &Box<dyn TSDBInterface + Send>,
+    ) -> Result<usize, Box<dyn Error>> {
+        let mut written = 0;
+        let trackers = self.trackers.values_mut().flatten().flatten();
+        for tracker in trackers.chain(self.scaled_windows.values_mut().flatten()) {
+            written += tracker.write_buffered(db)?;
+        }
+        Ok(written)
+    }
+
+    pub fn flush(
+        &mut self,
+        db: &Box<dyn TSDBInterface + Send>,
+        converter: &TimeConverter,
+    ) -> Result<(), Box<dyn Error>> {
+        let trackers = self.trackers.values_mut().flatten().flatten();
+        for tracker in trackers.chain(self.scaled_windows.values_mut().flatten()) {
+            tracker.flush(&self.flow, &db)?;
+        }
+
+        if converter.mode() == TimeMode::Flow {
+            self.rebase(db)?;
+        }
+        self.store_attributes(db, converter);
+        Ok(())
+    }
+
+    // Moves the base of flow relative timestamps to the earliest event of the flow
+    fn rebase(&mut self, db: &Box<dyn TSDBInterface + Send>) -> Result<(), Box<dyn Error>> {
+        let (Some(first), Some(min)) = (self.first_timestamp, self.min_timestamp) else {
+            return Ok(());
+        };
+        if min >= first {
+            return Ok(());
+        }
+
+        let offset = first - min;
+        let trackers = self.trackers.values_mut().flatten().flatten();
+        for tracker in trackers.chain(self.scaled_windows.values_mut().flatten()) {
+            tracker.rebase(&self.flow, db, offset).map_err(|err| {
+                format!("Failed to rebase {} to the flow start: {}", tracker.name, err)
+            })?;
+        }
+        self.summary.shift(offset);
+        self.first_timestamp = Some(min);
+        Ok(())
+    }
+
+    // Stores the summary and the time base so that absolute times can be reconstructed
+    fn store_attributes(&self, db: &Box<dyn TSDBInterface + Send>, converter: &TimeConverter) {
+        let mut attributes = vec![FlowAttribute {
+            name: "TIME_BASE".to_string(),
+            value: DataValue::String(converter.mode().as_str().to_string()),
+        }];
+
+        if converter.mode() == TimeMode::Flow {
+            if let Some(start) = self.first_timestamp {
+                attributes.push(FlowAttribute {
+                    name: "FLOW_START".to_string(),
+                    value: DataValue::Float(start),
+                });
+            }
+        }
+        attributes.extend(self.summary.attributes());
+
+        for (suffix, options) in self.handshakes.iter() {
+            attributes.push(FlowAttribute {
+                name: format!("SACK_PERMITTED{}", suffix),
+                value: DataValue::Boolean(options.sack_permitted),
+            });
+            if let Some(mss) = options.mss {
+                attributes.push(FlowAttribute {
+                    name: format!("MSS{}", suffix),
+                    value: DataValue::Int(mss as i64),
+                });
+            }
+            if let Some(wscale) = options.wscale {
+                attributes.push(FlowAttribute {
+                    name: format!("WSCALE{}", suffix),
+                    value: DataValue::Int(wscale as i64),
+                });
             }
         }
-        for tracker in self.probe_trackers.iter_mut() {
-            let res = tracker.flush(&self.flow, &db);
 
-            if res.is_err() {
+        for attribute in attributes.iter() {
+            // Attributes already exist if the flow was continued
+            if let Err(err) = db.set_flow_attribute(&self.flow, attribute) {
                 error!(
-                    "Failed flush probe trackers on {:?} - {}. Continuing...",
-                    tracker.ts,
-                    res.err().unwrap()
-                )
+                    "Failed to store {} for flow {:?} - {}. Continuing...",
+                    attribute.name, self.flow.tuple, err
+                );
             }
         }
+    }
+}
 
-        for tracker in self.sock_trackers.iter_mut() {
-            let res = tracker.flush(&self.flow, &db);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::{tcp_bad_csum::tcp_bad_csum_entry, tcp_packet::TcpPacket};
+    use crate::test_util::temp_db;
 
-            if res.is_err() {
-                error!(
-                    "Failed flush sock trackers on {:?} - {}. Continuing...",
-                    tracker.ts,
-                    res.err().unwrap()
-                )
-            }
+    fn bad_csum(time: u64) -> tcp_bad_csum_entry {
+        tcp_bad_csum_entry {
+            time,
+            ..Default::default()
         }
+    }
 
-        for tracker in self.cwnd_trackers.iter_mut() {
-            let res = tracker.flush(&self.flow, &db);
+    #[test]
+    fn flow_time_starts_at_the_earliest_event() {
+        let (_file, db) = temp_db("flow-start");
+        let db: Box<dyn TSDBInterface + Send> = Box::new(db);
+        let converter = TimeConverter::for_capture(TimeMode::Flow, 0);
 
-            if res.is_err() {
-                error!(
-                    "Failed flush cwnd trackers on {:?} - {}. Continuing...",
-                    tracker.ts,
-                    res.err().unwrap()
-                )
-            }
+        let tuple = bad_csum(0).get_ip_tuple();
+        let mut tracker = FlowTracker::new(&db, &tuple, 0, Arc::new(HashSet::new()));
+
+        // Events of another file arrive after later events of the flow
+        for time in [1000, 1500, 400, 2000] {
+            tracker
+                .add_event(&db, EventType::BadCsum, "", &bad_csum(time), &converter)
+                .unwrap();
         }
+        tracker.flush(&db, &converter).unwrap();
+
+        let series = db.list_time_series(&tracker.flow).unwrap().next().unwrap();
+        let timestamps: Vec<f64> = db
+            .get_data_points(&series)
+            .unwrap()
+            .map(|point| point.timestamp)
+            .collect();
+        assert_eq!(timestamps, vec![0.0, 600.0, 1100.0, 1600.0]);
+
+        let attribute = |name: &str| db.get_flow_attribute(&tracker.flow, name).unwrap().value.as_float();
+        assert_eq!(attribute("FLOW_START"), Some(400.0));
+        assert_eq!(attribute("FIRST_TIMESTAMP"), Some(0.0));
+        assert_eq!(attribute("LAST_TIMESTAMP"), Some(1600.0));
+    }
+
+    #[test]
+    fn resumed_import_counts_only_new_events() {
+        let (_file, db) = temp_db("resume-summary");
+        let db: Box<dyn TSDBInterface + Send> = Box::new(db);
+        let converter = TimeConverter::for_capture(TimeMode::Boot, 0);
+        let packet = |time: u64| TcpPacket {
+            time,
+            saddr: 0x0a000001,
+            daddr: 0x0a000002,
+            sport: 1000,
+            dport: 80,
+            payload_len: 100,
+            ..Default::default()
+        };
+        let tuple = packet(0).get_ip_tuple();
+
+        let import = |times: &[u64]| {
+            let mut tracker = FlowTracker::new(&db, &tuple, 0, Arc::new(HashSet::new()));
+            for &time in times {
+                tracker
+                    .add_event(&db, EventType::Packet, "", &packet(time), &converter)
+                    .unwrap();
+            }
+            tracker.flush(&db, &converter).unwrap();
+            tracker.flow
+        };
+
+        import(&[100, 200]);
+        // Interrupted import is repeated with the stored packets and a new one
+        let flow = import(&[100, 200, 300]);
+
+        let attribute = |name: &str| db.get_flow_attribute(&flow, name).unwrap().value.as_int();
+        assert_eq!(attribute("PACKETS"), Some(3));
+        assert_eq!(attribute("PAYLOAD_BYTES"), Some(300));
     }
 }
@@ -0,0 +1,316 @@
+// Recording file format written by tcbee-record
+// Has to be kept in sync with tcbee-record/tcbee-common/src/format.rs!
+//
+// Header:
+//  magic "TCBEEREC" | version u16 | record type u16 | record size u32 | boot offset i64
+//  | start time u64 | layout length u16 | host length u16 | layout | host | crc32 u32
+// Followed by records:
+//  marker "TCBR" | payload length u32 | crc32 of payload u32 | payload
+// A file that was closed by the recorder ends with the marker "TCBE".
+// All integers are little endian, payloads are bincode encoded structs.
+use std::fmt::Display;
+
+pub const FILE_MAGIC: [u8; 8] = *b"TCBEEREC";
+pub const FORMAT_VERSION: u16 = 1;
+pub const RECORD_MARKER: [u8; 4] = *b"TCBR";
+pub const END_MARKER: [u8; 4] = *b"TCBE";
+
+// Size of the header fields up to the variable length layout and host strings
+pub const FIXED_HEADER_SIZE: usize = 8 + 2 + 2 + 4 + 8 + 8 + 2 + 2;
+// Marker, length and checksum in front of every record
+pub const RECORD_FRAME_SIZE: usize = 4 + 4 + 4;
+
+// Record types, identify the struct stored in a file
+pub const RECORD_TCP_PACKET: u16 = 1;
+pub const RECORD_TCP_PROBE: u16 = 2;
+pub const RECORD_SOCK: u16 = 3;
+pub const RECORD_CWND: u16 = 4;
+pub const RECORD_RETRANSMIT_SYNACK: u16 = 5;
+pub const RECORD_BAD_CSUM: u16 = 6;
+
+// Recordings without a file header end every entry with this marker
+pub const LEGACY_MARKER: [u8; 4] = [255, 255, 255, 255];
+// Offset of the sock_cookie field added to socket and cwnd records with the versioned format
+pub const LEGACY_COOKIE_OFFSET: usize = 54;
+
+// Implemented by all structs that can be read from a recording
+pub trait RecordFormat {
+    const RECORD_TYPE: u16;
+    // Field names and types in order of serialization, has to match the recorder
+    const LAYOUT: &'static str;
+    // Size of the struct in recordings without header, None if it was not recorded before
+    const LEGACY_SIZE: Option<usize> = None;
+
+    /// Converts a struct of a recording without header to the current layout
+    fn from_legacy(entry: &[u8]) -> Vec<u8> {
+        entry.to_vec()
+    }
+}
+
+/// Adds an empty sock_cookie to a socket or cwnd struct of a recording without header
+pub fn insert_legacy_cookie(entry: &[u8]) -> Vec<u8> {
+    let mut converted = entry[..LEGACY_COOKIE_OFFSET].to_vec();
+    converted.extend_from_slice(&0u64.to_le_bytes());
+    converted.extend_from_slice(&entry[LEGACY_COOKIE_OFFSET..]);
+    converted
+}
+
+#[derive(Debug, Clone)]
+pub struct FileHeader {
+    pub version: u16,
+    pub record_type: u16,
+    pub record_size: u32,
+    // Nanoseconds to add to a recorded timestamp to get nanoseconds since UNIX epoch
+    pub boot_offset: i64,
+    // Timestamp of the start of the recording, same clock as recorded timestamps
+    pub start_time: u64,
+    pub layout: String,
+    pub host: String,
+}
+
+#[derive(Debug)]
+pub enum FormatError {
+    UnsupportedVersion(u16),
+    WrongRecordType { expected: u16, found: u16 },
+    LayoutMismatch { expected: String, found: String },
+    RecordSizeMismatch { expected: usize, found: u32 },
+    HeaderChecksum,
+    Truncated,
+    MissingHeader,
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FormatError::UnsupportedVersion(version) => write!(
+                f,
+                "Recording has format version {}, this tool only reads version {}",
+                version, FORMAT_VERSION
+            ),
+            FormatError::WrongRecordType { expected, found } => write!(
+                f,
+                "Recording contains record type {}, expected {}",
+                found, expected
+            ),
+            FormatError::LayoutMismatch { expected, found } => write!(
+                f,
+                "Recording struct layout does not match! Expected {} but found {}",
+                expected, found
+            ),
+            FormatError::RecordSizeMismatch { expected, found } => write!(
+                f,
+                "Recording record size is {} bytes, expected {}",
+                found, expected
+            ),
+            FormatError::HeaderChecksum => write!(f, "Recording header checksum is invalid"),
+            FormatError::Truncated => write!(f, "Recording header is truncated"),
+            FormatError::MissingHeader => write!(
+                f,
+                "Recording has no file header, but this record type was never recorded without one"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+impl FileHeader {
+    /// Number of bytes following the fixed header fields, including the checksum
+    pub fn remaining_size(fixed: &[u8]) -> usize {
+        read_u16(fixed, 32) as usize + read_u16(fixed, 34) as usize + 4
+    }
+
+    /// Parses a complete header, fixed holds FIXED_HEADER_SIZE bytes, rest the remaining bytes
+    pub fn parse(fixed: &[u8], rest: &[u8]) -> Result<FileHeader, FormatError> {
+        if fixed.len() < FIXED_HEADER_SIZE || rest.len() != FileHeader::remaining_size(fixed) {
+            return Err(FormatError::Truncated);
+        }
+
+        // Checksum covers everything but the checksum itself
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(fixed);
+        hasher.update(&rest[..rest.len() - 4]);
+        if hasher.finalize() != read_u32(rest, rest.len() - 4) {
+            return Err(FormatError::HeaderChecksum);
+        }
+
+        let version = read_u16(fixed, 8);
+        if version != FORMAT_VERSION {
+            return Err(FormatError::UnsupportedVersion(version));
+        }
+
+        let layout_len = read_u16(fixed, 32) as usize;
+        let host_len = read_u16(fixed, 34) as usize;
+
+        Ok(FileHeader {
+            version,
+            record_type: read_u16(fixed, 10),
+            record_size: read_u32(fixed, 12),
+            boot_offset: read_u64(fixed, 16) as i64,
+            start_time: read_u64(fixed, 24),
+            layout: String::from_utf8_lossy(&rest[..layout_len]).to_string(),
+            host: String::from_utf8_lossy(&rest[layout_len..layout_len + host_len]).to_string(),
+        })
+    }
+
+    /// Checks that the file contains records of type T
+    pub fn validate<T: RecordFormat>(&self, record_size: usize) -> Result<(), FormatError> {
+        if self.record_type != T::RECORD_TYPE {
+            return Err(FormatError::WrongRecordType {
+                expected: T::RECORD_TYPE,
+                found: self.record_type,
+            });
+        }
+        if self.layout != T::LAYOUT {
+            return Err(FormatError::LayoutMismatch {
+                expected: T::LAYOUT.to_string(),
+                found: self.layout.clone(),
+            });
+        }
+        if self.record_size as usize != record_size {
+            return Err(FormatError::RecordSizeMismatch {
+                expected: record_size,
+                found: self.record_size,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Parses length and checksum of a record frame, the marker has to be checked before
+pub fn parse_record_frame(frame: &[u8]) -> (u32, u32) {
+    (read_u32(frame, 4), read_u32(frame, 8))
+}
+
+pub fn record_checksum(payload: &[u8]) -> u32 {
+    crc32fast::hash(payload)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::bindings::{cwnd::cwnd_trace_entry, tcp_bad_csum::tcp_bad_csum_entry};
+    use crate::reader::FromBuffer;
+
+    pub(crate) const BOOT_OFFSET: i64 = 1_700_000_000_000_000_000;
+
+    // Header as written by tcbee-record for records of type T
+    pub(crate) fn header<T: RecordFormat + FromBuffer>(start_time: u64) -> Vec<u8> {
+        let layout = T::LAYOUT.as_bytes();
+        let host = b"host";
+
+        let mut header = FILE_MAGIC.to_vec();
+        header.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        header.extend_from_slice(&T::RECORD_TYPE.to_le_bytes());
+        header.extend_from_slice(&(T::ENTRY_SIZE as u32).to_le_bytes());
+        header.extend_from_slice(&BOOT_OFFSET.to_le_bytes());
+        header.extend_from_slice(&start_time.to_le_bytes());
+        header.extend_from_slice(&(layout.len() as u16).to_le_bytes());
+        header.extend_from_slice(&(host.len() as u16).to_le_bytes());
+        header.extend_from_slice(layout);
+        header.extend_from_slice(host);
+        let checksum = crc32fast::hash(&header);
+        header.extend_from_slice(&checksum.to_le_bytes());
+        header
+    }
+
+    pub(crate) fn record(payload: &[u8]) -> Vec<u8> {
+        let mut record = RECORD_MARKER.to_vec();
+        record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        record.extend_from_slice(&record_checksum(payload).to_le_bytes());
+        record.extend_from_slice(payload);
+        record
+    }
+
+    fn parse(header: &[u8]) -> Result<FileHeader, FormatError> {
+        FileHeader::parse(&header[..FIXED_HEADER_SIZE], &header[FIXED_HEADER_SIZE..])
+    }
+
+    #[test]
+    fn header_round_trip() {
+        let bytes = header::<tcp_bad_csum_entry>(500);
+        assert_eq!(
+            FileHeader::remaining_size(&bytes[..FIXED_HEADER_SIZE]),
+            bytes.len() - FIXED_HEADER_SIZE
+        );
+
+        let parsed = parse(&bytes).unwrap();
+        assert_eq!(parsed.version, FORMAT_VERSION);
+        assert_eq!(parsed.record_type, RECORD_BAD_CSUM);
+        assert_eq!(parsed.boot_offset, BOOT_OFFSET);
+        assert_eq!(parsed.start_time, 500);
+        assert_eq!(parsed.layout, tcp_bad_csum_entry::LAYOUT);
+        assert_eq!(parsed.host, "host");
+
+        assert!(parsed
+            .validate::<tcp_bad_csum_entry>(tcp_bad_csum_entry::ENTRY_SIZE)
+            .is_ok());
+        assert!(matches!(
+            parsed.validate::<cwnd_trace_entry>(cwnd_trace_entry::ENTRY_SIZE),
+            Err(FormatError::WrongRecordType {
+                expected: RECORD_CWND,
+                found: RECORD_BAD_CSUM
+            })
+        ));
+        assert!(matches!(
+            parsed.validate::<tcp_bad_csum_entry>(16),
+            Err(FormatError::RecordSizeMismatch {
+                expected: 16,
+                found: 64
+            })
+        ));
+
+        let mut other_layout = parsed.clone();
+        other_layout.layout = "time:u64".to_string();
+        assert!(matches!(
+            other_layout.validate::<tcp_bad_csum_entry>(tcp_bad_csum_entry::ENTRY_SIZE),
+            Err(FormatError::LayoutMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn corrupt_headers() {
+        let bytes = header::<tcp_bad_csum_entry>(500);
+
+        let mut flipped = bytes.clone();
+        flipped[20] ^= 1;
+        assert!(matches!(parse(&flipped), Err(FormatError::HeaderChecksum)));
+
+        // Checksum is valid, but the version is unknown
+        let mut newer = bytes[..bytes.len() - 4].to_vec();
+        newer[8..10].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        let checksum = crc32fast::hash(&newer);
+        newer.extend_from_slice(&checksum.to_le_bytes());
+        assert!(matches!(
+            parse(&newer),
+            Err(FormatError::UnsupportedVersion(version)) if version == FORMAT_VERSION + 1
+        ));
+
+        assert!(matches!(
+            parse(&bytes[..bytes.len() - 1]),
+            Err(FormatError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn record_frame() {
+        let framed = record(&[1, 2, 3]);
+        assert_eq!(framed.len(), RECORD_FRAME_SIZE + 3);
+        assert_eq!(
+            parse_record_frame(&framed[..RECORD_FRAME_SIZE]),
+            (3, record_checksum(&[1, 2, 3]))
+        );
+    }
+}
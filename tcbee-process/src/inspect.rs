@@ -0,0 +1,797 @@
+// Inspection of recording files without a database
+// Decodes a *.tcp file with the same bindings as the import and prints its records,
+// per-flow counts and time ranges and every framing error found on the way.
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::Display,
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    net::IpAddr,
+    path::Path,
+};
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use ts_storage::{DataValue, IpTuple};
+
+use crate::{
+    bindings::{
+        cwnd::cwnd_trace_entry, sock::sock_trace_entry, tcp_bad_csum::tcp_bad_csum_entry,
+        tcp_packet::TcpPacket, tcp_probe::TcpProbe,
+        tcp_retransmit_synack::tcp_retransmit_synack_entry,
+    },
+    db_writer::StreamKey,
+    flow_tracker::EventIndexer,
+    format::{
+        parse_record_frame, record_checksum, FileHeader, FormatError, RecordFormat, END_MARKER,
+        FILE_MAGIC, FIXED_HEADER_SIZE, LEGACY_MARKER, RECORD_BAD_CSUM, RECORD_CWND,
+        RECORD_FRAME_SIZE, RECORD_MARKER, RECORD_RETRANSMIT_SYNACK, RECORD_SOCK, RECORD_TCP_PACKET,
+        RECORD_TCP_PROBE,
+    },
+    reader::FromBuffer,
+};
+
+// Framing errors listed in table output, all are counted
+const MAX_LISTED_ERRORS: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn from_argument(argument: &str) -> Option<OutputFormat> {
+        match argument {
+            "table" => Some(OutputFormat::Table),
+            "json" => Some(OutputFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+// Problem in the structure of a file, offsets are byte positions in the file
+#[derive(Debug)]
+enum FramingError {
+    // Bytes between the end of a record and the next marker
+    MissingMarker { offset: u64, skipped: u64 },
+    WrongLength { offset: u64, length: u32 },
+    BadChecksum { offset: u64 },
+    // Payload that bincode cannot decode, imported as default values
+    Undecodable { offset: u64 },
+    // File ends inside a record
+    ShortRead { offset: u64, missing: usize },
+    // Recorder did not write the end marker, e.g. it was killed or is still running
+    NotClosed,
+    TrailingData { offset: u64 },
+}
+
+impl Display for FramingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FramingError::MissingMarker { offset, skipped } => write!(
+                f,
+                "{}: missing record marker, skipped {} bytes to the next one",
+                offset, skipped
+            ),
+            FramingError::WrongLength { offset, length } => {
+                write!(
+                    f,
+                    "{}: record length {} does not match the format",
+                    offset, length
+                )
+            }
+            FramingError::BadChecksum { offset } => {
+                write!(f, "{}: invalid record checksum", offset)
+            }
+            FramingError::Undecodable { offset } => {
+                write!(f, "{}: record payload cannot be decoded", offset)
+            }
+            FramingError::ShortRead { offset, missing } => write!(
+                f,
+                "{}: file ends inside a record, {} bytes missing",
+                offset, missing
+            ),
+            FramingError::NotClosed => {
+                write!(f, "end of file: no end marker, recording was not closed")
+            }
+            FramingError::TrailingData { offset } => {
+                write!(f, "{}: data after the end marker", offset)
+            }
+        }
+    }
+}
+
+struct Record {
+    offset: u64,
+    timestamp: f64,
+    tuple: IpTuple,
+    cookie: Option<u64>,
+    fields: Vec<(String, DataValue)>,
+}
+
+struct FlowStats {
+    records: u64,
+    first: f64,
+    last: f64,
+}
+
+// Reads bytes and keeps track of the position in the file
+struct ByteReader {
+    reader: BufReader<File>,
+    position: u64,
+}
+
+impl ByteReader {
+    // Fills as much of buffer as possible, returns the number of bytes read
+    fn fill(&mut self, buffer: &mut [u8]) -> usize {
+        let mut filled = 0;
+        while filled < buffer.len() {
+            match self.reader.read(&mut buffer[filled..]) {
+                Ok(0) | Err(_) => break,
+                Ok(read) => filled += read,
+            }
+        }
+        self.position += filled as u64;
+        filled
+    }
+
+    // Reads until the last bytes read match marker, returns false if the end of file is reached
+    fn resync(&mut self, marker: &[u8]) -> bool {
+        let mut window: Vec<u8> = Vec::with_capacity(marker.len());
+        let mut byte = [0 as u8; 1];
+
+        while self.fill(&mut byte) == 1 {
+            if window.len() == marker.len() {
+                window.remove(0);
+            }
+            window.push(byte[0]);
+
+            if window == marker {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+struct Inspection {
+    path: String,
+    header: Option<FileHeader>,
+    record_type: &'static str,
+    // Number of records to keep for output, None for all
+    limit: Option<usize>,
+    total: u64,
+    records: Vec<Record>,
+    flows: HashMap<StreamKey, FlowStats>,
+    errors: Vec<FramingError>,
+}
+
+impl Inspection {
+    fn add<T: EventIndexer>(&mut self, offset: u64, event: &T) {
+        self.total += 1;
+
+        let timestamp = event.get_timestamp();
+        let tuple = event.get_ip_tuple();
+        let cookie = event.get_sock_cookie();
+
+        let stats = self
+            .flows
+            .entry((tuple.clone(), cookie.unwrap_or(0)))
+            .or_insert(FlowStats {
+                records: 0,
+                first: timestamp,
+                last: timestamp,
+            });
+        stats.records += 1;
+        stats.first = stats.first.min(timestamp);
+        stats.last = stats.last.max(timestamp);
+
+        if self.limit.is_some_and(|limit| self.records.len() >= limit) {
+            return;
+        }
+
+        let fields = (0..=event.get_max_index())
+            .filter_map(|i| {
+                event
+                    .get_field(i)
+                    .map(|value| (event.get_field_name(i).to_string(), value))
+            })
+            .collect();
+
+        self.records.push(Record {
+            offset,
+            timestamp,
+            tuple,
+            cookie,
+            fields,
+        });
+    }
+
+    fn decode<T: EventIndexer + FromBuffer + DeserializeOwned>(
+        &mut self,
+        offset: u64,
+        payload: &Vec<u8>,
+    ) {
+        // FromBuffer falls back to default values, check first to report broken payloads
+        if bincode::deserialize::<T>(payload).is_err() {
+            self.errors.push(FramingError::Undecodable { offset });
+        }
+        self.add(offset, &T::from_buffer(payload));
+    }
+
+    // Reads marker, length and checksum framed records, same as FileReader::run_framed
+    fn read_framed<T: EventIndexer + FromBuffer + DeserializeOwned>(
+        &mut self,
+        reader: &mut ByteReader,
+    ) {
+        let entry_size = T::ENTRY_SIZE;
+
+        let mut frame = vec![0 as u8; RECORD_FRAME_SIZE];
+        let mut buffer = vec![0 as u8; entry_size];
+
+        loop {
+            let mut offset = reader.position;
+
+            let read = reader.fill(&mut frame[..4]);
+            if read == 0 {
+                self.errors.push(FramingError::NotClosed);
+                return;
+            }
+            if read < 4 {
+                self.errors.push(FramingError::ShortRead {
+                    offset,
+                    missing: 4 - read,
+                });
+                return;
+            }
+
+            if frame[..4] == END_MARKER {
+                let end = reader.position;
+                if reader.fill(&mut [0 as u8; 1]) > 0 {
+                    self.errors.push(FramingError::TrailingData { offset: end });
+                }
+                return;
+            }
+
+            if frame[..4] != RECORD_MARKER {
+                let found = reader.resync(&RECORD_MARKER);
+                let next = if found {
+                    reader.position - RECORD_MARKER.len() as u64
+                } else {
+                    reader.position
+                };
+                self.errors.push(FramingError::MissingMarker {
+                    offset,
+                    skipped: next - offset,
+                });
+                if !found {
+                    self.errors.push(FramingError::NotClosed);
+                    return;
+                }
+                offset = next;
+            }
+
+            let read = reader.fill(&mut frame[4..]);
+            if read < RECORD_FRAME_SIZE - 4 {
+                self.errors.push(FramingError::ShortRead {
+                    offset,
+                    missing: RECORD_FRAME_SIZE - 4 - read,
+                });
+                return;
+            }
+
+            let (length, checksum) = parse_record_frame(&frame);
+            if length as usize != entry_size {
+                self.errors
+                    .push(FramingError::WrongLength { offset, length });
+                continue;
+            }
+
+            let read = reader.fill(&mut buffer);
+            if read < entry_size {
+                self.errors.push(FramingError::ShortRead {
+                    offset,
+                    missing: entry_size - read,
+                });
+                return;
+            }
+
+            if record_checksum(&buffer) != checksum {
+                self.errors.push(FramingError::BadChecksum { offset });
+                continue;
+            }
+
+            self.decode::<T>(offset, &buffer);
+        }
+    }
+
+    // Reads entries of the old layout followed by LEGACY_MARKER, same as FileReader::run_legacy
+    fn read_legacy<T: EventIndexer + FromBuffer + RecordFormat + DeserializeOwned>(
+        &mut self,
+        reader: &mut ByteReader,
+    ) {
+        let Some(entry_size) = T::LEGACY_SIZE else {
+            return;
+        };
+
+        let mut buffer = vec![0 as u8; entry_size + LEGACY_MARKER.len()];
+
+        loop {
+            let offset = reader.position;
+
+            let read = reader.fill(&mut buffer);
+            if read == 0 {
+                return;
+            }
+            if read < buffer.len() {
+                self.errors.push(FramingError::ShortRead {
+                    offset,
+                    missing: buffer.len() - read,
+                });
+                return;
+            }
+
+            if buffer[entry_size..] != LEGACY_MARKER {
+                reader.resync(&LEGACY_MARKER);
+                self.errors.push(FramingError::MissingMarker {
+                    offset,
+                    skipped: reader.position - offset,
+                });
+                continue;
+            }
+
+            let entry = T::from_legacy(&buffer[..entry_size]);
+            self.decode::<T>(offset, &entry);
+        }
+    }
+}
+
+fn format_tuple(tuple: &IpTuple) -> String {
+    let address = |addr: &IpAddr, port: i64| match addr {
+        IpAddr::V4(addr) => format!("{}:{}", addr, port),
+        IpAddr::V6(addr) => format!("[{}]:{}", addr, port),
+    };
+    format!(
+        "{} -> {}",
+        address(&tuple.src, tuple.sport),
+        address(&tuple.dst, tuple.dport)
+    )
+}
+
+fn json_value(value: &DataValue) -> Value {
+    match value {
+        DataValue::Int(value) => json!(value),
+        DataValue::Float(value) => json!(value),
+        DataValue::Boolean(value) => json!(value),
+        DataValue::String(value) => json!(value),
+    }
+}
+
+impl Inspection {
+    // Flows ordered by their first record
+    fn sorted_flows(&self) -> Vec<(&StreamKey, &FlowStats)> {
+        let mut flows: Vec<_> = self.flows.iter().collect();
+        flows.sort_by(|a, b| a.1.first.total_cmp(&b.1.first));
+        flows
+    }
+
+    fn print_table(&self) {
+        println!("File:    {}", self.path);
+        match &self.header {
+            Some(header) => {
+                println!(
+                    "Format:  version {}, {} records of {} bytes, recorded on {}",
+                    header.version, self.record_type, header.record_size, header.host
+                );
+                println!(
+                    "Start:   {} ns since boot, boot offset {} ns",
+                    header.start_time, header.boot_offset
+                );
+            }
+            None => println!(
+                "Format:  legacy recording without header, {} records",
+                self.record_type
+            ),
+        }
+        println!("Records: {}", self.total);
+
+        if !self.records.is_empty() {
+            println!();
+            println!(
+                "{:>12}  {:>20}  {:<48}  {}",
+                "OFFSET", "TIMESTAMP", "FLOW", "FIELDS"
+            );
+            for record in self.records.iter() {
+                let mut flow = format_tuple(&record.tuple);
+                if let Some(cookie) = record.cookie {
+                    flow = format!("{} #{}", flow, cookie);
+                }
+                let fields: Vec<String> = record
+                    .fields
+                    .iter()
+                    .map(|(name, value)| format!("{}={}", name, value.as_string()))
+                    .collect();
+                println!(
+                    "{:>12}  {:>20.0}  {:<48}  {}",
+                    record.offset,
+                    record.timestamp,
+                    flow,
+                    fields.join(" ")
+                );
+            }
+            if (self.records.len() as u64) < self.total {
+                println!(
+                    "... {} more records",
+                    self.total - self.records.len() as u64
+                );
+            }
+        }
+
+        println!();
+        println!(
+            "{:>10}  {:>20}  {:>20}  {:>12}  {:<48}  {}",
+            "RECORDS", "FIRST", "LAST", "DURATION [s]", "FLOW", "COOKIE"
+        );
+        for ((tuple, cookie), stats) in self.sorted_flows() {
+            println!(
+                "{:>10}  {:>20.0}  {:>20.0}  {:>12.3}  {:<48}  {}",
+                stats.records,
+                stats.first,
+                stats.last,
+                (stats.last - stats.first) / 1e9,
+                format_tuple(tuple),
+                cookie
+            );
+        }
+
+        println!();
+        if self.errors.is_empty() {
+            println!("No framing errors");
+            return;
+        }
+        println!("{} framing errors:", self.errors.len());
+        for error in self.errors.iter().take(MAX_LISTED_ERRORS) {
+            println!("  {}", error);
+        }
+        if self.errors.len() > MAX_LISTED_ERRORS {
+            println!("  ... {} more", self.errors.len() - MAX_LISTED_ERRORS);
+        }
+    }
+
+    fn print_json(&self) {
+        let header = self.header.as_ref().map(|header| {
+            json!({
+                "version": header.version,
+                "record_type": header.record_type,
+                "record_size": header.record_size,
+                "boot_offset": header.boot_offset,
+                "start_time": header.start_time,
+                "host": header.host,
+                "layout": header.layout,
+            })
+        });
+
+        let records: Vec<Value> = self
+            .records
+            .iter()
+            .map(|record| {
+                let fields: serde_json::Map<String, Value> = record
+                    .fields
+                    .iter()
+                    .map(|(name, value)| (name.clone(), json_value(value)))
+                    .collect();
+                json!({
+                    "offset": record.offset,
+                    "timestamp": record.timestamp,
+                    "flow": format_tuple(&record.tuple),
+                    "sock_cookie": record.cookie,
+                    "fields": fields,
+                })
+            })
+            .collect();
+
+        let flows: Vec<Value> = self
+            .sorted_flows()
+            .into_iter()
+            .map(|((tuple, cookie), stats)| {
+                json!({
+                    "flow": format_tuple(tuple),
+                    "sock_cookie": cookie,
+                    "records": stats.records,
+                    "first": stats.first,
+                    "last": stats.last,
+                })
+            })
+            .collect();
+
+        let errors: Vec<String> = self.errors.iter().map(|error| error.to_string()).collect();
+
+        let output = json!({
+            "file": self.path,
+            "record_type": self.record_type,
+            "header": header,
+            "total_records": self.total,
+            "records": records,
+            "flows": flows,
+            "errors": errors,
+        });
+        println!("{}", output);
+    }
+}
+
+// Records start at offset start, after the header
+fn inspect<T>(
+    path: &str,
+    header: Option<FileHeader>,
+    start: u64,
+    record_type: &'static str,
+    format: OutputFormat,
+    limit: Option<usize>,
+) -> Result<(), Box<dyn Error>>
+where
+    T: EventIndexer + FromBuffer + RecordFormat + DeserializeOwned,
+{
+    match &header {
+        Some(header) => header.validate::<T>(T::ENTRY_SIZE)?,
+        None if T::LEGACY_SIZE.is_none() => return Err(FormatError::MissingHeader.into()),
+        None => {}
+    }
+
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut reader = ByteReader {
+        reader: BufReader::new(file),
+        position: start,
+    };
+
+    let mut inspection = Inspection {
+        path: path.to_string(),
+        header,
+        record_type,
+        limit,
+        total: 0,
+        records: Vec::new(),
+        flows: HashMap::new(),
+        errors: Vec::new(),
+    };
+
+    if inspection.header.is_some() {
+        inspection.read_framed::<T>(&mut reader);
+    } else {
+        inspection.read_legacy::<T>(&mut reader);
+    }
+
+    match format {
+        OutputFormat::Table => inspection.print_table(),
+        OutputFormat::Json => inspection.print_json(),
+    }
+    Ok(())
+}
+
+// Legacy recordings have no record type, use the file name written by tcbee-record
+fn record_type_of_name(path: &str) -> Option<u16> {
+    let name = Path::new(path).file_name()?.to_str()?;
+    match name {
+        "xdp.tcp" | "tc.tcp" => Some(RECORD_TCP_PACKET),
+        "probe.tcp" => Some(RECORD_TCP_PROBE),
+        "send_sock.tcp" | "recv_sock.tcp" => Some(RECORD_SOCK),
+        "send_cwnd.tcp" | "recv_cwnd.tcp" => Some(RECORD_CWND),
+        "retransmit_synack.tcp" => Some(RECORD_RETRANSMIT_SYNACK),
+        "bad_csum.tcp" => Some(RECORD_BAD_CSUM),
+        _ => None,
+    }
+}
+
+/// Prints the content of a recording file, limit is the number of records to print, None for all
+pub fn inspect_file(
+    path: &str,
+    format: OutputFormat,
+    limit: Option<usize>,
+) -> Result<(), Box<dyn Error>> {
+    let mut file = File::open(path)?;
+
+    // Same check as the import, a corrupt header is reported as error
+    // Files without header are scanned for the markers of legacy recordings
+    let mut fixed = vec![0 as u8; FIXED_HEADER_SIZE];
+    let (header, start) = if file.read_exact(&mut fixed).is_ok() && fixed[..8] == FILE_MAGIC {
+        let mut rest = vec![0 as u8; FileHeader::remaining_size(&fixed)];
+        file.read_exact(&mut rest)?;
+        (
+            Some(FileHeader::parse(&fixed, &rest)?),
+            (fixed.len() + rest.len()) as u64,
+        )
+    } else {
+        (None, 0)
+    };
+
+    let record_type = match &header {
+        Some(header) => header.record_type,
+        None => record_type_of_name(path).ok_or_else(|| {
+            format!(
+                "{} has no file header and an unknown name, cannot tell which records it contains!",
+                path
+            )
+        })?,
+    };
+
+    match record_type {
+        RECORD_TCP_PACKET => inspect::<TcpPacket>(path, header, start, "packet", format, limit),
+        RECORD_TCP_PROBE => inspect::<TcpProbe>(path, header, start, "probe", format, limit),
+        RECORD_SOCK => inspect::<sock_trace_entry>(path, header, start, "sock", format, limit),
+        RECORD_CWND => inspect::<cwnd_trace_entry>(path, header, start, "cwnd", format, limit),
+        RECORD_RETRANSMIT_SYNACK => inspect::<tcp_retransmit_synack_entry>(
+            path,
+            header,
+            start,
+            "retransmit_synack",
+            format,
+            limit,
+        ),
+        RECORD_BAD_CSUM => {
+            inspect::<tcp_bad_csum_entry>(path, header, start, "bad_csum", format, limit)
+        }
+        other => Err(format!("{} contains unknown record type {}!", path, other).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::tcp_bad_csum::tests::payload;
+    use crate::format::tests::{header, record};
+    use crate::reader::tests::legacy_cwnd;
+    use crate::test_util::TempPath;
+
+    // Reads all records of a bad_csum recording with the given content after the header
+    fn read(name: &str, records: &[u8], limit: Option<usize>) -> Inspection {
+        let mut content = header::<tcp_bad_csum_entry>(0);
+        let start = content.len() as u64;
+        content.extend_from_slice(records);
+
+        let temp = TempPath::with_content(&format!("inspect-{}.tcp", name), &content);
+        let path = temp.as_string();
+
+        let mut file = File::open(&path).unwrap();
+        file.seek(SeekFrom::Start(start)).unwrap();
+        let mut reader = ByteReader {
+            reader: BufReader::new(file),
+            position: start,
+        };
+        let mut inspection = Inspection {
+            path: path.clone(),
+            header: Some(
+                FileHeader::parse(
+                    &content[..FIXED_HEADER_SIZE],
+                    &content[FIXED_HEADER_SIZE..start as usize],
+                )
+                .unwrap(),
+            ),
+            record_type: "bad_csum",
+            limit,
+            total: 0,
+            records: Vec::new(),
+            flows: HashMap::new(),
+            errors: Vec::new(),
+        };
+        inspection.read_framed::<tcp_bad_csum_entry>(&mut reader);
+        inspection
+    }
+
+    #[test]
+    fn complete_recording() {
+        let mut records = record(&payload(300));
+        records.extend(record(&payload(100)));
+        records.extend(record(&payload(200)));
+        records.extend_from_slice(&END_MARKER);
+
+        let inspection = read("complete", &records, Some(2));
+        assert!(inspection.errors.is_empty());
+        assert_eq!(inspection.total, 3);
+        // Only the limit is kept for output, all are counted
+        assert_eq!(inspection.records.len(), 2);
+        assert_eq!(inspection.records[0].timestamp, 300.0);
+
+        assert_eq!(inspection.flows.len(), 1);
+        let stats = inspection.flows.values().next().unwrap();
+        assert_eq!(stats.records, 3);
+        assert_eq!((stats.first, stats.last), (100.0, 300.0));
+    }
+
+    #[test]
+    fn framing_errors() {
+        let frame = RECORD_FRAME_SIZE as u64 + tcp_bad_csum_entry::ENTRY_SIZE as u64;
+        let start = header::<tcp_bad_csum_entry>(0).len() as u64;
+
+        let mut records = record(&payload(1));
+        records.extend_from_slice(&[0xAB; 5]);
+        let mut corrupt = record(&payload(2));
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 1;
+        records.extend(corrupt);
+        records.extend(record(&payload(3)));
+        records.extend_from_slice(&END_MARKER);
+        records.push(0);
+
+        let inspection = read("errors", &records, None);
+        assert_eq!(inspection.total, 2);
+        let errors: Vec<String> = inspection.errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                format!(
+                    "{}: missing record marker, skipped 5 bytes to the next one",
+                    start + frame
+                ),
+                format!("{}: invalid record checksum", start + frame + 5),
+                format!("{}: data after the end marker", start + 3 * frame + 9),
+            ]
+        );
+    }
+
+    #[test]
+    fn unclosed_recording() {
+        let mut records = record(&payload(1));
+        records.extend_from_slice(&record(&payload(2))[..20]);
+
+        let inspection = read("unclosed", &records, None);
+        assert_eq!(inspection.total, 1);
+        assert!(matches!(
+            inspection.errors[..],
+            [FramingError::ShortRead { missing: 56, .. }]
+        ));
+
+        let inspection = read("killed", &record(&payload(1)), None);
+        assert!(matches!(inspection.errors[..], [FramingError::NotClosed]));
+    }
+
+    #[test]
+    fn legacy_recording() {
+        let mut content = legacy_cwnd(1, 10);
+        content.extend_from_slice(&[0xAB; 5]);
+        content.extend_from_slice(&LEGACY_MARKER);
+        content.extend(legacy_cwnd(2, 20));
+        content.extend(legacy_cwnd(3, 30));
+
+        let temp = TempPath::with_content("inspect-legacy.tcp", &content);
+        let mut reader = ByteReader {
+            reader: BufReader::new(File::open(temp.path()).unwrap()),
+            position: 0,
+        };
+        let mut inspection = Inspection {
+            path: temp.as_string(),
+            header: None,
+            record_type: "cwnd",
+            limit: None,
+            total: 0,
+            records: Vec::new(),
+            flows: HashMap::new(),
+            errors: Vec::new(),
+        };
+        inspection.read_legacy::<cwnd_trace_entry>(&mut reader);
+
+        // Entry 2 is lost while searching the next marker
+        assert_eq!(inspection.total, 2);
+        assert_eq!(inspection.records[1].timestamp, 3.0);
+        let errors: Vec<String> = inspection.errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            errors,
+            vec!["62: missing record marker, skipped 71 bytes to the next one".to_string()]
+        );
+    }
+
+    #[test]
+    fn files_without_header() {
+        // Record type is taken from the name, bad checksum records were never recorded without header
+        let dir = TempPath::dir("inspect-legacy");
+        let path = dir.path().join("bad_csum.tcp");
+        std::fs::write(&path, payload(1)).unwrap();
+        let result = inspect_file(&path.to_string_lossy(), OutputFormat::Json, None);
+
+        assert!(matches!(
+            result.err().unwrap().downcast_ref::<FormatError>(),
+            Some(FormatError::MissingHeader)
+        ));
+    }
+}
@@ -1,6 +1,20 @@
+mod connections;
 mod db_writer;
+mod export;
+mod fairness;
+mod filter;
 mod flow_tracker;
+mod format;
+mod inspect;
+mod owd;
+mod pcap;
+mod plugin_runner;
 mod reader;
+mod summary;
+#[cfg(test)]
+mod test_util;
+mod timestamps;
+mod writer_shard;
 
 mod bindings {
     pub mod ctypes;
@@ -8,25 +22,40 @@ mod bindings {
     pub mod tcp_packet;
     pub mod tcp_probe;
     pub mod cwnd;
+    pub mod tcp_retransmit_synack;
+    pub mod tcp_bad_csum;
 }
 
-use argparse::{ArgumentParser, Store, StoreTrue};
-use bindings::{sock::sock_trace_entry, cwnd::cwnd_trace_entry, tcp_packet::TcpPacket, tcp_probe::TcpProbe};
+use argparse::{ArgumentParser, Collect, Store, StoreTrue};
+use bindings::{
+    cwnd::cwnd_trace_entry, sock::sock_trace_entry, tcp_bad_csum::tcp_bad_csum_entry,
+    tcp_packet::TcpPacket, tcp_probe::TcpProbe,
+    tcp_retransmit_synack::tcp_retransmit_synack_entry,
+};
+use connections::ConnectionIndex;
 use db_writer::{DBOperation, DBWriter};
-use flow_tracker::EventIndexer;
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use filter::{FlowFilter, IpNetwork, TimeWindow};
+use flow_tracker::{Direction, EventIndexer};
+use format::RecordFormat;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use inspect::OutputFormat;
 use log::{error, info};
-use reader::{FileReader, FromBuffer};
+use pcap::{first_timestamp, CaptureImporter};
+use plugin_runner::PluginRunner;
+use reader::{import_key, read_header, FileReader, FromBuffer};
 use serde::Deserialize;
+use tcbee_plugins::ProcessorImplementation;
+use timestamps::{TimeConverter, TimeMode};
 use tokio::{
+    signal::ctrl_c,
     sync::mpsc::{self, Sender},
     task::{self, JoinHandle},
 };
 use tokio_util::sync::CancellationToken;
-use ts_storage::DBBackend;
+use ts_storage::{database_factory, sqlite::SQLiteTSDB, DBBackend, ImportState};
 
 use std::{
-    error::Error, fmt::Debug, path::Path
+    collections::HashMap, error::Error, fmt::Debug, path::Path, time::Duration
 };
 
 // Kernel sometimes uses a 28 Byte IP Address struct
@@ -40,6 +69,25 @@ fn shorten_to_ipv4(arg: [u8; 28]) -> [u8; 4] {
     std::array::from_fn(|i| arg[i + 4])
 }
 
+// All files written by tcbee-record
+const RECORDING_FILES: [&str; 9] = [
+    "xdp.tcp",
+    "tc.tcp",
+    "probe.tcp",
+    "send_sock.tcp",
+    "recv_sock.tcp",
+    "recv_cwnd.tcp",
+    "send_cwnd.tcp",
+    "retransmit_synack.tcp",
+    "bad_csum.tcp",
+];
+
+// Default number of parallel database writers
+const DEFAULT_WRITERS: usize = 4;
+
+// Max time imported events stay buffered in --follow mode
+const FOLLOW_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
 pub fn prepend_string(mut src: String, prefix: &str) -> String {
     src.insert_str(0, prefix);
     src
@@ -48,13 +96,16 @@ pub fn prepend_string(mut src: String, prefix: &str) -> String {
 
 async fn start_file_reader<
     'a,
-    T: EventIndexer + FromBuffer + Debug + Send + Clone + Deserialize<'a> + 'static,
+    T: EventIndexer + FromBuffer + RecordFormat + Debug + Send + Clone + Deserialize<'a> + 'static,
 >(
     path: String,
     tx: Sender<DBOperation>,
     token: CancellationToken,
     bars: &MultiProgress,
-) -> Option<JoinHandle<()>> {
+    direction: Direction,
+    follow: bool,
+    resume: Option<ImportState>,
+) -> Option<JoinHandle<ImportState>> {
     // Add progress bar to multibar
     let mut progress = ProgressBar::new(0).with_message(path.clone());
     progress.set_style(
@@ -74,7 +125,7 @@ async fn start_file_reader<
 
     // Initialize reader to db
     // TODO: change to if let
-    let reader_res = FileReader::<T>::new(&path, tx.clone(), token, progress).await;
+    let reader_res = FileReader::<T>::new(&path, tx.clone(), token, progress, direction, follow, resume).await;
     if reader_res.is_err() {
         panic!(
             "Could not open File at {} ! Error: {}",
@@ -85,9 +136,147 @@ async fn start_file_reader<
     let mut reader = reader_res.unwrap();
 
     // Start reader
-    Some(task::spawn(async move {
-        reader.run().await;
-    }))
+    Some(task::spawn(async move { reader.run().await }))
+}
+
+// Imports a packet capture in a blocking task
+fn start_capture_reader(
+    path: String,
+    tx: Sender<DBOperation>,
+    token: CancellationToken,
+    bars: &MultiProgress,
+    resume: Option<ImportState>,
+) -> JoinHandle<ImportState> {
+    let mut progress = ProgressBar::new(0).with_message(path.clone());
+    progress.set_style(
+        ProgressStyle::with_template(
+            "{msg} - [{eta_precise}/{elapsed_precise}] {bar:40.cyan/blue} {bytes:>7}/{total_bytes:7}",
+        )
+        .unwrap(),
+    );
+    progress = bars.add(progress);
+
+    let mut importer = CaptureImporter::new(&path, tx, token, progress, resume);
+    task::spawn_blocking(move || match importer.run() {
+        Ok(state) => state,
+        Err(e) => panic!("Could not read capture at {} ! Error: {}", path, e),
+    })
+}
+
+// Reads all socket events once to collect the socket cookies of every connection
+async fn scan_connections(source: &str) -> ConnectionIndex {
+    let (tx, rx) = mpsc::channel::<DBOperation>(100000);
+    let token = CancellationToken::new();
+    let bars = MultiProgress::with_draw_target(ProgressDrawTarget::hidden());
+
+    let index_thread = task::spawn_blocking(move || ConnectionIndex::build(rx));
+
+    let threads = vec![
+        start_file_reader::<TcpProbe>(
+            prepend_string("probe.tcp".to_string(), source),
+            tx.clone(),
+            token.clone(),
+            &bars,
+            Direction::Socket,
+            false,
+            None,
+        )
+        .await,
+        start_file_reader::<sock_trace_entry>(
+            prepend_string("send_sock.tcp".to_string(), source),
+            tx.clone(),
+            token.clone(),
+            &bars,
+            Direction::Egress,
+            false,
+            None,
+        )
+        .await,
+        start_file_reader::<sock_trace_entry>(
+            prepend_string("recv_sock.tcp".to_string(), source),
+            tx.clone(),
+            token.clone(),
+            &bars,
+            Direction::Ingress,
+            false,
+            None,
+        )
+        .await,
+        start_file_reader::<cwnd_trace_entry>(
+            prepend_string("recv_cwnd.tcp".to_string(), source),
+            tx.clone(),
+            token.clone(),
+            &bars,
+            Direction::Ingress,
+            false,
+            None,
+        )
+        .await,
+        start_file_reader::<cwnd_trace_entry>(
+            prepend_string("send_cwnd.tcp".to_string(), source),
+            tx.clone(),
+            token.clone(),
+            &bars,
+            Direction::Egress,
+            false,
+            None,
+        )
+        .await,
+    ];
+
+    for t in threads.into_iter().flatten() {
+        let _res = t.await;
+    }
+    drop(tx);
+
+    index_thread.await.unwrap_or_default()
+}
+
+// Writes the packets of xdp.tcp and tc.tcp to a pcapng file
+async fn export_pcap_recording(
+    source: &str,
+    output: &str,
+    ports: Vec<i64>,
+    time: TimeConverter,
+) -> Result<u64, Box<dyn Error>> {
+    let (tx, rx) = mpsc::channel::<DBOperation>(100000);
+    let token = CancellationToken::new();
+    let bars = MultiProgress::new();
+
+    let output = output.to_string();
+    let export_thread = task::spawn_blocking(move || {
+        export::export_recording(rx, &output, &ports, time).map_err(|e| e.to_string())
+    });
+
+    let threads = vec![
+        start_file_reader::<TcpPacket>(
+            prepend_string("xdp.tcp".to_string(), source),
+            tx.clone(),
+            token.clone(),
+            &bars,
+            Direction::Ingress,
+            false,
+            None,
+        )
+        .await,
+        start_file_reader::<TcpPacket>(
+            prepend_string("tc.tcp".to_string(), source),
+            tx.clone(),
+            token.clone(),
+            &bars,
+            Direction::Egress,
+            false,
+            None,
+        )
+        .await,
+    ];
+
+    for t in threads.into_iter().flatten() {
+        let _res = t.await;
+    }
+    drop(tx);
+
+    Ok(export_thread.await??)
 }
 
 #[tokio::main(flavor = "multi_thread")]
@@ -98,6 +287,29 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let mut output: String = "".to_string();
     let mut sqlite: bool = false;
     let mut duckdb: bool = false;
+    let mut plugin_names: Vec<String> = Vec::new();
+    let mut plugin_ports: Vec<i64> = Vec::new();
+    let mut time_mode: String = "boot".to_string();
+    let mut per_direction: bool = false;
+    let mut follow: bool = false;
+    let mut writers: usize = DEFAULT_WRITERS;
+    let mut pcap: String = "".to_string();
+    let mut export_pcap: String = "".to_string();
+    let mut export_from_db: bool = false;
+    let mut export_ports: Vec<i64> = Vec::new();
+    let mut export_flows: Vec<i64> = Vec::new();
+    let mut filter_ips: Vec<String> = Vec::new();
+    let mut filter_ports: Vec<i64> = Vec::new();
+    let mut min_packets: usize = 0;
+    let mut time_window: String = "".to_string();
+    let mut drop_fields: Vec<String> = Vec::new();
+    let mut inspect_path: String = "".to_string();
+    let mut inspect_format: String = "table".to_string();
+    let mut inspect_records: i64 = 20;
+    let mut owd_peer: String = "".to_string();
+    let mut fairness_flows: Vec<i64> = Vec::new();
+    let mut fairness_window: f64 = 100.0;
+    let mut link_capacity: f64 = 0.0;
 
     {
         let mut argparser = ArgumentParser::new();
@@ -121,11 +333,148 @@ async fn main() -> Result<(), Box<dyn Error>> {
             StoreTrue,
             "Store result to DuckDB, better performance",
         );
+        argparser.refer(&mut plugin_names).add_option(
+            &["--plugin"],
+            Collect,
+            "Run plugin on all flows after import, can be repeated. Available: dummy, upper_window, ce_fraction, cc_state, limiter, lifecycle, reordering, zero_window or path to a .wasm plugin",
+        );
+        argparser.refer(&mut plugin_ports).add_option(
+            &["--plugin-port"],
+            Collect,
+            "Only run plugins on flows with this source or destination port, can be repeated",
+        );
+        argparser.refer(&mut time_mode).add_option(
+            &["-t", "--time"],
+            Store,
+            "Timestamps to store: boot (default, ns since boot), utc (ns since UNIX epoch), recording (ns since recording start) or flow (ns since first event of flow)",
+        );
+        argparser.refer(&mut per_direction).add_option(
+            &["--per-direction"],
+            StoreTrue,
+            "Store ingress and egress of a connection as separate flows instead of one flow with _IN/_OUT series",
+        );
+
+        argparser.refer(&mut writers).add_option(
+            &["-w", "--writers"],
+            Store,
+            "Number of parallel DuckDB writers, flows are split between them. SQLite always uses one writer. Default: 4",
+        );
+        argparser.refer(&mut follow).add_option(
+            &["-f", "--follow"],
+            StoreTrue,
+            "Follow the files of a running recording, stops when the recorder closes them or on Ctrl+C",
+        );
+
+        argparser.refer(&mut pcap).add_option(
+            &["--pcap"],
+            Store,
+            "Import TCP packets of a pcap or pcapng capture instead of a TCBee recording",
+        );
+
+        argparser.refer(&mut filter_ips).add_option(
+            &["--filter-ip"],
+            Collect,
+            "Only import flows from or to this address or network, e.g. 10.0.0.0/8, can be repeated",
+        );
+        argparser.refer(&mut filter_ports).add_option(
+            &["--filter-port"],
+            Collect,
+            "Only import flows with this source or destination port, can be repeated",
+        );
+        argparser.refer(&mut min_packets).add_option(
+            &["--min-packets"],
+            Store,
+            "Only import flows with at least this many packets",
+        );
+        argparser.refer(&mut time_window).add_option(
+            &["--time-window"],
+            Store,
+            "Only import events in <start>:<end> seconds since the start of the recording, e.g. 10:30 or 60:",
+        );
+        argparser.refer(&mut drop_fields).add_option(
+            &["--drop-field"],
+            Collect,
+            "Do not store series of this field, e.g. CHECKSUM, can be repeated",
+        );
+
+        argparser.refer(&mut export_pcap).add_option(
+            &["--export-pcap"],
+            Store,
+            "Write the recorded packet headers to this pcapng file instead of importing them",
+        );
+        argparser.refer(&mut export_from_db).add_option(
+            &["--export-from-db"],
+            StoreTrue,
+            "Export the packets stored in the database selected with -q/-d and -o instead of the recording",
+        );
+        argparser.refer(&mut export_ports).add_option(
+            &["--export-port"],
+            Collect,
+            "Only export packets with this source or destination port, can be repeated",
+        );
+        argparser.refer(&mut export_flows).add_option(
+            &["--export-flow"],
+            Collect,
+            "Only export the flow with this database id, can be repeated. Requires --export-from-db",
+        );
+
+        argparser.refer(&mut inspect_path).add_option(
+            &["--inspect"],
+            Store,
+            "Print the records, flows and framing errors of a single recording file, no database is used",
+        );
+        argparser.refer(&mut inspect_format).add_option(
+            &["--inspect-format"],
+            Store,
+            "Output of --inspect: table (default) or json",
+        );
+        argparser.refer(&mut inspect_records).add_option(
+            &["--inspect-records"],
+            Store,
+            "Number of records printed by --inspect, -1 for all. Default: 20",
+        );
+
+        argparser.refer(&mut owd_peer).add_option(
+            &["--owd-peer"],
+            Store,
+            "Store one-way delays of the flows in the database selected with -q/-d and -o, using the database of a recording at the peer at this path",
+        );
+
+        argparser.refer(&mut fairness_flows).add_option(
+            &["--fairness-flow"],
+            Collect,
+            "Compare the flow with this database id to the other selected flows, repeat for at least two flows. Stores the results in a new group flow",
+        );
+        argparser.refer(&mut fairness_window).add_option(
+            &["--fairness-window"],
+            Store,
+            "Window of the throughput used by --fairness-flow in milliseconds. Default: 100",
+        );
+        argparser.refer(&mut link_capacity).add_option(
+            &["--link-capacity"],
+            Store,
+            "Capacity of the shared link in Mbit/s, enables the utilization of --fairness-flow",
+        );
 
         argparser.parse_args_or_exit();
     }
 
-    if !sqlite && !duckdb {
+    if !inspect_path.is_empty() {
+        let Some(format) = OutputFormat::from_argument(&inspect_format) else {
+            print!("Unknown inspect format {}! Available: table, json", inspect_format);
+            return Ok(());
+        };
+        let limit = usize::try_from(inspect_records).ok();
+        if let Err(e) = inspect::inspect_file(&inspect_path, format, limit) {
+            print!("Could not inspect {}! Error: {}", inspect_path, e);
+        }
+        return Ok(());
+    }
+
+    // Exporting a recording does not need a database
+    let export_only_recording = !export_pcap.is_empty() && !export_from_db;
+
+    if !sqlite && !duckdb && !export_only_recording {
         print!("Please select either --sqlite or --duckdb");
         return Ok(());
     }
@@ -134,6 +483,92 @@ async fn main() -> Result<(), Box<dyn Error>> {
         return Ok(());
     }
 
+    if follow && !pcap.is_empty() {
+        print!("--follow only supports TCBee recordings, not --pcap captures");
+        return Ok(());
+    }
+
+    // Resolve plugins before import to fail early on typos
+    let mut plugins: Vec<ProcessorImplementation> = Vec::new();
+    for name in plugin_names.iter() {
+        match ProcessorImplementation::from_argument(name) {
+            Ok(plugin) => plugins.push(plugin),
+            Err(e) => {
+                print!("{}", e);
+                return Ok(());
+            }
+        }
+    }
+
+    let Some(time_mode) = TimeMode::from_argument(&time_mode) else {
+        print!("Unknown time mode {}! Available: boot, utc, recording, flow", time_mode);
+        return Ok(());
+    };
+
+    let time = if !pcap.is_empty() {
+        // Captures have no file headers, the first packet marks the start of the recording
+        match first_timestamp(&pcap) {
+            Ok(first) => TimeConverter::for_capture(time_mode, first.unwrap_or(0)),
+            Err(e) => {
+                print!("Could not read capture {}! Error: {}", pcap, e);
+                return Ok(());
+            }
+        }
+    } else {
+        // File headers contain the clock offset and start of the recording
+        let mut headers = Vec::new();
+        for file in RECORDING_FILES.iter() {
+            let path = prepend_string(file.to_string(), &source);
+            if !Path::new(&path).exists() {
+                continue;
+            }
+            match read_header(&path) {
+                Ok(Some(header)) => headers.push(header),
+                Ok(None) => {}
+                Err(e) => {
+                    print!("Could not read header of {}! Error: {}", path, e);
+                    return Ok(());
+                }
+            }
+        }
+
+        if headers.is_empty() && !time_window.is_empty() {
+            print!("--time-window needs a recording with file headers!");
+            return Ok(());
+        }
+
+        match TimeConverter::new(time_mode, &headers) {
+            Ok(time) => time,
+            Err(e) => {
+                print!("{}", e);
+                return Ok(());
+            }
+        }
+    };
+
+    let mut networks = Vec::new();
+    for argument in filter_ips.iter() {
+        match IpNetwork::from_argument(argument) {
+            Ok(network) => networks.push(network),
+            Err(e) => {
+                print!("{}", e);
+                return Ok(());
+            }
+        }
+    }
+    let window = if time_window.is_empty() {
+        None
+    } else {
+        match TimeWindow::from_argument(&time_window) {
+            Ok(window) => Some(window),
+            Err(e) => {
+                print!("{}", e);
+                return Ok(());
+            }
+        }
+    };
+    let filter = FlowFilter::new(networks, filter_ports, window, time, min_packets, drop_fields);
+
     if output.is_empty() {
         if sqlite {
             output = "/tmp/db.sqlite".to_string();
@@ -145,9 +580,80 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     let mut backend = DBBackend::SQLite(output.clone());
     if duckdb {
-        backend = DBBackend::DuckDB(output);
+        backend = DBBackend::DuckDB(output.clone());
     }
 
+    // SQLite allows one writer at a time, more writers would only wait for each other
+    if !duckdb && writers > 1 {
+        info!("SQLite only supports one writer, ignoring --writers {}", writers);
+        writers = 1;
+    }
+
+    if !export_pcap.is_empty() {
+        let exported = if export_from_db {
+            let database = match database_factory::<SQLiteTSDB>(backend) {
+                Ok(database) => database,
+                Err(e) => panic!("Could not open Database! Error: {}", e),
+            };
+            export::export_database(database.as_ref(), &export_pcap, &export_ports, &export_flows)?
+        } else {
+            export_pcap_recording(&source, &export_pcap, export_ports, time).await?
+        };
+        println!("Exported {} packets to {}", exported, export_pcap);
+        return Ok(());
+    }
+
+    // Both databases are imported already, the peer uses the same backend
+    if !owd_peer.is_empty() {
+        let peer_backend = if duckdb {
+            DBBackend::DuckDB(owd_peer.clone())
+        } else {
+            DBBackend::SQLite(owd_peer.clone())
+        };
+        let (database, peer) = match (
+            database_factory::<SQLiteTSDB>(backend),
+            database_factory::<SQLiteTSDB>(peer_backend),
+        ) {
+            (Ok(database), Ok(peer)) => (database, peer),
+            (Err(e), _) | (_, Err(e)) => panic!("Could not open Database! Error: {}", e),
+        };
+        let computed = owd::compute_owd(database.as_ref(), peer.as_ref())?;
+        println!("Stored one-way delays of {} flows in {}", computed, output);
+        return Ok(());
+    }
+
+    // Flows were imported before, possibly from several recordings with the same clock
+    if !fairness_flows.is_empty() {
+        let database = match database_factory::<SQLiteTSDB>(backend) {
+            Ok(database) => database,
+            Err(e) => panic!("Could not open Database! Error: {}", e),
+        };
+        let capacity = (link_capacity > 0.0).then_some(link_capacity * 1e6);
+        let group = fairness::compute_fairness(
+            database.as_ref(),
+            &fairness_flows,
+            fairness_window * 1e6,
+            capacity,
+        )?;
+        println!(
+            "Stored fairness of {} flows in group flow {} of {}",
+            fairness_flows.len(),
+            group.id.unwrap_or(0),
+            output
+        );
+        return Ok(());
+    }
+
+    // Socket events of a running recording are not written yet, sockets are indexed during the import
+    // Captures contain no socket events at all
+    let connections = if follow || !pcap.is_empty() {
+        ConnectionIndex::new()
+    } else {
+        println!("Collecting socket cookies of connections...");
+        scan_connections(&source).await
+    };
+    info!("Found {} sockets", connections.num_connections());
+
     let progress_bars = MultiProgress::new();
 
     let status = progress_bars.add(ProgressBar::new(5));
@@ -164,84 +670,202 @@ async fn main() -> Result<(), Box<dyn Error>> {
     info!("Starting db backend!");
     println!("Starting readers, initial processing may be slow due to setup of streams!");
 
+    let database = match database_factory::<SQLiteTSDB>(backend) {
+        Ok(database) => database,
+        Err(e) => panic!("Could not open Database! Error: {}", e),
+    };
+
+    // Import progress of every file, stored when the import stops
+    let state_db = database.new_connection()?;
+    let mut import_states: HashMap<&str, ImportState> = HashMap::new();
+    if !pcap.is_empty() {
+        if let Some(state) = state_db.get_import_state(&import_key(&pcap))? {
+            import_states.insert(pcap.as_str(), state);
+        }
+    } else {
+        for file in RECORDING_FILES.iter() {
+            let path = prepend_string(file.to_string(), &source);
+            if let Some(state) = state_db.get_import_state(&import_key(&path))? {
+                import_states.insert(*file, state);
+            }
+        }
+    }
+    if !import_states.is_empty() {
+        println!("Continuing previous import");
+    }
+    let resume = |file: &str| import_states.get(file).cloned();
+
     // Create DB Backend handler
-    let db_res = DBWriter::new(backend, rx,status);
+    let db_res = DBWriter::new(
+        database,
+        rx,
+        status,
+        time,
+        per_direction,
+        connections,
+        filter,
+        writers,
+    );
     if db_res.is_err() {
         panic!("Could not open Database! Error: {}", db_res.err().unwrap())
     }
     let mut db = db_res.unwrap();
 
-    let _db_thread = task::spawn_blocking(move || {
+    // Returns true if all read events were written
+    let db_thread = task::spawn_blocking(move || {
         let res = db.run();
         if res.is_err() {
             error!(
                 "DB Backend stopping on error! Error: {}",
                 res.err().unwrap()
-            )
+            );
+            return false;
         }
+        true
     });
 
     info!("Starting file readers!");
 
+    // Stop readers on Ctrl+C, events read so far are still written and the import can be continued
+    let token = stop_token.clone();
+    task::spawn(async move {
+        let _ = ctrl_c().await;
+        token.cancel();
+    });
+
+    if follow {
+        println!("Following recording in {}, press Ctrl+C to stop!", source);
+
+        // Periodically write buffered events so that they show up in the database
+        let flush_tx = tx.clone();
+        let token = stop_token.clone();
+        task::spawn(async move {
+            let mut interval = tokio::time::interval(FOLLOW_FLUSH_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        if flush_tx.send(DBOperation::Flush).await.is_err() {
+                            return;
+                        }
+                    }
+                    _ = token.cancelled() => return,
+                }
+            }
+        });
+    }
+
     // Start all tasks
     // TODO: move to config file!
     
-    let threads = vec![
-        start_file_reader::<TcpPacket>(
-            prepend_string("xdp.tcp".to_string(),&source),
-            tx.clone(),
-            stop_token.clone(),
-            &progress_bars,
-        )
-        .await,
-        start_file_reader::<TcpPacket>(
-            prepend_string("tc.tcp".to_string(),&source),
-            tx.clone(),
-            stop_token.clone(),
-            &progress_bars,
-        )
-        .await,
-        start_file_reader::<TcpProbe>(
-            prepend_string("probe.tcp".to_string(),&source),
-            tx.clone(),
-            stop_token.clone(),
-            &progress_bars,
-        )
-        .await,
-        start_file_reader::<sock_trace_entry>(
-            prepend_string("send_sock.tcp".to_string(),&source),
-            tx.clone(),
-            stop_token.clone(),
-            &progress_bars,
-        )
-        .await,
-        start_file_reader::<sock_trace_entry>(
-            prepend_string("recv_sock.tcp".to_string(),&source),
-            tx.clone(),
-            stop_token.clone(),
-            &progress_bars,
-        )
-        .await,
-        start_file_reader::<cwnd_trace_entry>(
-            prepend_string("recv_cwnd.tcp".to_string(),&source),
+    let threads = if !pcap.is_empty() {
+        vec![Some(start_capture_reader(
+            pcap.clone(),
             tx.clone(),
             stop_token.clone(),
             &progress_bars,
-        )
-        .await,
-        start_file_reader::<cwnd_trace_entry>(
-            prepend_string("send_cwnd.tcp".to_string(),&source),
-            tx.clone(),
-            stop_token.clone(),
-            &progress_bars,
-        )
-        .await,
-    ];
+            import_states.get(pcap.as_str()).cloned(),
+        ))]
+    } else {
+        vec![
+            start_file_reader::<TcpPacket>(
+                prepend_string("xdp.tcp".to_string(),&source),
+                tx.clone(),
+                stop_token.clone(),
+                &progress_bars,
+                Direction::Ingress,
+                follow,
+                resume("xdp.tcp"),
+            )
+            .await,
+            start_file_reader::<TcpPacket>(
+                prepend_string("tc.tcp".to_string(),&source),
+                tx.clone(),
+                stop_token.clone(),
+                &progress_bars,
+                Direction::Egress,
+                follow,
+                resume("tc.tcp"),
+            )
+            .await,
+            start_file_reader::<TcpProbe>(
+                prepend_string("probe.tcp".to_string(),&source),
+                tx.clone(),
+                stop_token.clone(),
+                &progress_bars,
+                Direction::Socket,
+                follow,
+                resume("probe.tcp"),
+            )
+            .await,
+            start_file_reader::<sock_trace_entry>(
+                prepend_string("send_sock.tcp".to_string(),&source),
+                tx.clone(),
+                stop_token.clone(),
+                &progress_bars,
+                Direction::Egress,
+                follow,
+                resume("send_sock.tcp"),
+            )
+            .await,
+            start_file_reader::<sock_trace_entry>(
+                prepend_string("recv_sock.tcp".to_string(),&source),
+                tx.clone(),
+                stop_token.clone(),
+                &progress_bars,
+                Direction::Ingress,
+                follow,
+                resume("recv_sock.tcp"),
+            )
+            .await,
+            start_file_reader::<cwnd_trace_entry>(
+                prepend_string("recv_cwnd.tcp".to_string(),&source),
+                tx.clone(),
+                stop_token.clone(),
+                &progress_bars,
+                Direction::Ingress,
+                follow,
+                resume("recv_cwnd.tcp"),
+            )
+            .await,
+            start_file_reader::<cwnd_trace_entry>(
+                prepend_string("send_cwnd.tcp".to_string(),&source),
+                tx.clone(),
+                stop_token.clone(),
+                &progress_bars,
+                Direction::Egress,
+                follow,
+                resume("send_cwnd.tcp"),
+            )
+            .await,
+            start_file_reader::<tcp_retransmit_synack_entry>(
+                prepend_string("retransmit_synack.tcp".to_string(),&source),
+                tx.clone(),
+                stop_token.clone(),
+                &progress_bars,
+                Direction::Socket,
+                follow,
+                resume("retransmit_synack.tcp"),
+            )
+            .await,
+            start_file_reader::<tcp_bad_csum_entry>(
+                prepend_string("bad_csum.tcp".to_string(),&source),
+                tx.clone(),
+                stop_token.clone(),
+                &progress_bars,
+                Direction::Ingress,
+                follow,
+                resume("bad_csum.tcp"),
+            )
+            .await,
+        ]
+    };
 
     // Wait for file threads to finish!
-    // TODO add ctrl + c check!
+    let mut finished_states = Vec::new();
     for t in threads.into_iter().flatten() {
-        let _res = t.await;
+        if let Ok(state) = t.await {
+            finished_states.push(state);
+        }
     }
     // Ensure that all channel tx are dropped to signal db_backend to stop
     drop(tx);
@@ -251,5 +875,41 @@ async fn main() -> Result<(), Box<dyn Error>> {
     // Signal stop to db backend
     stop_token.cancel();
 
+    // Read entries are only imported once the db backend flushed them
+    // After a failed write the progress is kept, the next import repeats the lost events
+    if !db_thread.await.unwrap_or(false) {
+        return Err("Import did not complete, import progress of all files is unchanged!".into());
+    }
+
+    for state in finished_states.iter() {
+        if let Err(e) = state_db.set_import_state(state) {
+            error!("Failed to store import state of {}! Error: {}", state.path, e);
+        }
+    }
+    drop(state_db);
+
+    if plugins.is_empty() {
+        return Ok(());
+    }
+
+    let mut plugin_backend = DBBackend::SQLite(output.clone());
+    if duckdb {
+        plugin_backend = DBBackend::DuckDB(output);
+    }
+
+    let plugin_status = progress_bars.add(ProgressBar::new(0));
+    plugin_status.set_style(
+        ProgressStyle::with_template("{msg} - {bar:40.cyan/blue} {pos:>7}/{len:7}").unwrap(),
+    );
+
+    let mut runner = PluginRunner::new(plugin_backend, plugins, plugin_ports, plugin_status)?;
+    let plugin_thread = task::spawn_blocking(move || {
+        let res = runner.run();
+        if res.is_err() {
+            error!("Plugins stopping on error! Error: {}", res.err().unwrap())
+        }
+    });
+    let _res = plugin_thread.await;
+
     Ok(())
 }
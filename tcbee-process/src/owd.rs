@@ -0,0 +1,321 @@
+// One-way delays between two recordings of the same connections, one at each endpoint
+// A segment sent by one host (tc.tcp) is matched with the segment received by the other (xdp.tcp)
+// by its sequence and acknowledgment number, payload length and flags.
+// Clocks of the hosts differ, the offset is estimated per flow like NTP:
+// the minimum delays of both directions are assumed to be equal.
+// Delays are stored in nanoseconds in the flows of the local database:
+// OWD_OUT at the time the local host sent the segment, OWD_IN at the time it received it.
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+};
+
+use log::{info, warn};
+use tcbee_plugins::{store_series, SeriesData};
+use ts_storage::{DataValue, Flow, FlowAttribute, TSDBInterface};
+
+use crate::{bindings::tcp_packet::TcpPacket, export::flow_packets, flow_tracker::Direction};
+
+// Identifies a segment on both hosts, retransmissions and duplicate ACKs are matched in order
+type SegmentKey = (u32, u32, u16, bool, bool, bool);
+
+fn segment_key(packet: &TcpPacket) -> SegmentKey {
+    (
+        packet.seq,
+        packet.ack,
+        packet.payload_len,
+        packet.flag_syn,
+        packet.flag_fin,
+        packet.flag_rst,
+    )
+}
+
+// Packets of a merged flow sent in direction, ordered by time
+fn packets_of(
+    packets: &[(u64, Option<Direction>, TcpPacket)],
+    direction: Direction,
+) -> Vec<(f64, SegmentKey)> {
+    packets
+        .iter()
+        .filter(|(_, dir, _)| *dir == Some(direction))
+        .map(|(timestamp, _, packet)| (*timestamp as f64, segment_key(packet)))
+        .collect()
+}
+
+/// Matches sent with received segments, returns (send time, receive time) in the clocks of both hosts
+fn match_segments(sent: &[(f64, SegmentKey)], received: &[(f64, SegmentKey)]) -> Vec<(f64, f64)> {
+    let mut pending: HashMap<SegmentKey, VecDeque<f64>> = HashMap::new();
+    for (timestamp, key) in sent.iter() {
+        pending.entry(*key).or_default().push_back(*timestamp);
+    }
+
+    // Segments lost on the path have no match and stay pending
+    received
+        .iter()
+        .filter_map(|(timestamp, key)| {
+            let send = pending.get_mut(key)?.pop_front()?;
+            Some((send, *timestamp))
+        })
+        .collect()
+}
+
+/// Offset of the peer clock to the local clock, 0 if a direction has no matched segments
+fn estimate_offset(forward: &[(f64, f64)], reverse: &[(f64, f64)]) -> f64 {
+    let min_delay = |matched: &[(f64, f64)]| {
+        matched
+            .iter()
+            .map(|(send, receive)| receive - send)
+            .reduce(f64::min)
+    };
+    match (min_delay(forward), min_delay(reverse)) {
+        (Some(forward), Some(reverse)) => (forward - reverse) / 2.0,
+        _ => 0.0,
+    }
+}
+
+// First and last timestamp of a flow, only comparable between hosts for UTC timestamps
+fn utc_range(db: &dyn TSDBInterface, flow: &Flow) -> Option<(f64, f64)> {
+    let attribute = |name: &str| db.get_flow_attribute(flow, name).ok().map(|a| a.value);
+    if !matches!(attribute("TIME_BASE"), Some(DataValue::String(base)) if base == "utc") {
+        return None;
+    }
+    Some((
+        attribute("FIRST_TIMESTAMP")?.as_float()?,
+        attribute("LAST_TIMESTAMP")?.as_float()?,
+    ))
+}
+
+// Flow of the peer database, the peer stored the connection with the tuple reversed
+// Socket cookies are local to each host, connections that reuse a tuple
+// are told apart by overlapping time ranges. Err if several flows of the peer match.
+fn peer_flow<'a>(
+    db: &dyn TSDBInterface,
+    flow: &Flow,
+    peer: &dyn TSDBInterface,
+    peer_flows: &'a [Flow],
+) -> Result<Option<&'a Flow>, String> {
+    let tuple = Direction::Ingress.connection_tuple(flow.tuple.clone());
+    let mut candidates: Vec<&Flow> = peer_flows
+        .iter()
+        .filter(|peer| peer.tuple == tuple)
+        .collect();
+
+    if let Some((first, last)) = utc_range(db, flow) {
+        candidates.retain(|candidate| match utc_range(peer, candidate) {
+            Some((peer_first, peer_last)) => first <= peer_last && peer_first <= last,
+            None => true,
+        });
+    }
+
+    match candidates.len() {
+        0 => Ok(None),
+        1 => Ok(Some(candidates[0])),
+        n => Err(format!(
+            "{} flows of the peer have the same addresses and ports, import both recordings with --time utc to tell them apart",
+            n
+        )),
+    }
+}
+
+fn owd_of_flow(
+    db: &dyn TSDBInterface,
+    flow: &Flow,
+    peer: &dyn TSDBInterface,
+    peer_flow: &Flow,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let local = flow_packets(db, flow)?;
+    let remote = flow_packets(peer, peer_flow)?;
+
+    // Sent by the local host and received by the peer
+    let forward = match_segments(
+        &packets_of(&local, Direction::Egress),
+        &packets_of(&remote, Direction::Ingress),
+    );
+    // Sent by the peer and received by the local host
+    let reverse = match_segments(
+        &packets_of(&remote, Direction::Egress),
+        &packets_of(&local, Direction::Ingress),
+    );
+    if forward.is_empty() && reverse.is_empty() {
+        return Err("No segments of the flow were recorded by both hosts".into());
+    }
+
+    let offset = estimate_offset(&forward, &reverse);
+
+    // Reordered segments were received in a different order than sent
+    let (mut forward, mut reverse) = (forward, reverse);
+    forward.sort_by(|a, b| a.0.total_cmp(&b.0));
+    reverse.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    let mut owd_out = SeriesData::new("OWD_OUT", DataValue::Float(0.0));
+    for (send, receive) in forward.iter() {
+        owd_out.push(*send, DataValue::Float(receive - send - offset));
+    }
+    let mut owd_in = SeriesData::new("OWD_IN", DataValue::Float(0.0));
+    for (send, receive) in reverse.iter() {
+        owd_in.push(*receive, DataValue::Float(receive - send + offset));
+    }
+
+    let mut created = Vec::new();
+    for series in [owd_out, owd_in] {
+        store_series(db, flow, &series)?;
+        created.push(series.name);
+    }
+
+    let attributes = [
+        ("CLOCK_OFFSET", DataValue::Float(offset)),
+        ("OWD_MATCHED_OUT", DataValue::Int(forward.len() as i64)),
+        ("OWD_MATCHED_IN", DataValue::Int(reverse.len() as i64)),
+    ];
+    for (name, value) in attributes {
+        db.set_flow_attribute(
+            flow,
+            &FlowAttribute {
+                name: name.to_string(),
+                value,
+            },
+        )?;
+        created.push(name.to_string());
+    }
+
+    Ok(created)
+}
+
+/// Stores the one-way delays of every flow of db that the peer database also contains
+/// Both databases need merged flows, returns the number of flows with delays
+pub fn compute_owd(
+    db: &dyn TSDBInterface,
+    peer: &dyn TSDBInterface,
+) -> Result<usize, Box<dyn Error>> {
+    let flows: Vec<Flow> = db.list_flows()?.collect();
+    let peer_flows: Vec<Flow> = peer.list_flows()?.collect();
+
+    let mut computed = 0;
+    for flow in flows.iter() {
+        let peer_flow = match peer_flow(db, flow, peer, &peer_flows) {
+            Ok(Some(peer_flow)) => peer_flow,
+            Ok(None) => {
+                info!("Flow {:?} was not recorded by the peer", flow.tuple);
+                continue;
+            }
+            Err(e) => {
+                warn!("Skipping flow {:?}: {}", flow.tuple, e);
+                continue;
+            }
+        };
+
+        // Flows without packets of both hosts are expected, e.g. traffic to other hosts
+        match owd_of_flow(db, flow, peer, peer_flow) {
+            Ok(created) => {
+                info!("Created {:?} for flow {:?}", created, flow.tuple);
+                computed += 1;
+            }
+            Err(e) => warn!(
+                "Could not compute one-way delays of flow {:?}: {}. Continuing...",
+                flow.tuple, e
+            ),
+        }
+    }
+
+    Ok(computed)
+}
+
+#[cfg(test)]
+mod tests {
+    use ts_storage::IpTuple;
+
+    use super::*;
+    use crate::test_util::temp_db;
+
+    fn key(seq: u32) -> SegmentKey {
+        (seq, 0, 100, false, false, false)
+    }
+
+    #[test]
+    fn segments_are_matched_in_order() {
+        // Segment 2 is retransmitted, segment 3 is lost
+        let sent = vec![
+            (0.0, key(1)),
+            (10.0, key(2)),
+            (20.0, key(3)),
+            (30.0, key(2)),
+        ];
+        let received = vec![(115.0, key(1)), (140.0, key(2)), (112.0, key(2))];
+
+        assert_eq!(
+            match_segments(&sent, &received),
+            vec![(0.0, 115.0), (10.0, 140.0), (30.0, 112.0)]
+        );
+        assert!(match_segments(&sent, &[(50.0, key(4))]).is_empty());
+    }
+
+    #[test]
+    fn offset_from_minimum_delays() {
+        // Peer clock is 1000 ahead, both directions have a minimum delay of 10
+        let forward = vec![(0.0, 1010.0), (100.0, 1150.0)];
+        let reverse = vec![(2000.0, 1010.0), (2100.0, 1130.0)];
+        assert_eq!(estimate_offset(&forward, &reverse), 1000.0);
+
+        // One direction alone says nothing about the offset
+        assert_eq!(estimate_offset(&forward, &[]), 0.0);
+        assert_eq!(estimate_offset(&[], &reverse), 0.0);
+    }
+
+    #[test]
+    fn reused_tuples_are_told_apart_by_time() {
+        let (_local_file, local) = temp_db("owd-local");
+        let (_peer_file, peer) = temp_db("owd-peer");
+        let tuple = IpTuple {
+            src: "10.0.0.1".parse().unwrap(),
+            dst: "10.0.0.2".parse().unwrap(),
+            sport: 1000,
+            dport: 80,
+            l4proto: 6,
+        };
+        let reversed = Direction::Ingress.connection_tuple(tuple.clone());
+
+        let flow_at =
+            |db: &dyn TSDBInterface, tuple: &IpTuple, cookie: i64, range: Option<(f64, f64)>| {
+                let flow = db.create_flow_with_cookie(tuple, cookie).unwrap();
+                if let Some((first, last)) = range {
+                    let attributes = [
+                        ("TIME_BASE", DataValue::String("utc".to_string())),
+                        ("FIRST_TIMESTAMP", DataValue::Float(first)),
+                        ("LAST_TIMESTAMP", DataValue::Float(last)),
+                    ];
+                    for (name, value) in attributes {
+                        let attribute = FlowAttribute {
+                            name: name.to_string(),
+                            value,
+                        };
+                        db.add_flow_attribute(&flow, &attribute).unwrap();
+                    }
+                }
+                flow
+            };
+
+        // Peer recorded two connections with the same tuple and other socket cookies
+        let first = flow_at(&peer, &reversed, 7, Some((100.0, 200.0))).id;
+        let second = flow_at(&peer, &reversed, 8, Some((300.0, 400.0))).id;
+        let peer_flows: Vec<Flow> = peer.list_flows().unwrap().collect();
+
+        let flow = flow_at(&local, &tuple, 1, Some((310.0, 390.0)));
+        let found = peer_flow(&local, &flow, &peer, &peer_flows).unwrap();
+        assert_eq!(found.and_then(|flow| flow.id), second);
+
+        let flow = flow_at(&local, &tuple, 2, Some((500.0, 600.0)));
+        assert!(peer_flow(&local, &flow, &peer, &peer_flows)
+            .unwrap()
+            .is_none());
+
+        // Without UTC timestamps the connections can not be told apart
+        let flow = flow_at(&local, &tuple, 3, None);
+        assert!(peer_flow(&local, &flow, &peer, &peer_flows).is_err());
+        assert_eq!(
+            peer_flow(&local, &flow, &peer, &peer_flows[..1])
+                .unwrap()
+                .and_then(|flow| flow.id),
+            first
+        );
+    }
+}
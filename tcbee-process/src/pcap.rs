@@ -0,0 +1,1491 @@
+// Import and export of packet captures as written by tcpdump, Wireshark and similar tools
+// Supports pcap (micro- and nanosecond timestamps) and pcapng in both byte orders.
+// Ethernet (with VLAN tags), Linux cooked, loopback and raw IP captures are decoded,
+// TCP packets over IPv4 and IPv6 are converted to TcpPacket entries as recorded by XDP/TC.
+// Exports are written as pcapng with synthesized Ethernet, IP and TCP headers.
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::net::IpAddr;
+
+use indicatif::ProgressBar;
+use log::{info, warn};
+use tokio::sync::mpsc::Sender;
+use tokio_util::sync::CancellationToken;
+use ts_storage::{ImportState, IpTuple};
+
+use crate::{
+    bindings::tcp_packet::TcpPacket,
+    db_writer::DBOperation,
+    flow_tracker::{Direction, EventIndexer},
+    reader::import_key,
+};
+
+// File magics, pcap files are identified by their first four bytes
+const PCAP_MAGIC_MICRO: u32 = 0xa1b2c3d4;
+const PCAP_MAGIC_NANO: u32 = 0xa1b23c4d;
+const PCAPNG_SECTION_HEADER: u32 = 0x0a0d0d0a;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b3c4d;
+
+// pcapng block types, packet records of classic pcap are reported as type 0
+const PCAP_PACKET_RECORD: u32 = 0;
+const BLOCK_INTERFACE_DESCRIPTION: u32 = 1;
+const BLOCK_SIMPLE_PACKET: u32 = 3;
+const BLOCK_ENHANCED_PACKET: u32 = 6;
+
+// Lengths above are treated as corrupt instead of allocating them
+// Largest snaplen of libpcap, and a block with such a frame and plenty of options
+const MAX_FRAME_SIZE: u32 = 262_144;
+const MAX_BLOCK_SIZE: u32 = 16 * 1024 * 1024;
+
+// pcapng option codes
+const OPT_END: u16 = 0;
+const OPT_SHB_USERAPPL: u16 = 4;
+const OPT_IF_NAME: u16 = 2;
+const OPT_IF_TSRESOL: u16 = 9;
+const OPT_EPB_FLAGS: u16 = 2;
+
+// Link layer types
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: [u16; 3] = [0x8100, 0x88a8, 0x9100];
+
+const IPPROTO_TCP: u8 = 6;
+// IPv6 extension headers that may precede the TCP header
+const IPV6_HOP_BY_HOP: u8 = 0;
+const IPV6_ROUTING: u8 = 43;
+const IPV6_FRAGMENT: u8 = 44;
+const IPV6_AUTH: u8 = 51;
+const IPV6_DEST_OPTS: u8 = 60;
+
+// Timestamp resolution if an interface does not define one
+const DEFAULT_TICKS_PER_SECOND: u64 = 1_000_000;
+
+// Interfaces of exported captures, packets are assigned by their direction
+const EXPORT_INTERFACES: [&str; 3] = ["ingress", "egress", "unknown"];
+// Exported timestamps are in nanoseconds, i.e. 10^-9 seconds
+const EXPORT_TSRESOL: u8 = 9;
+const EXPORT_TTL: u8 = 64;
+
+#[derive(Debug)]
+pub enum CaptureError {
+    UnknownFormat(u32),
+    UnknownByteOrder(u32),
+    InvalidBlock { block_type: u32, length: u32 },
+    UnknownInterface(u32),
+}
+
+impl Display for CaptureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CaptureError::UnknownFormat(magic) => {
+                write!(
+                    f,
+                    "File is neither pcap nor pcapng, magic is {:#010x}",
+                    magic
+                )
+            }
+            CaptureError::UnknownByteOrder(magic) => {
+                write!(
+                    f,
+                    "pcapng section has unknown byte order magic {:#010x}",
+                    magic
+                )
+            }
+            CaptureError::InvalidBlock { block_type, length } => write!(
+                f,
+                "Capture block of type {} has invalid length {}",
+                block_type, length
+            ),
+            CaptureError::UnknownInterface(id) => {
+                write!(f, "Packet refers to undefined interface {}", id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+// A captured link layer frame
+pub struct Frame {
+    // Nanoseconds since UNIX epoch
+    pub timestamp: u64,
+    pub linktype: u32,
+    pub data: Vec<u8>,
+    // Only known if the capture stores the direction of packets
+    pub direction: Option<Direction>,
+}
+
+struct Interface {
+    linktype: u32,
+    ticks_per_second: u64,
+}
+
+enum CaptureFormat {
+    Pcap {
+        big_endian: bool,
+        nanos: bool,
+        linktype: u32,
+    },
+    PcapNg {
+        big_endian: bool,
+        interfaces: Vec<Interface>,
+    },
+}
+
+fn read_u16(buf: &[u8], offset: usize, big_endian: bool) -> u16 {
+    let bytes = [buf[offset], buf[offset + 1]];
+    if big_endian {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    }
+}
+
+fn read_u32(buf: &[u8], offset: usize, big_endian: bool) -> u32 {
+    let bytes = buf[offset..offset + 4].try_into().unwrap();
+    if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    }
+}
+
+// Options are code u16 | length u16 | value padded to 4 bytes
+fn find_option(options: &[u8], code: u16, big_endian: bool) -> Option<&[u8]> {
+    let mut offset = 0;
+    while offset + 4 <= options.len() {
+        let opt_code = read_u16(options, offset, big_endian);
+        let length = read_u16(options, offset + 2, big_endian) as usize;
+        if opt_code == OPT_END || offset + 4 + length > options.len() {
+            return None;
+        }
+        if opt_code == code {
+            return Some(&options[offset + 4..offset + 4 + length]);
+        }
+        offset += 4 + length.div_ceil(4) * 4;
+    }
+    None
+}
+
+/// Sequential reader for the frames of a pcap or pcapng file
+pub struct CaptureReader<R> {
+    reader: R,
+    format: CaptureFormat,
+    // Bytes read so far, frames end at this offset
+    position: u64,
+}
+
+impl CaptureReader<BufReader<File>> {
+    pub fn open(path: &str) -> Result<Self, Box<dyn Error>> {
+        CaptureReader::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> CaptureReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, Box<dyn Error>> {
+        let mut magic = [0 as u8; 4];
+        reader.read_exact(&mut magic)?;
+
+        let format = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+            (PCAPNG_SECTION_HEADER, _) => CaptureFormat::PcapNg {
+                big_endian: false,
+                interfaces: Vec::new(),
+            },
+            (PCAP_MAGIC_MICRO, _)
+            | (PCAP_MAGIC_NANO, _)
+            | (_, PCAP_MAGIC_MICRO)
+            | (_, PCAP_MAGIC_NANO) => {
+                let big_endian = u32::from_be_bytes(magic) == PCAP_MAGIC_MICRO
+                    || u32::from_be_bytes(magic) == PCAP_MAGIC_NANO;
+                let nanos = read_u32(&magic, 0, big_endian) == PCAP_MAGIC_NANO;
+
+                // Version, timezone, sigfigs, snaplen and link type
+                let mut header = [0 as u8; 20];
+                reader.read_exact(&mut header)?;
+
+                CaptureFormat::Pcap {
+                    big_endian,
+                    nanos,
+                    linktype: read_u32(&header, 16, big_endian) & 0xffff,
+                }
+            }
+            _ => {
+                return Err(Box::new(CaptureError::UnknownFormat(u32::from_be_bytes(
+                    magic,
+                ))))
+            }
+        };
+
+        let mut capture = CaptureReader {
+            reader,
+            format,
+            position: 4,
+        };
+
+        // The section header block was started by the magic, read the rest of it
+        if let CaptureFormat::PcapNg { .. } = capture.format {
+            capture.read_section_header()?;
+        } else {
+            capture.position += 20;
+        }
+
+        Ok(capture)
+    }
+
+    /// Bytes read so far
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    fn read_bytes(&mut self, length: usize) -> Result<Vec<u8>, std::io::Error> {
+        let mut buf = vec![0 as u8; length];
+        self.reader.read_exact(&mut buf)?;
+        self.position += length as u64;
+        Ok(buf)
+    }
+
+    // Starts a new section, the block type was already read
+    fn read_section_header(&mut self) -> Result<(), Box<dyn Error>> {
+        let start = self.read_bytes(8)?;
+        let big_endian = match u32::from_le_bytes(start[4..8].try_into().unwrap()) {
+            PCAPNG_BYTE_ORDER_MAGIC => false,
+            magic if magic.swap_bytes() == PCAPNG_BYTE_ORDER_MAGIC => true,
+            magic => return Err(Box::new(CaptureError::UnknownByteOrder(magic))),
+        };
+
+        let length = read_u32(&start, 0, big_endian);
+        if length < 28 || length > MAX_BLOCK_SIZE || length % 4 != 0 {
+            return Err(Box::new(CaptureError::InvalidBlock {
+                block_type: PCAPNG_SECTION_HEADER,
+                length,
+            }));
+        }
+        // Version, section length, options and trailing length are not needed
+        self.read_bytes(length as usize - 12)?;
+
+        // Interface ids are only valid within a section
+        self.format = CaptureFormat::PcapNg {
+            big_endian,
+            interfaces: Vec::new(),
+        };
+        Ok(())
+    }
+
+    /// Returns the next frame, None at the end of the file
+    pub fn next_frame(&mut self) -> Result<Option<Frame>, Box<dyn Error>> {
+        match self.format {
+            CaptureFormat::Pcap { .. } => self.next_pcap_frame(),
+            CaptureFormat::PcapNg { .. } => self.next_pcapng_frame(),
+        }
+    }
+
+    fn next_pcap_frame(&mut self) -> Result<Option<Frame>, Box<dyn Error>> {
+        let CaptureFormat::Pcap {
+            big_endian,
+            nanos,
+            linktype,
+        } = self.format
+        else {
+            unreachable!()
+        };
+
+        // Seconds, sub seconds, captured length, original length
+        let header = match self.read_bytes(16) {
+            Ok(header) => header,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(Box::new(e)),
+        };
+
+        let seconds = read_u32(&header, 0, big_endian) as u64;
+        let fraction = read_u32(&header, 4, big_endian) as u64;
+        let captured = read_u32(&header, 8, big_endian);
+        if captured > MAX_FRAME_SIZE {
+            return Err(Box::new(CaptureError::InvalidBlock {
+                block_type: PCAP_PACKET_RECORD,
+                length: captured,
+            }));
+        }
+
+        let data = self.read_bytes(captured as usize)?;
+
+        Ok(Some(Frame {
+            timestamp: seconds * 1_000_000_000 + if nanos { fraction } else { fraction * 1000 },
+            linktype,
+            data,
+            direction: None,
+        }))
+    }
+
+    fn next_pcapng_frame(&mut self) -> Result<Option<Frame>, Box<dyn Error>> {
+        loop {
+            let block_type = match self.read_bytes(4) {
+                Ok(bytes) => u32::from_le_bytes(bytes[..4].try_into().unwrap()),
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(Box::new(e)),
+            };
+
+            // Byte order of the section header is not known before reading it
+            if block_type == PCAPNG_SECTION_HEADER {
+                self.read_section_header()?;
+                continue;
+            }
+
+            let CaptureFormat::PcapNg { big_endian, .. } = self.format else {
+                unreachable!()
+            };
+            let block_type = if big_endian {
+                block_type.swap_bytes()
+            } else {
+                block_type
+            };
+
+            let length = read_u32(&self.read_bytes(4)?, 0, big_endian);
+            if length < 12 || length > MAX_BLOCK_SIZE || length % 4 != 0 {
+                return Err(Box::new(CaptureError::InvalidBlock { block_type, length }));
+            }
+            let body = self.read_bytes(length as usize - 12)?;
+            // Trailing copy of the block length
+            self.read_bytes(4)?;
+
+            match block_type {
+                BLOCK_INTERFACE_DESCRIPTION => self.add_interface(&body, big_endian)?,
+                BLOCK_ENHANCED_PACKET => {
+                    return self.parse_enhanced_packet(&body, big_endian).map(Some)
+                }
+                // Simple packets carry no timestamp and cannot be placed in a time series
+                BLOCK_SIMPLE_PACKET => {}
+                // Name resolution, statistics and custom blocks
+                _ => {}
+            }
+        }
+    }
+
+    fn add_interface(&mut self, body: &[u8], big_endian: bool) -> Result<(), Box<dyn Error>> {
+        if body.len() < 8 {
+            return Err(Box::new(CaptureError::InvalidBlock {
+                block_type: BLOCK_INTERFACE_DESCRIPTION,
+                length: body.len() as u32 + 12,
+            }));
+        }
+
+        // Resolution is 10^-n or 2^-n seconds if the highest bit is set
+        let ticks_per_second = match find_option(&body[8..], OPT_IF_TSRESOL, big_endian) {
+            Some([resolution, ..]) if resolution & 0x80 != 0 => 1u64 << (resolution & 0x7f).min(63),
+            Some([resolution, ..]) => 10u64.pow((*resolution as u32).min(19)),
+            _ => DEFAULT_TICKS_PER_SECOND,
+        };
+
+        if let CaptureFormat::PcapNg { interfaces, .. } = &mut self.format {
+            interfaces.push(Interface {
+                linktype: read_u16(body, 0, big_endian) as u32,
+                ticks_per_second,
+            });
+        }
+        Ok(())
+    }
+
+    fn parse_enhanced_packet(
+        &self,
+        body: &[u8],
+        big_endian: bool,
+    ) -> Result<Frame, Box<dyn Error>> {
+        let CaptureFormat::PcapNg { interfaces, .. } = &self.format else {
+            unreachable!()
+        };
+
+        if body.len() < 20 {
+            return Err(Box::new(CaptureError::InvalidBlock {
+                block_type: BLOCK_ENHANCED_PACKET,
+                length: body.len() as u32 + 12,
+            }));
+        }
+
+        let interface_id = read_u32(body, 0, big_endian);
+        let Some(interface) = interfaces.get(interface_id as usize) else {
+            return Err(Box::new(CaptureError::UnknownInterface(interface_id)));
+        };
+
+        let ticks =
+            ((read_u32(body, 4, big_endian) as u64) << 32) | read_u32(body, 8, big_endian) as u64;
+        let captured = (read_u32(body, 12, big_endian) as usize).min(body.len() - 20);
+        let data = body[20..20 + captured].to_vec();
+
+        // Lowest two bits of the flags are the direction, 1 inbound and 2 outbound
+        let options = &body[(20 + captured.div_ceil(4) * 4).min(body.len())..];
+        let direction = match find_option(options, OPT_EPB_FLAGS, big_endian) {
+            Some(flags) if flags.len() >= 4 => match read_u32(flags, 0, big_endian) & 0x3 {
+                1 => Some(Direction::Ingress),
+                2 => Some(Direction::Egress),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        Ok(Frame {
+            timestamp: (ticks as u128 * 1_000_000_000 / interface.ticks_per_second as u128) as u64,
+            linktype: interface.linktype,
+            data,
+            direction,
+        })
+    }
+}
+
+/// Timestamp of the first frame in a capture, used as start of the recording
+pub fn first_timestamp(path: &str) -> Result<Option<u64>, Box<dyn Error>> {
+    let mut capture = CaptureReader::open(path)?;
+    Ok(capture.next_frame()?.map(|frame| frame.timestamp))
+}
+
+// Returns the network layer protocol and its offset in the frame
+fn network_layer(frame: &Frame) -> Option<(u16, usize)> {
+    let data = &frame.data;
+    match frame.linktype {
+        LINKTYPE_ETHERNET => {
+            let mut offset = 12;
+            let mut ethertype = u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?);
+            // Skip VLAN tags, QinQ frames carry several
+            while ETHERTYPE_VLAN.contains(&ethertype) {
+                offset += 4;
+                ethertype = u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?);
+            }
+            Some((ethertype, offset + 2))
+        }
+        LINKTYPE_LINUX_SLL => Some((u16::from_be_bytes(data.get(14..16)?.try_into().ok()?), 16)),
+        LINKTYPE_LINUX_SLL2 => Some((u16::from_be_bytes(data.get(0..2)?.try_into().ok()?), 20)),
+        // Address family in host byte order, the IP version is enough to tell them apart
+        LINKTYPE_NULL => ip_version(data.get(4..)?).map(|ethertype| (ethertype, 4)),
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => {
+            ip_version(data).map(|ethertype| (ethertype, 0))
+        }
+        _ => None,
+    }
+}
+
+fn ip_version(data: &[u8]) -> Option<u16> {
+    match data.first()? >> 4 {
+        4 => Some(ETHERTYPE_IPV4),
+        6 => Some(ETHERTYPE_IPV6),
+        _ => None,
+    }
+}
+
+/// Decodes a TCP packet from a captured frame, None for all other frames
+pub fn parse_packet(frame: &Frame) -> Option<TcpPacket> {
+    let (ethertype, offset) = network_layer(frame)?;
+    let ip = frame.data.get(offset..)?;
+
+    let mut packet = TcpPacket {
+        time: frame.timestamp,
+        ..Default::default()
+    };
+
+    let ip_payload: u16;
+    let tcp = match ethertype {
+        ETHERTYPE_IPV4 => {
+            let header_length = ((ip.first()? & 0x0f) * 4) as usize;
+            let fragment_offset = u16::from_be_bytes(ip.get(6..8)?.try_into().ok()?) & 0x1fff;
+            // Only the first fragment contains the TCP header
+            if *ip.get(9)? != IPPROTO_TCP || fragment_offset != 0 {
+                return None;
+            }
+            packet.saddr = u32::from_be_bytes(ip.get(12..16)?.try_into().ok()?);
+            packet.daddr = u32::from_be_bytes(ip.get(16..20)?.try_into().ok()?);
+            packet.dscp = ip.get(1)? >> 2;
+            packet.ecn = ip.get(1)? & 0x3;
+            packet.ip_id = u16::from_be_bytes(ip.get(4..6)?.try_into().ok()?);
+            packet.ttl = *ip.get(8)?;
+            // Lengths are taken from the headers, captures may be truncated
+            let total_length = u16::from_be_bytes(ip.get(2..4)?.try_into().ok()?);
+            ip_payload = total_length.saturating_sub(header_length as u16);
+            ip.get(header_length..)?
+        }
+        ETHERTYPE_IPV6 => {
+            packet.saddr_v6 = ip.get(8..24)?.try_into().ok()?;
+            packet.daddr_v6 = ip.get(24..40)?.try_into().ok()?;
+            let traffic_class = u16::from_be_bytes(ip.get(0..2)?.try_into().ok()?) >> 4;
+            packet.dscp = (traffic_class as u8) >> 2;
+            packet.ecn = (traffic_class as u8) & 0x3;
+            packet.ttl = *ip.get(7)?;
+            let payload_length = u16::from_be_bytes(ip.get(4..6)?.try_into().ok()?);
+
+            let mut next_header = *ip.get(6)?;
+            let mut header_offset = 40;
+            loop {
+                match next_header {
+                    IPPROTO_TCP => break,
+                    IPV6_HOP_BY_HOP | IPV6_ROUTING | IPV6_DEST_OPTS => {
+                        next_header = *ip.get(header_offset)?;
+                        header_offset += (*ip.get(header_offset + 1)? as usize + 1) * 8;
+                    }
+                    IPV6_AUTH => {
+                        next_header = *ip.get(header_offset)?;
+                        header_offset += (*ip.get(header_offset + 1)? as usize + 2) * 4;
+                    }
+                    IPV6_FRAGMENT => {
+                        let fragment_offset = u16::from_be_bytes(
+                            ip.get(header_offset + 2..header_offset + 4)?
+                                .try_into()
+                                .ok()?,
+                        ) >> 3;
+                        if fragment_offset != 0 {
+                            return None;
+                        }
+                        next_header = *ip.get(header_offset)?;
+                        header_offset += 8;
+                    }
+                    _ => return None,
+                }
+            }
+            // Payload length includes the extension headers
+            ip_payload = payload_length.saturating_sub(header_offset as u16 - 40);
+            ip.get(header_offset..)?
+        }
+        _ => return None,
+    };
+
+    // Ports, sequence and acknowledgement number, offset, flags, window and checksum
+    let header = tcp.get(..18)?;
+    packet.sport = u16::from_be_bytes([header[0], header[1]]);
+    packet.dport = u16::from_be_bytes([header[2], header[3]]);
+    packet.seq = u32::from_be_bytes(header[4..8].try_into().ok()?);
+    packet.ack = u32::from_be_bytes(header[8..12].try_into().ok()?);
+    let flags = header[13];
+    packet.flag_fin = flags & 0x01 != 0;
+    packet.flag_syn = flags & 0x02 != 0;
+    packet.flag_rst = flags & 0x04 != 0;
+    packet.flag_psh = flags & 0x08 != 0;
+    packet.flag_ack = flags & 0x10 != 0;
+    packet.flag_urg = flags & 0x20 != 0;
+    packet.flag_ece = flags & 0x40 != 0;
+    packet.flag_cwr = flags & 0x80 != 0;
+    packet.window = u16::from_be_bytes([header[14], header[15]]);
+    packet.checksum = u16::from_be_bytes([header[16], header[17]]);
+
+    // Options are between the fixed header and the data offset, truncated captures keep what was captured
+    let header_length = ((header[12] >> 4) as usize * 4).max(20);
+    packet.tcp_hdr_len = header_length as u8;
+    packet.payload_len = ip_payload.saturating_sub(header_length as u16);
+    if let Some(options) = tcp.get(20..header_length.min(tcp.len())) {
+        packet.parse_options(options);
+    }
+
+    Some(packet)
+}
+
+// Endpoints of a connection in a fixed order, independent of the packet direction
+fn connection_key(tuple: &IpTuple) -> (IpAddr, i64, IpAddr, i64) {
+    if (tuple.src, tuple.sport) <= (tuple.dst, tuple.dport) {
+        (tuple.src, tuple.sport, tuple.dst, tuple.dport)
+    } else {
+        (tuple.dst, tuple.dport, tuple.src, tuple.sport)
+    }
+}
+
+/// Reads a capture and passes its TCP packets to the db writer
+pub struct CaptureImporter {
+    path: String,
+    tx: Sender<DBOperation>,
+    token: CancellationToken,
+    progress: ProgressBar,
+    // Import state of a previous run of this capture
+    resume: Option<ImportState>,
+    // Source of the first packet of every connection, i.e. its local side
+    initiators: HashMap<(IpAddr, i64, IpAddr, i64), (IpAddr, i64)>,
+}
+
+impl CaptureImporter {
+    pub fn new(
+        path: &str,
+        tx: Sender<DBOperation>,
+        token: CancellationToken,
+        progress: ProgressBar,
+        resume: Option<ImportState>,
+    ) -> CaptureImporter {
+        CaptureImporter {
+            path: path.to_string(),
+            tx,
+            token,
+            progress,
+            resume,
+            initiators: HashMap::new(),
+        }
+    }
+
+    // Captures without direction flags are oriented from the host that opened the connection
+    fn direction(&mut self, packet: &TcpPacket) -> Direction {
+        let tuple = packet.get_ip_tuple();
+
+        // A SYN/ACK is sent by the host that accepted the connection
+        let initiator = if packet.flag_syn && packet.flag_ack {
+            (tuple.dst, tuple.dport)
+        } else {
+            (tuple.src, tuple.sport)
+        };
+
+        let local = self
+            .initiators
+            .entry(connection_key(&tuple))
+            .or_insert(initiator);
+
+        if *local == (tuple.src, tuple.sport) {
+            Direction::Egress
+        } else {
+            Direction::Ingress
+        }
+    }
+
+    /// Imports all packets, blocks until the capture is read
+    pub fn run(&mut self) -> Result<ImportState, Box<dyn Error>> {
+        let mut capture = CaptureReader::open(&self.path)?;
+        self.progress
+            .set_length(std::fs::metadata(&self.path)?.len());
+
+        let mut state = ImportState {
+            path: import_key(&self.path),
+            file_id: 0,
+            offset: 0,
+        };
+        let mut skip_until = 0;
+        let mut ignored: u64 = 0;
+
+        loop {
+            if self.token.is_cancelled() {
+                break;
+            }
+
+            let frame = match capture.next_frame() {
+                Ok(Some(frame)) => frame,
+                Ok(None) => break,
+                Err(e) => {
+                    warn!(
+                        "Stopping import of {} at byte {}: {}",
+                        self.path,
+                        capture.position(),
+                        e
+                    );
+                    break;
+                }
+            };
+            self.progress.set_position(capture.position());
+
+            // Captures are identified by the time of their first frame
+            if state.file_id == 0 {
+                state.file_id = frame.timestamp as i64;
+                if let Some(resume) = self
+                    .resume
+                    .as_ref()
+                    .filter(|resume| resume.file_id == state.file_id)
+                {
+                    info!(
+                        "Continuing import of {} at byte {}",
+                        self.path, resume.offset
+                    );
+                    skip_until = resume.offset as u64;
+                }
+            }
+
+            let Some(packet) = parse_packet(&frame) else {
+                ignored += 1;
+                continue;
+            };
+
+            // Orientation has to be known for packets of earlier imports as well
+            let direction = frame.direction.unwrap_or_else(|| self.direction(&packet));
+            if capture.position() <= skip_until {
+                continue;
+            }
+
+            if self.tx.blocking_send(packet.as_db_op(direction)).is_err() {
+                info!("Stopping import of {} on channel close!", self.path);
+                break;
+            }
+            state.offset = capture.position() as i64;
+        }
+
+        if ignored > 0 {
+            info!("Ignored {} frames without TCP in {}", ignored, self.path);
+        }
+
+        // Nothing new was read, keep the previous state
+        if state.offset == 0 {
+            state.offset = skip_until as i64;
+        }
+
+        self.progress.finish();
+        Ok(state)
+    }
+}
+
+fn push_option(block: &mut Vec<u8>, code: u16, value: &[u8]) {
+    block.extend_from_slice(&code.to_le_bytes());
+    block.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    block.extend_from_slice(value);
+    block.resize(block.len().div_ceil(4) * 4, 0);
+}
+
+// Internet checksum over the IPv4 header
+fn ipv4_checksum(header: &[u8]) -> u16 {
+    let mut sum: u32 = header
+        .chunks(2)
+        .map(|word| u16::from_be_bytes([word[0], word[1]]) as u32)
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Builds an Ethernet frame for a recorded packet
+/// Recordings contain no payload, so the frame ends after the TCP header while the IP length includes the payload.
+/// MAC addresses are placeholders, the TCP checksum is the recorded one and does not match the synthesized frame.
+pub fn synthesize_frame(packet: &TcpPacket) -> Vec<u8> {
+    let mut frame = vec![0 as u8; 12];
+    let options = packet.options_bytes();
+    let tcp_length = 20 + options.len() as u16;
+    // Recordings of older versions have no TTL
+    let ttl = if packet.ttl > 0 {
+        packet.ttl
+    } else {
+        EXPORT_TTL
+    };
+
+    // Same address family detection as EventIndexer::get_ip_tuple
+    if packet.is_ipv4() {
+        frame.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+        let mut ip = vec![0x45, packet.dscp << 2 | packet.ecn];
+        ip.extend_from_slice(
+            &(20 + tcp_length)
+                .saturating_add(packet.payload_len)
+                .to_be_bytes(),
+        );
+        ip.extend_from_slice(&packet.ip_id.to_be_bytes());
+        // Don't fragment flag
+        ip.extend_from_slice(&[0x40, 0, ttl, IPPROTO_TCP, 0, 0]);
+        ip.extend_from_slice(&packet.saddr.to_be_bytes());
+        ip.extend_from_slice(&packet.daddr.to_be_bytes());
+        let checksum = ipv4_checksum(&ip);
+        ip[10..12].copy_from_slice(&checksum.to_be_bytes());
+        frame.extend_from_slice(&ip);
+    } else {
+        frame.extend_from_slice(&ETHERTYPE_IPV6.to_be_bytes());
+        // Version and traffic class, flow label is not recorded
+        let traffic_class = packet.dscp << 2 | packet.ecn;
+        frame.extend_from_slice(&[0x60 | traffic_class >> 4, (traffic_class & 0xf) << 4, 0, 0]);
+        frame.extend_from_slice(&tcp_length.saturating_add(packet.payload_len).to_be_bytes());
+        frame.extend_from_slice(&[IPPROTO_TCP, ttl]);
+        frame.extend_from_slice(&packet.saddr_v6);
+        frame.extend_from_slice(&packet.daddr_v6);
+    }
+
+    let flags = (packet.flag_fin as u8)
+        | (packet.flag_syn as u8) << 1
+        | (packet.flag_rst as u8) << 2
+        | (packet.flag_psh as u8) << 3
+        | (packet.flag_ack as u8) << 4
+        | (packet.flag_urg as u8) << 5
+        | (packet.flag_ece as u8) << 6
+        | (packet.flag_cwr as u8) << 7;
+
+    frame.extend_from_slice(&packet.sport.to_be_bytes());
+    frame.extend_from_slice(&packet.dport.to_be_bytes());
+    frame.extend_from_slice(&packet.seq.to_be_bytes());
+    frame.extend_from_slice(&packet.ack.to_be_bytes());
+    // Header length in words, including the options
+    frame.extend_from_slice(&[((tcp_length / 4) as u8) << 4, flags]);
+    frame.extend_from_slice(&packet.window.to_be_bytes());
+    frame.extend_from_slice(&packet.checksum.to_be_bytes());
+    // Urgent pointer
+    frame.extend_from_slice(&[0, 0]);
+    frame.extend_from_slice(&options);
+
+    frame
+}
+
+/// Writes packets to a little endian pcapng file with one interface per direction
+pub struct PcapNgWriter<W: Write> {
+    writer: W,
+    packets: u64,
+}
+
+impl PcapNgWriter<BufWriter<File>> {
+    pub fn create(path: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(PcapNgWriter::new(BufWriter::new(File::create(path)?))?)
+    }
+}
+
+impl<W: Write> PcapNgWriter<W> {
+    pub fn new(writer: W) -> Result<Self, std::io::Error> {
+        let mut pcap = PcapNgWriter { writer, packets: 0 };
+
+        // Section header: byte order magic, version 1.0, unknown section length
+        let mut body = Vec::new();
+        body.extend_from_slice(&PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&(-1i64).to_le_bytes());
+        push_option(&mut body, OPT_SHB_USERAPPL, b"tcbee-process");
+        push_option(&mut body, OPT_END, &[]);
+        pcap.write_block(PCAPNG_SECTION_HEADER, &body)?;
+
+        for name in EXPORT_INTERFACES {
+            let mut body = Vec::new();
+            body.extend_from_slice(&(LINKTYPE_ETHERNET as u16).to_le_bytes());
+            // Reserved and snap length, 0 is unlimited
+            body.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+            push_option(&mut body, OPT_IF_NAME, name.as_bytes());
+            push_option(&mut body, OPT_IF_TSRESOL, &[EXPORT_TSRESOL]);
+            push_option(&mut body, OPT_END, &[]);
+            pcap.write_block(BLOCK_INTERFACE_DESCRIPTION, &body)?;
+        }
+
+        Ok(pcap)
+    }
+
+    fn write_block(&mut self, block_type: u32, body: &[u8]) -> Result<(), std::io::Error> {
+        let length = (body.len() + 12) as u32;
+        self.writer.write_all(&block_type.to_le_bytes())?;
+        self.writer.write_all(&length.to_le_bytes())?;
+        self.writer.write_all(body)?;
+        self.writer.write_all(&length.to_le_bytes())
+    }
+
+    /// Writes a packet, timestamp is in nanoseconds since UNIX epoch
+    pub fn write_packet(
+        &mut self,
+        packet: &TcpPacket,
+        direction: Option<Direction>,
+        timestamp: u64,
+    ) -> Result<(), std::io::Error> {
+        let (interface, flags): (u32, u32) = match direction {
+            Some(Direction::Ingress) => (0, 1),
+            Some(Direction::Egress) => (1, 2),
+            _ => (2, 0),
+        };
+
+        let frame = synthesize_frame(packet);
+        // Payload is not captured, the original length tells Wireshark that the packet was truncated
+        let original_length = frame.len() as u32 + packet.payload_len as u32;
+
+        let mut body = Vec::with_capacity(frame.len() + 40);
+        body.extend_from_slice(&interface.to_le_bytes());
+        body.extend_from_slice(&((timestamp >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(timestamp as u32).to_le_bytes());
+        body.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        body.extend_from_slice(&original_length.to_le_bytes());
+        body.extend_from_slice(&frame);
+        body.resize(body.len().div_ceil(4) * 4, 0);
+        if flags != 0 {
+            push_option(&mut body, OPT_EPB_FLAGS, &flags.to_le_bytes());
+            push_option(&mut body, OPT_END, &[]);
+        }
+
+        self.packets += 1;
+        self.write_block(BLOCK_ENHANCED_PACKET, &body)
+    }
+
+    /// Flushes the file, returns the number of written packets
+    pub fn finish(mut self) -> Result<u64, std::io::Error> {
+        self.writer.flush()?;
+        Ok(self.packets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::bindings::tcp_packet::{
+        TCP_OPTION_MSS, TCP_OPTION_SACK, TCP_OPTION_SACK_PERMITTED, TCP_OPTION_TIMESTAMP,
+        TCP_OPTION_WSCALE,
+    };
+    use crate::test_util::TempPath;
+
+    const SYN: u8 = 0x02;
+    const ACK: u8 = 0x10;
+    const PAYLOAD: u16 = 100;
+
+    // TCP header from port 1000 to 80 with an MSS option
+    fn tcp_header(flags: u8) -> Vec<u8> {
+        let mut tcp = Vec::new();
+        tcp.extend_from_slice(&1000u16.to_be_bytes());
+        tcp.extend_from_slice(&80u16.to_be_bytes());
+        tcp.extend_from_slice(&1u32.to_be_bytes());
+        tcp.extend_from_slice(&2u32.to_be_bytes());
+        tcp.extend_from_slice(&[6 << 4, flags]);
+        tcp.extend_from_slice(&512u16.to_be_bytes());
+        tcp.extend_from_slice(&0xbeefu16.to_be_bytes());
+        // Urgent pointer and MSS of 1460
+        tcp.extend_from_slice(&[0, 0, 2, 4, 0x05, 0xb4]);
+        tcp
+    }
+
+    // IPv4 header with ECT(0), payload is not captured
+    fn ipv4(tcp: &[u8]) -> Vec<u8> {
+        let total_length = 20 + tcp.len() as u16 + PAYLOAD;
+        let mut ip = vec![0x45, 0x02];
+        ip.extend_from_slice(&total_length.to_be_bytes());
+        ip.extend_from_slice(&0x1234u16.to_be_bytes());
+        ip.extend_from_slice(&[0x40, 0, 64, IPPROTO_TCP, 0, 0]);
+        ip.extend_from_slice(&Ipv4Addr::new(10, 0, 0, 1).octets());
+        ip.extend_from_slice(&Ipv4Addr::new(10, 0, 0, 2).octets());
+        ip.extend_from_slice(tcp);
+        ip
+    }
+
+    // IPv6 header followed by extension headers, next_header is the type of the first one
+    fn ipv6(next_header: u8, extensions: &[u8], tcp: &[u8]) -> Vec<u8> {
+        let payload_length = (extensions.len() + tcp.len()) as u16 + PAYLOAD;
+        let mut ip = vec![0x60, 0, 0, 0];
+        ip.extend_from_slice(&payload_length.to_be_bytes());
+        ip.extend_from_slice(&[next_header, 64]);
+        ip.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        ip.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        ip.extend_from_slice(extensions);
+        ip.extend_from_slice(tcp);
+        ip
+    }
+
+    fn ethernet(vlan_tags: usize, ethertype: u16, ip: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0 as u8; 12];
+        for _ in 0..vlan_tags {
+            frame.extend_from_slice(&ETHERTYPE_VLAN[0].to_be_bytes());
+            frame.extend_from_slice(&[0, 1]);
+        }
+        frame.extend_from_slice(&ethertype.to_be_bytes());
+        frame.extend_from_slice(ip);
+        frame
+    }
+
+    fn frame(linktype: u32, data: Vec<u8>) -> Frame {
+        Frame {
+            timestamp: 1,
+            linktype,
+            data,
+            direction: None,
+        }
+    }
+
+    fn assert_ipv4_packet(packet: &TcpPacket) {
+        assert_eq!(Ipv4Addr::from(packet.saddr), Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(Ipv4Addr::from(packet.daddr), Ipv4Addr::new(10, 0, 0, 2));
+        assert_eq!((packet.sport, packet.dport), (1000, 80));
+        assert_eq!((packet.seq, packet.ack), (1, 2));
+        assert_eq!((packet.window, packet.checksum), (512, 0xbeef));
+        assert!(packet.flag_ack && !packet.flag_syn);
+        assert_eq!((packet.mss, packet.tcp_hdr_len), (1460, 24));
+        assert_eq!(packet.payload_len, PAYLOAD);
+        assert_eq!((packet.ip_id, packet.ttl, packet.ecn), (0x1234, 64, 2));
+    }
+
+    fn u16_bytes(value: u16, big_endian: bool) -> [u8; 2] {
+        if big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        }
+    }
+
+    fn u32_bytes(value: u32, big_endian: bool) -> [u8; 4] {
+        if big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        }
+    }
+
+    // pcap file with frames of (seconds, fraction, data)
+    fn pcap(
+        big_endian: bool,
+        nanos: bool,
+        linktype: u32,
+        frames: &[(u32, u32, Vec<u8>)],
+    ) -> Vec<u8> {
+        let magic = if nanos {
+            PCAP_MAGIC_NANO
+        } else {
+            PCAP_MAGIC_MICRO
+        };
+        let mut file = u32_bytes(magic, big_endian).to_vec();
+        file.extend_from_slice(&u16_bytes(2, big_endian));
+        file.extend_from_slice(&u16_bytes(4, big_endian));
+        file.extend_from_slice(&[0; 8]);
+        file.extend_from_slice(&u32_bytes(65535, big_endian));
+        file.extend_from_slice(&u32_bytes(linktype, big_endian));
+        for (seconds, fraction, data) in frames {
+            file.extend_from_slice(&u32_bytes(*seconds, big_endian));
+            file.extend_from_slice(&u32_bytes(*fraction, big_endian));
+            file.extend_from_slice(&u32_bytes(data.len() as u32, big_endian));
+            file.extend_from_slice(&u32_bytes(data.len() as u32, big_endian));
+            file.extend_from_slice(data);
+        }
+        file
+    }
+
+    fn pcapng_block(file: &mut Vec<u8>, block_type: u32, body: &[u8], big_endian: bool) {
+        let length = body.len() as u32 + 12;
+        file.extend_from_slice(&u32_bytes(block_type, big_endian));
+        file.extend_from_slice(&u32_bytes(length, big_endian));
+        file.extend_from_slice(body);
+        file.extend_from_slice(&u32_bytes(length, big_endian));
+    }
+
+    fn pcapng_option(body: &mut Vec<u8>, code: u16, value: &[u8], big_endian: bool) {
+        body.extend_from_slice(&u16_bytes(code, big_endian));
+        body.extend_from_slice(&u16_bytes(value.len() as u16, big_endian));
+        body.extend_from_slice(value);
+        body.resize(body.len().div_ceil(4) * 4, 0);
+    }
+
+    // pcapng file with one interface and packets of (ticks, EPB flags, data)
+    fn pcapng(
+        big_endian: bool,
+        tsresol: Option<u8>,
+        linktype: u16,
+        packets: &[(u64, u32, Vec<u8>)],
+    ) -> Vec<u8> {
+        let mut file = Vec::new();
+
+        let mut body = u32_bytes(PCAPNG_BYTE_ORDER_MAGIC, big_endian).to_vec();
+        body.extend_from_slice(&u16_bytes(1, big_endian));
+        body.extend_from_slice(&u16_bytes(0, big_endian));
+        body.extend_from_slice(&[0xff; 8]);
+        pcapng_block(&mut file, PCAPNG_SECTION_HEADER, &body, big_endian);
+
+        let mut body = u16_bytes(linktype, big_endian).to_vec();
+        body.extend_from_slice(&[0; 6]);
+        if let Some(resolution) = tsresol {
+            pcapng_option(&mut body, OPT_IF_TSRESOL, &[resolution], big_endian);
+        }
+        pcapng_option(&mut body, OPT_END, &[], big_endian);
+        pcapng_block(&mut file, BLOCK_INTERFACE_DESCRIPTION, &body, big_endian);
+
+        for (ticks, flags, data) in packets {
+            let mut body = u32_bytes(0, big_endian).to_vec();
+            body.extend_from_slice(&u32_bytes((ticks >> 32) as u32, big_endian));
+            body.extend_from_slice(&u32_bytes(*ticks as u32, big_endian));
+            body.extend_from_slice(&u32_bytes(data.len() as u32, big_endian));
+            body.extend_from_slice(&u32_bytes(data.len() as u32, big_endian));
+            body.extend_from_slice(data);
+            body.resize(body.len().div_ceil(4) * 4, 0);
+            pcapng_option(
+                &mut body,
+                OPT_EPB_FLAGS,
+                &u32_bytes(*flags, big_endian),
+                big_endian,
+            );
+            pcapng_option(&mut body, OPT_END, &[], big_endian);
+            pcapng_block(&mut file, BLOCK_ENHANCED_PACKET, &body, big_endian);
+        }
+        file
+    }
+
+    fn read_all(file: &[u8]) -> Vec<Frame> {
+        let mut capture = CaptureReader::new(file).expect("Capture not recognized!");
+        let mut frames = Vec::new();
+        while let Some(frame) = capture.next_frame().expect("Invalid frame!") {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    #[test]
+    fn pcap_timestamps_in_both_byte_orders() {
+        let data = ethernet(0, ETHERTYPE_IPV4, &ipv4(&tcp_header(ACK)));
+
+        for big_endian in [false, true] {
+            let micro = read_all(&pcap(
+                big_endian,
+                false,
+                LINKTYPE_ETHERNET,
+                &[(10, 5, data.clone())],
+            ));
+            assert_eq!(micro.len(), 1);
+            assert_eq!(micro[0].timestamp, 10_000_005_000);
+            assert_eq!(micro[0].linktype, LINKTYPE_ETHERNET);
+            assert_ipv4_packet(&parse_packet(&micro[0]).unwrap());
+
+            let nano = read_all(&pcap(
+                big_endian,
+                true,
+                LINKTYPE_RAW,
+                &[(10, 5, ipv4(&tcp_header(ACK)))],
+            ));
+            assert_eq!(nano[0].timestamp, 10_000_000_005);
+            assert_ipv4_packet(&parse_packet(&nano[0]).unwrap());
+        }
+    }
+
+    #[test]
+    fn pcapng_resolution_and_direction() {
+        let data = ethernet(0, ETHERTYPE_IPV4, &ipv4(&tcp_header(ACK)));
+
+        for big_endian in [false, true] {
+            // Microseconds without if_tsresol
+            let frames = read_all(&pcapng(
+                big_endian,
+                None,
+                LINKTYPE_ETHERNET as u16,
+                &[
+                    (5, 1, data.clone()),
+                    (6, 2, data.clone()),
+                    (7, 0, data.clone()),
+                ],
+            ));
+            let timestamps: Vec<u64> = frames.iter().map(|frame| frame.timestamp).collect();
+            assert_eq!(timestamps, vec![5000, 6000, 7000]);
+            assert_eq!(frames[0].direction, Some(Direction::Ingress));
+            assert_eq!(frames[1].direction, Some(Direction::Egress));
+            assert_eq!(frames[2].direction, None);
+            assert_ipv4_packet(&parse_packet(&frames[0]).unwrap());
+
+            let nanos = read_all(&pcapng(
+                big_endian,
+                Some(9),
+                LINKTYPE_ETHERNET as u16,
+                &[(5, 0, data.clone())],
+            ));
+            assert_eq!(nanos[0].timestamp, 5);
+
+            // 2^-10 seconds
+            let binary = read_all(&pcapng(
+                big_endian,
+                Some(0x80 | 10),
+                LINKTYPE_ETHERNET as u16,
+                &[(1024, 0, data.clone())],
+            ));
+            assert_eq!(binary[0].timestamp, 1_000_000_000);
+        }
+    }
+
+    #[test]
+    fn unknown_format() {
+        assert!(CaptureReader::new(&[0u8; 24][..]).is_err());
+    }
+
+    fn invalid_length(file: &[u8]) -> Option<(u32, u32)> {
+        let mut capture = CaptureReader::new(file).expect("Capture not recognized!");
+        match capture.next_frame() {
+            Err(e) => match e.downcast_ref::<CaptureError>() {
+                Some(CaptureError::InvalidBlock { block_type, length }) => {
+                    Some((*block_type, *length))
+                }
+                _ => None,
+            },
+            Ok(_) => None,
+        }
+    }
+
+    #[test]
+    fn oversized_lengths_are_rejected() {
+        // Captured length is far beyond any snaplen
+        let mut file = pcap(false, false, LINKTYPE_ETHERNET, &[]);
+        file.extend_from_slice(&[0; 8]);
+        file.extend_from_slice(&0xFFFF_FFF0u32.to_le_bytes());
+        file.extend_from_slice(&0xFFFF_FFF0u32.to_le_bytes());
+        assert_eq!(
+            invalid_length(&file),
+            Some((PCAP_PACKET_RECORD, 0xFFFF_FFF0))
+        );
+
+        for length in [0xFFFF_FFF0, 8] {
+            let mut file = pcapng(false, None, LINKTYPE_ETHERNET as u16, &[]);
+            file.extend_from_slice(&BLOCK_ENHANCED_PACKET.to_le_bytes());
+            file.extend_from_slice(&u32::to_le_bytes(length));
+            assert_eq!(invalid_length(&file), Some((BLOCK_ENHANCED_PACKET, length)));
+        }
+    }
+
+    #[test]
+    fn link_types() {
+        let ip = ipv4(&tcp_header(ACK));
+
+        let mut sll = vec![0 as u8; 14];
+        sll.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+        sll.extend_from_slice(&ip);
+
+        let mut sll2 = ETHERTYPE_IPV4.to_be_bytes().to_vec();
+        sll2.extend_from_slice(&[0; 18]);
+        sll2.extend_from_slice(&ip);
+
+        // Address family in host byte order
+        let mut null = 2u32.to_le_bytes().to_vec();
+        null.extend_from_slice(&ip);
+
+        let frames = [
+            frame(LINKTYPE_ETHERNET, ethernet(0, ETHERTYPE_IPV4, &ip)),
+            frame(LINKTYPE_ETHERNET, ethernet(1, ETHERTYPE_IPV4, &ip)),
+            // QinQ
+            frame(LINKTYPE_ETHERNET, ethernet(2, ETHERTYPE_IPV4, &ip)),
+            frame(LINKTYPE_LINUX_SLL, sll),
+            frame(LINKTYPE_LINUX_SLL2, sll2),
+            frame(LINKTYPE_NULL, null),
+            frame(LINKTYPE_RAW, ip.clone()),
+            frame(LINKTYPE_IPV4, ip.clone()),
+        ];
+        for frame in frames.iter() {
+            let packet = parse_packet(frame)
+                .unwrap_or_else(|| panic!("Link type {} not decoded!", frame.linktype));
+            assert_ipv4_packet(&packet);
+        }
+
+        assert!(parse_packet(&frame(147, ip)).is_none());
+    }
+
+    #[test]
+    fn non_tcp_and_fragments_are_ignored() {
+        let mut udp = ipv4(&tcp_header(ACK));
+        udp[9] = 17;
+        assert!(parse_packet(&frame(LINKTYPE_RAW, udp)).is_none());
+
+        let mut fragment = ipv4(&tcp_header(ACK));
+        fragment[7] = 10;
+        assert!(parse_packet(&frame(LINKTYPE_RAW, fragment)).is_none());
+
+        let arp = ethernet(0, 0x0806, &[0; 28]);
+        assert!(parse_packet(&frame(LINKTYPE_ETHERNET, arp)).is_none());
+    }
+
+    #[test]
+    fn ipv6_extension_headers() {
+        // Hop-by-hop options of 8 bytes, then the first fragment
+        let mut extensions = vec![IPV6_FRAGMENT, 0, 0, 0, 0, 0, 0, 0];
+        extensions.extend_from_slice(&[IPPROTO_TCP, 0, 0, 1, 0, 0, 0, 1]);
+        let ip = ipv6(IPV6_HOP_BY_HOP, &extensions, &tcp_header(SYN));
+
+        let packet = parse_packet(&frame(LINKTYPE_ETHERNET, ethernet(0, ETHERTYPE_IPV6, &ip)))
+            .expect("IPv6 packet not decoded!");
+        assert!(!packet.is_ipv4());
+        assert_eq!(Ipv6Addr::from(packet.saddr_v6), Ipv6Addr::LOCALHOST);
+        assert_eq!((packet.sport, packet.dport), (1000, 80));
+        assert!(packet.flag_syn);
+        assert_eq!((packet.payload_len, packet.ttl), (PAYLOAD, 64));
+
+        // Later fragments contain no TCP header
+        let mut extensions = vec![IPPROTO_TCP, 0, 0, 8, 0, 0, 0, 1];
+        extensions.extend_from_slice(&[0; 8]);
+        let ip = ipv6(IPV6_FRAGMENT, &extensions, &tcp_header(ACK));
+        assert!(parse_packet(&frame(LINKTYPE_IPV6, ip)).is_none());
+    }
+
+    fn importer(
+        path: &str,
+        resume: Option<ImportState>,
+    ) -> (CaptureImporter, mpsc::Receiver<DBOperation>) {
+        let (tx, rx) = mpsc::channel(16);
+        let importer = CaptureImporter::new(
+            path,
+            tx,
+            CancellationToken::new(),
+            ProgressBar::hidden(),
+            resume,
+        );
+        (importer, rx)
+    }
+
+    #[test]
+    fn direction_from_initiator() {
+        let (mut importer, _rx) = importer("", None);
+
+        // Capture starts with the SYN/ACK, its destination opened the connection
+        let syn_ack = parse_packet(&frame(LINKTYPE_RAW, ipv4(&tcp_header(SYN | ACK)))).unwrap();
+        assert_eq!(importer.direction(&syn_ack), Direction::Ingress);
+
+        let mut reply = syn_ack;
+        std::mem::swap(&mut reply.saddr, &mut reply.daddr);
+        std::mem::swap(&mut reply.sport, &mut reply.dport);
+        reply.flag_syn = false;
+        assert_eq!(importer.direction(&reply), Direction::Egress);
+
+        // Without handshake the sender of the first packet is the local host
+        let mut other = parse_packet(&frame(LINKTYPE_RAW, ipv4(&tcp_header(ACK)))).unwrap();
+        other.sport = 2000;
+        assert_eq!(importer.direction(&other), Direction::Egress);
+    }
+
+    #[test]
+    fn resume_skips_imported_frames() {
+        let data = ipv4(&tcp_header(ACK));
+        let file = pcap(
+            false,
+            false,
+            LINKTYPE_RAW,
+            &[
+                (1, 0, data.clone()),
+                (2, 0, data.clone()),
+                (3, 0, data.clone()),
+            ],
+        );
+        let temp = TempPath::with_content("pcap-resume.pcap", &file);
+        let path = temp.as_string();
+
+        let count = |rx: &mut mpsc::Receiver<DBOperation>| {
+            let mut packets = 0;
+            while let Ok(op) = rx.try_recv() {
+                assert!(matches!(op, DBOperation::Packet(_, Direction::Egress)));
+                packets += 1;
+            }
+            packets
+        };
+
+        let (mut first, mut rx) = importer(&path, None);
+        let state = first.run().unwrap();
+        assert_eq!(count(&mut rx), 3);
+        assert_eq!(state.file_id, 1_000_000_000);
+        assert_eq!(state.offset, file.len() as i64);
+
+        // Interrupted after the first frame
+        let after_first = (24 + 16 + data.len()) as i64;
+        let resume = ImportState {
+            offset: after_first,
+            ..state.clone()
+        };
+        let (mut second, mut rx) = importer(&path, Some(resume));
+        assert_eq!(second.run().unwrap().offset, file.len() as i64);
+        assert_eq!(count(&mut rx), 2);
+
+        // Nothing was appended
+        let (mut third, mut rx) = importer(&path, Some(state.clone()));
+        assert_eq!(third.run().unwrap().offset, state.offset);
+        assert_eq!(count(&mut rx), 0);
+
+        // A different capture at the same path is imported from the start
+        let other = ImportState {
+            file_id: 5,
+            ..state
+        };
+        let (mut fourth, mut rx) = importer(&path, Some(other));
+        fourth.run().unwrap();
+        assert_eq!(count(&mut rx), 3);
+    }
+
+    #[test]
+    fn pcapng_export_round_trip() {
+        let mut ipv4 = TcpPacket {
+            saddr: u32::from(Ipv4Addr::new(10, 0, 0, 1)),
+            daddr: u32::from(Ipv4Addr::new(10, 0, 0, 2)),
+            sport: 1000,
+            dport: 80,
+            seq: 1,
+            ack: 2,
+            window: 0,
+            flag_syn: true,
+            flag_ack: true,
+            flag_ece: true,
+            checksum: 0xbeef,
+            payload_len: 1000,
+            ip_id: 7,
+            ttl: 60,
+            dscp: 10,
+            ecn: 3,
+            mss: 1460,
+            wscale: 7,
+            tsval: 100,
+            tsecr: 200,
+            sack_blocks: 2,
+            sack: [1, 2, 3, 4, 0, 0, 0, 0],
+            ..Default::default()
+        };
+        ipv4.options = TCP_OPTION_MSS
+            | TCP_OPTION_WSCALE
+            | TCP_OPTION_SACK_PERMITTED
+            | TCP_OPTION_SACK
+            | TCP_OPTION_TIMESTAMP;
+        let ipv6 = TcpPacket {
+            saddr_v6: Ipv6Addr::LOCALHOST.octets(),
+            daddr_v6: Ipv6Addr::LOCALHOST.octets(),
+            sport: 80,
+            dport: 1000,
+            flag_fin: true,
+            flag_cwr: true,
+            window: 100,
+            ttl: 5,
+            ..Default::default()
+        };
+        let written = [
+            (ipv4, Some(Direction::Egress), 1_500_000_000_123u64),
+            (ipv6, Some(Direction::Ingress), 1_500_000_000_456),
+            (ipv4, None, 1_500_000_000_789),
+        ];
+
+        let mut file: Vec<u8> = Vec::new();
+        let mut writer = PcapNgWriter::new(&mut file).unwrap();
+        for (packet, direction, timestamp) in written.iter() {
+            writer.write_packet(packet, *direction, *timestamp).unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), 3);
+
+        let frames = read_all(&file);
+        assert_eq!(frames.len(), written.len());
+        for (frame, (expected, direction, timestamp)) in frames.iter().zip(written.iter()) {
+            assert_eq!(frame.timestamp, *timestamp);
+            assert_eq!(frame.direction, *direction);
+
+            let packet = parse_packet(frame).expect("Exported packet not decoded!");
+            assert_eq!(packet.get_ip_tuple(), expected.get_ip_tuple());
+            assert_eq!(
+                (packet.seq, packet.ack, packet.window, packet.checksum),
+                (
+                    expected.seq,
+                    expected.ack,
+                    expected.window,
+                    expected.checksum
+                )
+            );
+            assert_eq!(
+                (
+                    packet.flag_syn,
+                    packet.flag_ack,
+                    packet.flag_fin,
+                    packet.flag_ece,
+                    packet.flag_cwr
+                ),
+                (
+                    expected.flag_syn,
+                    expected.flag_ack,
+                    expected.flag_fin,
+                    expected.flag_ece,
+                    expected.flag_cwr
+                )
+            );
+            assert_eq!(
+                (packet.payload_len, packet.ttl, packet.dscp, packet.ecn),
+                (
+                    expected.payload_len,
+                    expected.ttl,
+                    expected.dscp,
+                    expected.ecn
+                )
+            );
+            assert_eq!(packet.is_ipv4(), expected.is_ipv4());
+            if packet.is_ipv4() {
+                assert_eq!(packet.ip_id, expected.ip_id);
+            }
+            assert_eq!(packet.options, expected.options);
+            assert_eq!(
+                (packet.mss, packet.wscale, packet.tsval, packet.tsecr),
+                (
+                    expected.mss,
+                    expected.wscale,
+                    expected.tsval,
+                    expected.tsecr
+                )
+            );
+            assert_eq!(packet.sack_blocks, expected.sack_blocks);
+            assert_eq!(packet.sack, expected.sack);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::error::Error;
+
+use indicatif::ProgressBar;
+use log::{error, info};
+use tcbee_plugins::{run_processor_on_flow, ProcessorImplementation};
+use ts_storage::{database_factory, sqlite::SQLiteTSDB, DBBackend, Flow, TSDBInterface};
+
+// Runs selected plugins on the flows of a finished database
+pub struct PluginRunner {
+    db: Box<dyn TSDBInterface + Send>,
+    plugins: Vec<ProcessorImplementation>,
+    // Only flows with one of these ports as source or destination, all flows if empty
+    ports: Vec<i64>,
+    status: ProgressBar,
+}
+
+impl PluginRunner {
+    pub fn new(
+        backend: DBBackend,
+        plugins: Vec<ProcessorImplementation>,
+        ports: Vec<i64>,
+        status: ProgressBar,
+    ) -> Result<PluginRunner, Box<dyn Error>> {
+        let db: Box<dyn TSDBInterface + Send> = database_factory::<SQLiteTSDB>(backend)?;
+
+        Ok(PluginRunner {
+            db,
+            plugins,
+            ports,
+            status,
+        })
+    }
+
+    fn flow_selected(&self, flow: &Flow) -> bool {
+        self.ports.is_empty()
+            || self.ports.contains(&flow.tuple.sport)
+            || self.ports.contains(&flow.tuple.dport)
+    }
+
+    pub fn run(&mut self) -> Result<(), Box<dyn Error>> {
+        let flows: Vec<Flow> = self
+            .db
+            .list_flows()?
+            .filter(|flow| self.flow_selected(flow))
+            .collect();
+
+        self.status.set_length((flows.len() * self.plugins.len()) as u64);
+        self.status.set_position(0);
+
+        for plugin in self.plugins.iter() {
+            let processor = plugin.create_processor();
+
+            self.status
+                .set_message(format!("Running plugin {}", processor.receive_name()));
+
+            for flow in flows.iter() {
+                self.status.inc(1);
+
+                let res = run_processor_on_flow(self.db.as_ref(), flow, processor.as_ref());
+
+                // Flows without required series are expected, e.g. if no kernel metrics were recorded
+                match res {
+                    Ok(created) => info!(
+                        "Plugin {} created {:?} for flow {:?}",
+                        plugin.cli_name(),
+                        created,
+                        flow.tuple
+                    ),
+                    Err(e) => error!(
+                        "Plugin {} failed on flow {:?}: {}. Continuing...",
+                        plugin.cli_name(),
+                        flow.tuple,
+                        e
+                    ),
+                }
+            }
+        }
+
+        self.status.set_message(format!(
+            "Ran {} plugins on {} flows",
+            self.plugins.len(),
+            flows.len()
+        ));
+
+        Ok(())
+    }
+}
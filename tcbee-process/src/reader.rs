@@ -1,24 +1,65 @@
 use std::fmt::Debug;
+use std::io::SeekFrom;
 use std::os::unix::fs::MetadataExt;
+use std::time::Duration;
 use std::{error::Error, marker::PhantomData};
 
-use log::{debug, info};
+use log::{debug, info, warn};
 use serde::Deserialize;
 use tokio::fs::{File, OpenOptions};
-use tokio::io::{AsyncReadExt, BufReader};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, BufReader};
 use tokio::sync::mpsc::Sender;
 use tokio::task;
 use tokio_util::sync::CancellationToken;
+use ts_storage::ImportState;
 
-use crate::{db_writer::DBOperation, flow_tracker::EventIndexer};
+use crate::format::{
+    parse_record_frame, record_checksum, FileHeader, FormatError, RecordFormat, END_MARKER,
+    FILE_MAGIC, FIXED_HEADER_SIZE, LEGACY_MARKER, RECORD_FRAME_SIZE, RECORD_MARKER,
+};
+use crate::{
+    db_writer::DBOperation,
+    flow_tracker::{Direction, EventIndexer},
+};
 
 use indicatif::ProgressBar;
 
+// Time to wait for new data when following a file that is still recorded
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 pub trait FromBuffer {
+    // Size of the bincode serialized struct
     const ENTRY_SIZE: usize;
     fn from_buffer(buf: &Vec<u8>) -> Self;
 }
 
+/// Reads only the header of a recording file, None for legacy recordings
+pub fn read_header(path: &str) -> Result<Option<FileHeader>, Box<dyn Error>> {
+    let mut infile = std::fs::File::open(path)?;
+
+    let mut fixed = vec![0 as u8; FIXED_HEADER_SIZE];
+    if std::io::Read::read_exact(&mut infile, &mut fixed).is_err() || fixed[..8] != FILE_MAGIC {
+        return Ok(None);
+    }
+
+    let mut rest = vec![0 as u8; FileHeader::remaining_size(&fixed)];
+    std::io::Read::read_exact(&mut infile, &mut rest)?;
+
+    Ok(Some(FileHeader::parse(&fixed, &rest)?))
+}
+
+/// Key of a file in the import state table, independent of how the source directory was given
+pub fn import_key(path: &str) -> String {
+    std::fs::canonicalize(path)
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or(path.to_string())
+}
+
+// Identifies the recording a file belongs to, legacy recordings cannot be told apart
+fn recording_id(header: Option<&FileHeader>) -> i64 {
+    header.map(|header| header.start_time as i64).unwrap_or(0)
+}
+
 pub struct FileReader<T> {
     path: String,
     reader: BufReader<File>,
@@ -26,21 +67,111 @@ pub struct FileReader<T> {
     tx: Sender<DBOperation>,
     token: CancellationToken,
     progress: ProgressBar,
+    // Orientation of the recorded tuples, depends on the probe that wrote the file
+    direction: Direction,
+    // None if the file was written before the versioned format
+    header: Option<FileHeader>,
+    // Number of skipped corrupt entries
+    skipped: u64,
+    // Wait for new entries at the end of file until the recorder closes it
+    follow: bool,
+    // Bytes of a single entry including framing
+    stride: u64,
+    // Bytes read from the file so far
+    position: u64,
+    // End of the last entry passed to the db writer, imports continue from here
+    imported: u64,
     _marker: PhantomData<T>,
 }
 
-impl<'a,T: EventIndexer + Debug + FromBuffer + Deserialize<'a> + Clone> FileReader<T> {
+impl<'a, T: EventIndexer + Debug + FromBuffer + RecordFormat + Deserialize<'a> + Clone>
+    FileReader<T>
+{
     pub async fn new(
         path: &str,
         tx: Sender<DBOperation>,
         token: CancellationToken,
-        progress: ProgressBar
+        progress: ProgressBar,
+        direction: Direction,
+        follow: bool,
+        resume: Option<ImportState>,
     ) -> Result<FileReader<T>, Box<dyn Error>> {
         let infile = OpenOptions::new().read(true).open(path).await?;
 
-        let to_read = infile.metadata().await?.size();
+        // The recorder may not have written the header yet
+        if follow {
+            while infile.metadata().await?.size() < FIXED_HEADER_SIZE as u64 {
+                if token.is_cancelled() {
+                    break;
+                }
+                tokio::time::sleep(FOLLOW_POLL_INTERVAL).await;
+            }
+        }
+
+        let size = infile.metadata().await?.size();
+        let mut to_read = size;
+
+        let mut reader = BufReader::new(infile);
+
+        // Check for file header, files without one are read as legacy recordings
+        // and converted to the current layout
+        let mut fixed = vec![0 as u8; FIXED_HEADER_SIZE];
+        let header = if reader.read_exact(&mut fixed).await.is_ok() && fixed[..8] == FILE_MAGIC {
+            let mut rest = vec![0 as u8; FileHeader::remaining_size(&fixed)];
+            reader.read_exact(&mut rest).await?;
 
-        let reader = BufReader::new(infile);
+            let header = FileHeader::parse(&fixed, &rest)?;
+            header.validate::<T>(T::ENTRY_SIZE)?;
+
+            info!(
+                "Reading {} in format version {}, recorded on {}",
+                path, header.version, header.host
+            );
+            debug!(
+                "{}: boot offset {} ns, recording started at {} ns",
+                path, header.boot_offset, header.start_time
+            );
+
+            to_read -= (fixed.len() + rest.len()) as u64;
+            Some(header)
+        } else if size > 0 && T::LEGACY_SIZE.is_none() {
+            return Err(FormatError::MissingHeader.into());
+        } else {
+            if size > 0 {
+                warn!(
+                    "{} has no file header, reading as legacy recording without validation!",
+                    path
+                );
+            }
+            reader.seek(SeekFrom::Start(0)).await?;
+            None
+        };
+
+        let stride = match T::LEGACY_SIZE {
+            Some(legacy_size) if header.is_none() => legacy_size + LEGACY_MARKER.len(),
+            _ => T::ENTRY_SIZE + RECORD_FRAME_SIZE,
+        } as u64;
+
+        // Entries start after the header
+        let mut position = size - to_read;
+
+        // Skip entries imported by a previous run of the same recording
+        if let Some(state) = resume {
+            let file_id = recording_id(header.as_ref());
+            let offset = state.offset as u64;
+
+            if state.file_id != file_id {
+                warn!(
+                    "{} belongs to another recording than the last import, importing all entries!",
+                    path
+                );
+            } else if offset > position && offset <= position + to_read {
+                info!("Continuing import of {} at byte {}", path, offset);
+                reader.seek(SeekFrom::Start(offset)).await?;
+                to_read -= offset - position;
+                position = offset;
+            }
+        }
 
         Ok(FileReader {
             path: path.to_string(),
@@ -49,65 +180,382 @@ impl<'a,T: EventIndexer + Debug + FromBuffer + Deserialize<'a> + Clone> FileRead
             tx,
             token,
             progress,
+            direction,
+            header,
+            skipped: 0,
+            follow,
+            stride,
+            position,
+            imported: position,
             _marker: PhantomData,
         })
     }
 
-    // TODO: track file percentage!
-    pub async fn run(&mut self) {
-        // Get bytes for read struct
-        // TODO: I DONT KNOW WHY THIS 4 BYTE MISALIGNMENT HAPPENS, IT JUST DOES
-        // FIX THIS!
-        let entry_size = T::ENTRY_SIZE;
-        //let entry_size = size_of::<T>();
-        //let entry_size = 68;
+    // Fills buffer, returns false if the end of file is reached
+    // When following, waits for the recorder to write more data until the token is cancelled
+    async fn fill(&mut self, buffer: &mut [u8]) -> bool {
+        if !self.follow {
+            if self.reader.read_exact(buffer).await.is_err() {
+                return false;
+            }
+            self.position += buffer.len() as u64;
+            return true;
+        }
 
-        debug!("Entry size: {} bytes for {}", entry_size, self.path);
+        let mut filled = 0;
+        while filled < buffer.len() {
+            match self.reader.read(&mut buffer[filled..]).await {
+                Ok(0) => {
+                    if self.token.is_cancelled() {
+                        return false;
+                    }
+                    self.update_length().await;
+                    tokio::time::sleep(FOLLOW_POLL_INTERVAL).await;
+                }
+                Ok(read) => {
+                    filled += read;
+                    self.position += read as u64;
+                }
+                Err(_) => return false,
+            }
+        }
+        true
+    }
 
-        // Buffer for file reads
+    // The file is still growing, update the expected number of entries
+    async fn update_length(&mut self) {
+        if let Ok(metadata) = self.reader.get_ref().metadata().await {
+            self.progress.set_length(metadata.size() / self.stride);
+        }
+    }
+
+    // Reads until the last bytes read match marker, returns false if the end of file is reached
+    async fn resync(&mut self, marker: &[u8]) -> bool {
+        let mut window: Vec<u8> = Vec::with_capacity(marker.len());
+        let mut byte = [0 as u8; 1];
+
+        while self.fill(&mut byte).await {
+            if window.len() == marker.len() {
+                window.remove(0);
+            }
+            window.push(byte[0]);
+
+            if window == marker {
+                return true;
+            }
+        }
+        false
+    }
+
+    // Parses an entry and passes it to the db writer, returns false if the channel is closed
+    async fn forward(&mut self, buffer: &Vec<u8>) -> bool {
+        let event = T::from_buffer(buffer);
+
+        if self.tx.send(event.as_db_op(self.direction)).await.is_err() {
+            info!("Stopping file read {} on channel close!", self.path);
+            return false;
+        }
+
+        self.progress.inc(1);
+        self.imported = self.position;
+
+        // Allow other threads to run
+        task::yield_now().await;
+        true
+    }
+
+    // Reads marker, length and checksum framed records
+    async fn run_framed(&mut self) {
+        let entry_size = T::ENTRY_SIZE;
+
+        let mut frame = vec![0 as u8; RECORD_FRAME_SIZE];
         let mut buffer = vec![0 as u8; entry_size];
 
-        // Progress bar based on total number of entries
-        let num_entries = self.to_read / entry_size as u64;
-        // Update length of progress bar with expected number of entries
-        self.progress.set_length(num_entries);
-
-        // Read until error is returned
-        while let Ok(read) = self.reader.read_exact(&mut buffer).await {
-            // Check if end of file is reached
-            if read < 1 {
-                info!("Reached end of file for {}. Stopping!", self.path);
-                self.progress.finish();
+        loop {
+            if self.token.is_cancelled() {
                 return;
             }
 
+            if !self.fill(&mut frame[..4]).await {
+                return;
+            }
 
-            // Bytes were read, try to parse to struct
-            //let event: T = unsafe { std::ptr::read(buffer.as_ptr() as *const _) };
-            let event = T::from_buffer(&buffer);
-            // TODO: error handling
-            //let event: T = bincode::deserialize::<'b,T>(&buf).unwrap();
+            // Recorder closed the file, no more records follow
+            if frame[..4] == END_MARKER {
+                info!("Recording of {} is complete", self.path);
+                return;
+            }
 
-            let db_op = event.as_db_op();
+            if frame[..4] != RECORD_MARKER {
+                warn!("Lost record boundary in {}, resyncing!", self.path);
+                self.skipped += 1;
+                if !self.resync(&RECORD_MARKER).await {
+                    return;
+                }
+                frame[..4].copy_from_slice(&RECORD_MARKER);
+            }
+
+            if !self.fill(&mut frame[4..]).await {
+                return;
+            }
 
-            let res = self.tx.send(db_op).await;
+            let (length, checksum) = parse_record_frame(&frame);
 
-            // If an error is returned, then channel is closed
-            if res.is_err() {
-                info!("Stopping file read {} on channel close!", self.path);
-                self.progress.finish();
+            // A wrong length means the frame itself is corrupt, search for the next one
+            if length as usize != entry_size {
+                warn!(
+                    "Record of {} bytes in {}, expected {}. Resyncing!",
+                    length, self.path, entry_size
+                );
+                self.skipped += 1;
+                continue;
+            }
+
+            if !self.fill(&mut buffer).await {
+                warn!("Last record in {} is truncated!", self.path);
                 return;
             }
 
-            self.progress.inc(1);
+            if record_checksum(&buffer) != checksum {
+                warn!("Skipping record with invalid checksum in {}!", self.path);
+                self.skipped += 1;
+                continue;
+            }
 
-            // Allow other threads to run
-            task::yield_now().await;
+            if !self.forward(&buffer).await {
+                return;
+            }
+        }
+    }
+
+    // Reads entries of the old layout followed by LEGACY_MARKER
+    async fn run_legacy(&mut self) {
+        let Some(entry_size) = T::LEGACY_SIZE else {
+            return;
+        };
+
+        let mut buffer = vec![0 as u8; entry_size + LEGACY_MARKER.len()];
+
+        loop {
+            if self.token.is_cancelled() {
+                return;
+            }
+
+            if !self.fill(&mut buffer).await {
+                return;
+            }
+
+            // Entries that do not end with the marker are misaligned, skip to the next marker
+            if buffer[entry_size..] != LEGACY_MARKER {
+                warn!("Misaligned entry in {}, resyncing!", self.path);
+                self.skipped += 1;
+                if !self.resync(&LEGACY_MARKER).await {
+                    return;
+                }
+                continue;
+            }
+
+            let entry = T::from_legacy(&buffer[..entry_size]);
+            if !self.forward(&entry).await {
+                return;
+            }
+        }
+    }
+
+    // Import progress to store in the database, the next import of this file starts after it
+    pub fn import_state(&self) -> ImportState {
+        ImportState {
+            path: import_key(&self.path),
+            file_id: recording_id(self.header.as_ref()),
+            offset: self.imported as i64,
+        }
+    }
+
+    pub async fn run(&mut self) -> ImportState {
+        debug!("Entry size: {} bytes for {}", T::ENTRY_SIZE, self.path);
+
+        // Update length of progress bar with expected number of entries
+        self.progress.set_length(self.to_read / self.stride);
+
+        if self.header.is_some() {
+            self.run_framed().await;
+        } else {
+            self.run_legacy().await;
+        }
+
+        if self.skipped > 0 {
+            warn!(
+                "Skipped {} corrupt entries while reading {}!",
+                self.skipped, self.path
+            );
         }
 
-        // Error was thrown, EOF reached!
         info!("Reached end of file for {}. Stopping!", self.path);
         self.progress.finish();
-        return;
+
+        self.import_state()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::bindings::cwnd::cwnd_trace_entry;
+    use crate::bindings::tcp_bad_csum::{tcp_bad_csum_entry, tests::payload};
+    use crate::format::tests::{header, record};
+    use crate::test_util::TempPath;
+    use tokio::sync::mpsc;
+
+    fn write_recording(name: &str, content: &[u8]) -> TempPath {
+        TempPath::with_content(&format!("reader-{}.tcp", name), content)
+    }
+
+    // Reads the file and returns the timestamps of all forwarded entries
+    async fn read_all(file: TempPath) -> (Vec<f64>, u64) {
+        let (tx, mut rx) = mpsc::channel(100);
+        let mut reader = FileReader::<tcp_bad_csum_entry>::new(
+            &file.as_string(),
+            tx,
+            CancellationToken::new(),
+            ProgressBar::hidden(),
+            Direction::Ingress,
+            false,
+            None,
+        )
+        .await
+        .expect("Failed to open recording!");
+        reader.run().await;
+        let skipped = reader.skipped;
+        drop(reader);
+
+        let mut times = Vec::new();
+        while let Some(op) = rx.recv().await {
+            match op {
+                DBOperation::BadCsum(entry, _) => times.push(entry.get_timestamp()),
+                _ => panic!("Unexpected operation!"),
+            }
+        }
+        (times, skipped)
+    }
+
+    #[tokio::test]
+    async fn reads_framed_records() {
+        let mut content = header::<tcp_bad_csum_entry>(0);
+        content.extend(record(&payload(1)));
+        content.extend(record(&payload(2)));
+        content.extend_from_slice(&END_MARKER);
+        // Nothing after the end marker is read
+        content.extend(record(&payload(3)));
+
+        let (times, skipped) = read_all(write_recording("framed", &content)).await;
+        assert_eq!(times, vec![1.0, 2.0]);
+        assert_eq!(skipped, 0);
+    }
+
+    #[tokio::test]
+    async fn resyncs_after_corruption() {
+        let mut content = header::<tcp_bad_csum_entry>(0);
+        content.extend(record(&payload(1)));
+        // Garbage between records
+        content.extend_from_slice(&[0xAB; 7]);
+        content.extend(record(&payload(2)));
+        // Payload does not match checksum
+        let mut corrupt = record(&payload(3));
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 1;
+        content.extend(corrupt);
+        // Wrong length in frame
+        let mut short = record(&payload(4)[..16]);
+        short.extend_from_slice(&[0; 48]);
+        content.extend(short);
+        content.extend(record(&payload(5)));
+        // Truncated last record
+        content.extend_from_slice(&record(&payload(6))[..30]);
+
+        let (times, skipped) = read_all(write_recording("resync", &content)).await;
+        assert_eq!(times, vec![1.0, 2.0, 5.0]);
+        assert!(skipped >= 3);
+    }
+
+    #[tokio::test]
+    async fn rejects_files_without_header() {
+        let file = write_recording("legacy", &payload(1));
+        let (tx, _rx) = mpsc::channel(1);
+        let result = FileReader::<tcp_bad_csum_entry>::new(
+            &file.as_string(),
+            tx,
+            CancellationToken::new(),
+            ProgressBar::hidden(),
+            Direction::Ingress,
+            false,
+            None,
+        )
+        .await;
+
+        let error = result.err().expect("Recording without header was read!");
+        assert!(matches!(
+            error.downcast_ref::<FormatError>(),
+            Some(FormatError::MissingHeader)
+        ));
+    }
+
+    // cwnd entry as written before the file header, without sock_cookie
+    pub(crate) fn legacy_cwnd(time: u64, snd_cwnd: u32) -> Vec<u8> {
+        let mut entry = time.to_le_bytes().to_vec();
+        entry.extend_from_slice(&0x0100000A_0200000A_u64.to_le_bytes());
+        entry.extend_from_slice(&[0; 32]);
+        entry.extend_from_slice(&0x1F90_C350_u32.to_le_bytes());
+        entry.extend_from_slice(&2_u16.to_le_bytes());
+        entry.extend_from_slice(&snd_cwnd.to_le_bytes());
+        entry.extend_from_slice(&LEGACY_MARKER);
+        entry
+    }
+
+    #[tokio::test]
+    async fn reads_legacy_recordings() {
+        let mut content = legacy_cwnd(1, 10);
+        // Misaligned entries are skipped up to the next marker, which ends the entry after them
+        content.extend_from_slice(&[0xAB; 5]);
+        content.extend_from_slice(&LEGACY_MARKER);
+        content.extend(legacy_cwnd(2, 20));
+        content.extend(legacy_cwnd(3, 30));
+        let file = write_recording("legacy-cwnd", &content);
+
+        let (tx, mut rx) = mpsc::channel(100);
+        let mut reader = FileReader::<cwnd_trace_entry>::new(
+            &file.as_string(),
+            tx,
+            CancellationToken::new(),
+            ProgressBar::hidden(),
+            Direction::Egress,
+            false,
+            None,
+        )
+        .await
+        .expect("Failed to open legacy recording!");
+        let state = reader.run().await;
+        assert_eq!(state.file_id, 0);
+        assert_eq!(state.offset, content.len() as i64);
+        drop(reader);
+
+        let mut entries = Vec::new();
+        while let Some(op) = rx.recv().await {
+            match op {
+                DBOperation::Cwnd(entry, _) => entries.push(entry),
+                _ => panic!("Unexpected operation!"),
+            }
+        }
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].time, entries[0].snd_cwnd), (1, 10));
+        assert_eq!((entries[1].time, entries[1].snd_cwnd), (3, 30));
+        assert_eq!(entries[1].family, 2);
+        assert_eq!(entries[1].sock_cookie, 0);
+    }
+
+    #[tokio::test]
+    async fn empty_files_have_no_entries() {
+        let (times, skipped) = read_all(write_recording("empty", &[])).await;
+        assert!(times.is_empty());
+        assert_eq!(skipped, 0);
     }
 }
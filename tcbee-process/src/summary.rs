@@ -0,0 +1,358 @@
+// Summary of a flow, stored as flow attributes at the end of an import
+// Allows to sort and filter flows without reading their time series.
+// Timestamps and durations are in nanoseconds of the selected time mode.
+use std::collections::HashMap;
+
+use log::error;
+use ts_storage::{DataValue, Flow, FlowAttribute, TSDBInterface};
+
+use crate::flow_tracker::{EventIndexer, EventType};
+
+#[derive(Debug, Default)]
+pub struct FlowSummary {
+    first: Option<f64>,
+    last: Option<f64>,
+    // Packets per series name suffix, i.e. direction
+    packets: HashMap<&'static str, i64>,
+    // Sum of PAYLOAD_LEN of packets per series name suffix
+    payload_bytes: HashMap<&'static str, i64>,
+    syn: bool,
+    fin: bool,
+    rst: bool,
+    // Sum of DATA_LEN of probe events
+    data_bytes: i64,
+    // Socket counters, the last value is the total
+    bytes_acked: Option<i64>,
+    bytes_received: Option<i64>,
+    total_retrans: Option<i64>,
+    synack_retransmissions: i64,
+    max_cwnd: Option<i64>,
+    min_srtt: Option<i64>,
+    srtt_sum: i64,
+    srtt_samples: i64,
+}
+
+fn max_of(current: Option<i64>, value: i64) -> Option<i64> {
+    Some(current.map_or(value, |current| current.max(value)))
+}
+
+impl FlowSummary {
+    pub fn new() -> FlowSummary {
+        FlowSummary::default()
+    }
+
+    /// Continues the summary stored by a previous import of the flow
+    pub fn restore(db: &Box<dyn TSDBInterface + Send>, flow: &Flow) -> FlowSummary {
+        let mut summary = FlowSummary::new();
+
+        let attributes: HashMap<String, DataValue> = match db.list_flow_attributes(flow) {
+            Ok(attributes) => attributes.map(|a| (a.name, a.value)).collect(),
+            Err(err) => {
+                error!(
+                    "Failed to read attributes of flow {:?} - {}. Continuing...",
+                    flow.tuple, err
+                );
+                return summary;
+            }
+        };
+        let int = |name: &str| attributes.get(name).and_then(|value| value.as_int());
+        let float = |name: &str| attributes.get(name).and_then(|value| value.as_float());
+        let flag = |name: &str| matches!(attributes.get(name), Some(DataValue::Boolean(true)));
+
+        summary.first = float("FIRST_TIMESTAMP");
+        summary.last = float("LAST_TIMESTAMP");
+        for suffix in ["", "_IN", "_OUT"] {
+            if let Some(packets) = int(&format!("PACKETS{}", suffix)) {
+                summary.packets.insert(suffix, packets);
+            }
+            if let Some(bytes) = int(&format!("PAYLOAD_BYTES{}", suffix)) {
+                summary.payload_bytes.insert(suffix, bytes);
+            }
+        }
+        summary.syn = flag("SYN_SEEN");
+        summary.fin = flag("FIN_SEEN");
+        summary.rst = flag("RST_SEEN");
+        summary.data_bytes = int("DATA_BYTES").unwrap_or(0);
+        summary.bytes_acked = int("BYTES_ACKED");
+        summary.bytes_received = int("BYTES_RECEIVED");
+        // Derived values like AVG_SRTT are recalculated from the raw counters
+        summary.synack_retransmissions = int("SYNACK_RETRANSMISSIONS").unwrap_or(0);
+        summary.total_retrans = int("TOTAL_RETRANS");
+        summary.max_cwnd = int("MAX_CWND");
+        summary.min_srtt = int("MIN_SRTT");
+        summary.srtt_samples = int("SRTT_SAMPLES").unwrap_or(0);
+        summary.srtt_sum = int("SRTT_SUM").unwrap_or(0);
+
+        summary
+    }
+
+    /// Adds an event, time is the converted timestamp of the event
+    pub fn add_event<T: EventIndexer>(
+        &mut self,
+        etype: EventType,
+        suffix: &'static str,
+        event: &T,
+        time: f64,
+    ) {
+        self.first = Some(self.first.map_or(time, |first| first.min(time)));
+        self.last = Some(self.last.map_or(time, |last| last.max(time)));
+
+        match etype {
+            EventType::Packet => *self.packets.entry(suffix).or_insert(0) += 1,
+            EventType::RetransmitSynack => self.synack_retransmissions += 1,
+            _ => {}
+        }
+
+        for i in 0..=event.get_max_index() {
+            let Some(value) = event.get_field(i) else {
+                continue;
+            };
+            let int = value.as_int().unwrap_or(0);
+
+            match event.get_field_name(i) {
+                "FLAG_SYN" => self.syn |= matches!(value, DataValue::Boolean(true)),
+                "FLAG_FIN" => self.fin |= matches!(value, DataValue::Boolean(true)),
+                "FLAG_RST" => self.rst |= matches!(value, DataValue::Boolean(true)),
+                "DATA_LEN" => self.data_bytes += int,
+                "PAYLOAD_LEN" => *self.payload_bytes.entry(suffix).or_insert(0) += int,
+                "bytes_acked" => self.bytes_acked = max_of(self.bytes_acked, int),
+                "bytes_received" => self.bytes_received = max_of(self.bytes_received, int),
+                "total_retrans" => self.total_retrans = max_of(self.total_retrans, int),
+                "SND_CWND" | "snd_cwnd" | "perf_snd_cwnd" => {
+                    self.max_cwnd = max_of(self.max_cwnd, int)
+                }
+                "SRTT" => {
+                    self.min_srtt = Some(self.min_srtt.map_or(int, |min| min.min(int)));
+                    self.srtt_sum += int;
+                    self.srtt_samples += 1;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Adds offset to the first and last timestamp, used when the time base of the flow moves
+    pub fn shift(&mut self, offset: f64) {
+        self.first = self.first.map(|first| first + offset);
+        self.last = self.last.map(|last| last + offset);
+    }
+
+    pub fn attributes(&self) -> Vec<FlowAttribute> {
+        let mut values: Vec<(String, DataValue)> = Vec::new();
+
+        if let (Some(first), Some(last)) = (self.first, self.last) {
+            values.push(("FIRST_TIMESTAMP".to_string(), DataValue::Float(first)));
+            values.push(("LAST_TIMESTAMP".to_string(), DataValue::Float(last)));
+            values.push(("DURATION".to_string(), DataValue::Float(last - first)));
+        }
+        for (suffix, packets) in self.packets.iter() {
+            values.push((format!("PACKETS{}", suffix), DataValue::Int(*packets)));
+        }
+        for (suffix, bytes) in self.payload_bytes.iter() {
+            values.push((format!("PAYLOAD_BYTES{}", suffix), DataValue::Int(*bytes)));
+        }
+        if !self.packets.is_empty() {
+            values.push(("SYN_SEEN".to_string(), DataValue::Boolean(self.syn)));
+            values.push(("FIN_SEEN".to_string(), DataValue::Boolean(self.fin)));
+            values.push(("RST_SEEN".to_string(), DataValue::Boolean(self.rst)));
+        }
+        if self.data_bytes > 0 {
+            values.push(("DATA_BYTES".to_string(), DataValue::Int(self.data_bytes)));
+        }
+        if let Some(bytes) = self.bytes_acked {
+            values.push(("BYTES_ACKED".to_string(), DataValue::Int(bytes)));
+        }
+        if let Some(bytes) = self.bytes_received {
+            values.push(("BYTES_RECEIVED".to_string(), DataValue::Int(bytes)));
+        }
+        if let Some(total_retrans) = self.total_retrans {
+            values.push(("TOTAL_RETRANS".to_string(), DataValue::Int(total_retrans)));
+        }
+        if self.total_retrans.is_some() || self.synack_retransmissions > 0 {
+            let total = self.total_retrans.unwrap_or(0) + self.synack_retransmissions;
+            values.push(("RETRANSMISSIONS".to_string(), DataValue::Int(total)));
+            values.push((
+                "SYNACK_RETRANSMISSIONS".to_string(),
+                DataValue::Int(self.synack_retransmissions),
+            ));
+        }
+        if let Some(cwnd) = self.max_cwnd {
+            values.push(("MAX_CWND".to_string(), DataValue::Int(cwnd)));
+        }
+        if let Some(srtt) = self.min_srtt {
+            values.push(("MIN_SRTT".to_string(), DataValue::Int(srtt)));
+            values.push((
+                "AVG_SRTT".to_string(),
+                DataValue::Float(self.srtt_sum as f64 / self.srtt_samples as f64),
+            ));
+            values.push((
+                "SRTT_SAMPLES".to_string(),
+                DataValue::Int(self.srtt_samples),
+            ));
+            values.push(("SRTT_SUM".to_string(), DataValue::Int(self.srtt_sum)));
+        }
+
+        values
+            .into_iter()
+            .map(|(name, value)| FlowAttribute { name, value })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, net::IpAddr};
+
+    use ts_storage::IpTuple;
+
+    use super::*;
+    use crate::{
+        bindings::tcp_packet::TcpPacket, db_writer::DBOperation, flow_tracker::Direction,
+        test_util::temp_db,
+    };
+
+    // Event at the given time with the given named fields
+    struct Fields(u64, Vec<(&'static str, DataValue)>);
+
+    fn tuple() -> IpTuple {
+        IpTuple {
+            src: "10.0.0.1".parse().unwrap(),
+            dst: "10.0.0.2".parse().unwrap(),
+            sport: 1000,
+            dport: 80,
+            l4proto: 6,
+        }
+    }
+
+    impl Fields {
+        fn value(&self, name: &str) -> Option<&DataValue> {
+            self.1.iter().find(|(field, _)| *field == name).map(|(_, value)| value)
+        }
+    }
+
+    impl EventIndexer for Fields {
+        fn get_field(&self, index: usize) -> Option<DataValue> {
+            Some(self.1[index].1.clone())
+        }
+        fn get_default_field(&self, index: usize) -> DataValue {
+            self.1[index].1.clone()
+        }
+        fn get_field_name(&self, index: usize) -> &str {
+            self.1[index].0
+        }
+        fn get_ip_tuple(&self) -> IpTuple {
+            tuple()
+        }
+        fn get_max_index(&self) -> usize {
+            self.1.len() - 1
+        }
+        fn get_timestamp(&self) -> f64 {
+            self.0 as f64
+        }
+        // Written as a packet of the flow with the SYN flag and payload length of the fields
+        fn as_db_op(self, direction: Direction) -> DBOperation {
+            let tuple = self.get_ip_tuple();
+            let (IpAddr::V4(src), IpAddr::V4(dst)) = (tuple.src, tuple.dst) else {
+                unreachable!()
+            };
+            let packet = TcpPacket {
+                time: self.0,
+                saddr: src.into(),
+                daddr: dst.into(),
+                sport: tuple.sport as u16,
+                dport: tuple.dport as u16,
+                flag_syn: matches!(self.value("FLAG_SYN"), Some(DataValue::Boolean(true))),
+                payload_len: self.value("PAYLOAD_LEN").and_then(|v| v.as_int()).unwrap_or(0) as u16,
+                ..Default::default()
+            };
+            DBOperation::Packet(packet, direction)
+        }
+    }
+
+    fn packet(time: u64, syn: bool, payload: i64) -> Fields {
+        Fields(
+            time,
+            vec![
+                ("FLAG_SYN", DataValue::Boolean(syn)),
+                ("PAYLOAD_LEN", DataValue::Int(payload)),
+            ],
+        )
+    }
+
+    fn socket(time: u64, srtt: i64, total_retrans: i64) -> Fields {
+        Fields(
+            time,
+            vec![
+                ("SRTT", DataValue::Int(srtt)),
+                ("total_retrans", DataValue::Int(total_retrans)),
+            ],
+        )
+    }
+
+    fn add(summary: &mut FlowSummary, etype: EventType, suffix: &'static str, event: Fields) {
+        summary.add_event(etype, suffix, &event, event.get_timestamp());
+    }
+
+    fn values(attributes: Vec<FlowAttribute>) -> BTreeMap<String, String> {
+        attributes
+            .into_iter()
+            .map(|a| (a.name, format!("{:?}", a.value)))
+            .collect()
+    }
+
+    #[test]
+    fn counters() {
+        let mut summary = FlowSummary::new();
+        add(&mut summary, EventType::Packet, "_OUT", packet(100, true, 0));
+        add(&mut summary, EventType::Packet, "_OUT", packet(300, false, 1000));
+        add(&mut summary, EventType::Packet, "_IN", packet(200, false, 10));
+        add(&mut summary, EventType::Socket, "", socket(250, 30, 1));
+        add(&mut summary, EventType::Socket, "", socket(260, 10, 2));
+        add(&mut summary, EventType::RetransmitSynack, "", packet(50, false, 0));
+
+        let values = values(summary.attributes());
+        assert_eq!(values["FIRST_TIMESTAMP"], "Float(50.0)");
+        assert_eq!(values["DURATION"], "Float(250.0)");
+        assert_eq!(values["PACKETS_OUT"], "Int(2)");
+        assert_eq!(values["PACKETS_IN"], "Int(1)");
+        assert_eq!(values["PAYLOAD_BYTES_OUT"], "Int(1000)");
+        assert_eq!(values["SYN_SEEN"], "Boolean(true)");
+        assert_eq!(values["FIN_SEEN"], "Boolean(false)");
+        assert_eq!(values["TOTAL_RETRANS"], "Int(2)");
+        assert_eq!(values["RETRANSMISSIONS"], "Int(3)");
+        assert_eq!(values["MIN_SRTT"], "Int(10)");
+        assert_eq!(values["AVG_SRTT"], "Float(20.0)");
+    }
+
+    #[test]
+    fn restore_continues_the_summary() {
+        let (_file, db) = temp_db("summary");
+        let db: Box<dyn TSDBInterface + Send> = Box::new(db);
+        let flow = db.create_flow(&tuple()).unwrap();
+
+        let mut summary = FlowSummary::new();
+        add(&mut summary, EventType::Packet, "", packet(100, true, 100));
+        add(&mut summary, EventType::Socket, "", socket(150, 30, 1));
+        add(&mut summary, EventType::RetransmitSynack, "", packet(120, false, 0));
+        for attribute in summary.attributes() {
+            db.add_flow_attribute(&flow, &attribute).unwrap();
+        }
+
+        let mut restored = FlowSummary::restore(&db, &flow);
+        assert_eq!(values(restored.attributes()), values(summary.attributes()));
+
+        // A second import adds to the stored counters
+        add(&mut restored, EventType::Packet, "", packet(400, false, 50));
+        add(&mut restored, EventType::Socket, "", socket(410, 20, 4));
+        let values = values(restored.attributes());
+        assert_eq!(values["FIRST_TIMESTAMP"], "Float(100.0)");
+        assert_eq!(values["LAST_TIMESTAMP"], "Float(410.0)");
+        assert_eq!(values["PACKETS"], "Int(2)");
+        assert_eq!(values["PAYLOAD_BYTES"], "Int(150)");
+        assert_eq!(values["SYN_SEEN"], "Boolean(true)");
+        assert_eq!(values["TOTAL_RETRANS"], "Int(4)");
+        assert_eq!(values["RETRANSMISSIONS"], "Int(5)");
+        assert_eq!(values["AVG_SRTT"], "Float(25.0)");
+        assert_eq!(values["SRTT_SAMPLES"], "Int(2)");
+    }
+}
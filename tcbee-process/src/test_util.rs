@@ -0,0 +1,60 @@
+// Fixtures shared by the unit tests
+use std::path::{Path, PathBuf};
+
+use ts_storage::sqlite::SQLiteTSDB;
+
+/// File or directory in the temp directory, removed when dropped, also if the test fails
+pub struct TempPath {
+    path: PathBuf,
+}
+
+impl TempPath {
+    /// name has to be unique per test, the process id separates concurrent test runs
+    pub fn new(name: &str) -> TempPath {
+        let path = std::env::temp_dir().join(format!("tcbee-{}-{}", std::process::id(), name));
+        let temp = TempPath { path };
+        temp.remove();
+        temp
+    }
+
+    pub fn with_content(name: &str, content: &[u8]) -> TempPath {
+        let temp = TempPath::new(name);
+        std::fs::write(&temp.path, content).unwrap();
+        temp
+    }
+
+    pub fn dir(name: &str) -> TempPath {
+        let temp = TempPath::new(name);
+        std::fs::create_dir_all(&temp.path).unwrap();
+        temp
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn as_string(&self) -> String {
+        self.path.to_string_lossy().to_string()
+    }
+
+    fn remove(&self) {
+        if self.path.is_dir() {
+            let _ = std::fs::remove_dir_all(&self.path);
+        } else {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+/// Empty SQLite database, declare the TempPath first so that it is removed after the database
+pub fn temp_db(name: &str) -> (TempPath, SQLiteTSDB) {
+    let file = TempPath::new(&format!("{}.sqlite", name));
+    let db = SQLiteTSDB::new(file.as_string()).unwrap();
+    (file, db)
+}
@@ -0,0 +1,163 @@
+// Conversion of recorded timestamps
+// All probes stamp events with bpf_ktime_get_ns, i.e. CLOCK_MONOTONIC nanoseconds since boot.
+// Converted timestamps stay in nanoseconds.
+use crate::format::FileHeader;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeMode {
+    // Nanoseconds since boot of the recording host, as recorded
+    Boot,
+    // Nanoseconds since UNIX epoch
+    Utc,
+    // Nanoseconds since start of the recording
+    Recording,
+    // Nanoseconds since the first event of each flow
+    Flow,
+}
+
+impl TimeMode {
+    pub const ALL: &'static [Self] = &[Self::Boot, Self::Utc, Self::Recording, Self::Flow];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            TimeMode::Boot => "boot",
+            TimeMode::Utc => "utc",
+            TimeMode::Recording => "recording",
+            TimeMode::Flow => "flow",
+        }
+    }
+
+    pub fn from_argument(argument: &str) -> Option<TimeMode> {
+        Self::ALL
+            .iter()
+            .find(|mode| mode.as_str() == argument)
+            .cloned()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TimeConverter {
+    mode: TimeMode,
+    // Nanoseconds from boot time to UNIX time
+    boot_offset: i64,
+    // Boot time of the earliest recording start
+    recording_start: u64,
+}
+
+impl TimeConverter {
+    /// Creates a converter from the headers of all files of a recording
+    pub fn new(mode: TimeMode, headers: &Vec<FileHeader>) -> Result<TimeConverter, String> {
+        let first = headers.first();
+
+        if first.is_none() && (mode == TimeMode::Utc || mode == TimeMode::Recording) {
+            return Err(format!(
+                "Time mode {} needs a recording with file headers, use boot or flow for legacy recordings!",
+                mode.as_str()
+            ));
+        }
+
+        // All files are written by the same recorder, offsets only differ by clock jitter
+        let boot_offset = first.map(|header| header.boot_offset).unwrap_or(0);
+        let recording_start = headers
+            .iter()
+            .map(|header| header.start_time)
+            .min()
+            .unwrap_or(0);
+
+        Ok(TimeConverter {
+            mode,
+            boot_offset,
+            recording_start,
+        })
+    }
+
+    /// Creates a converter for packet captures, their timestamps are already UNIX time
+    pub fn for_capture(mode: TimeMode, first_timestamp: u64) -> TimeConverter {
+        TimeConverter {
+            mode,
+            boot_offset: 0,
+            recording_start: first_timestamp,
+        }
+    }
+
+    pub fn mode(&self) -> TimeMode {
+        self.mode
+    }
+
+    /// Nanoseconds of a recorded timestamp since the start of the recording, used by filters
+    pub fn since_recording_start(&self, time: f64) -> f64 {
+        time - self.recording_start as f64
+    }
+
+    /// Converts a recorded timestamp, flow_start is the first recorded timestamp of the flow
+    pub fn convert(&self, time: f64, flow_start: f64) -> f64 {
+        match self.mode {
+            TimeMode::Boot => time,
+            TimeMode::Utc => time + self.boot_offset as f64,
+            TimeMode::Recording => time - self.recording_start as f64,
+            TimeMode::Flow => time - flow_start,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(boot_offset: i64, start_time: u64) -> FileHeader {
+        FileHeader {
+            version: 1,
+            record_type: 1,
+            record_size: 0,
+            boot_offset,
+            start_time,
+            layout: String::new(),
+            host: "host".to_string(),
+        }
+    }
+
+    #[test]
+    fn mode_arguments() {
+        for mode in TimeMode::ALL {
+            assert_eq!(TimeMode::from_argument(mode.as_str()), Some(*mode));
+        }
+        assert_eq!(TimeMode::from_argument("local"), None);
+    }
+
+    #[test]
+    fn conversions() {
+        // Files of one recording start at slightly different times
+        let headers = vec![header(1_000_000, 700), header(1_000_003, 500)];
+        let convert = |mode| {
+            TimeConverter::new(mode, &headers)
+                .unwrap()
+                .convert(2000.0, 1500.0)
+        };
+
+        assert_eq!(convert(TimeMode::Boot), 2000.0);
+        assert_eq!(convert(TimeMode::Utc), 1_002_000.0);
+        assert_eq!(convert(TimeMode::Recording), 1500.0);
+        assert_eq!(convert(TimeMode::Flow), 500.0);
+
+        let converter = TimeConverter::new(TimeMode::Boot, &headers).unwrap();
+        assert_eq!(converter.since_recording_start(2000.0), 1500.0);
+    }
+
+    #[test]
+    fn empty_recordings() {
+        assert!(TimeConverter::new(TimeMode::Utc, &vec![]).is_err());
+        assert!(TimeConverter::new(TimeMode::Recording, &vec![]).is_err());
+
+        let converter = TimeConverter::new(TimeMode::Boot, &vec![]).unwrap();
+        assert_eq!(converter.convert(2000.0, 0.0), 2000.0);
+    }
+
+    #[test]
+    fn captures() {
+        let converter = TimeConverter::for_capture(TimeMode::Utc, 100);
+        assert_eq!(converter.convert(2000.0, 0.0), 2000.0);
+
+        let converter = TimeConverter::for_capture(TimeMode::Recording, 100);
+        assert_eq!(converter.convert(2000.0, 0.0), 1900.0);
+    }
+}
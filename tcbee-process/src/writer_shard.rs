@@ -0,0 +1,286 @@
+// Database writer for a subset of flows
+// The DBWriter assigns every flow to one shard, so that trackers are never shared between threads.
+// Each shard has its own database connection and groups its writes into transactions.
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+use tokio::sync::mpsc::{error::TryRecvError, Receiver};
+use ts_storage::TSDBInterface;
+
+use crate::{
+    db_writer::{DBOperation, StreamKey},
+    filter::FlowFilter,
+    flow_tracker::{EventIndexer, EventType, FlowTracker},
+    timestamps::TimeConverter,
+};
+
+// Max number of points written in one transaction
+const TRANSACTION_ROWS: usize = 100000;
+// Transactions younger than this stay open while waiting for events
+const TRANSACTION_MIN_AGE: Duration = Duration::from_millis(100);
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(1);
+// Events kept per flow below the minimum number of packets, later events are dropped
+const MAX_WAITING_EVENTS: usize = 10000;
+// Events kept by all flows of a shard below the minimum number of packets
+const MAX_WAITING_TOTAL: usize = 1000000;
+
+#[derive(Debug)]
+pub enum ShardOperation {
+    // Event of a flow and suffix of its series names
+    Event(StreamKey, &'static str, DBOperation),
+    // Write all buffered events to the database
+    Flush,
+}
+
+// Flow below the minimum number of packets, its events are kept until it reaches it
+#[derive(Debug, Default)]
+struct WaitingFlow {
+    packets: usize,
+    events: Vec<(&'static str, DBOperation)>,
+    // Events that exceeded the limits, packets are still counted
+    dropped: usize,
+}
+
+pub struct WriterShard {
+    db: Box<dyn TSDBInterface + Send>,
+    streams: HashMap<StreamKey, FlowTracker>,
+    waiting: HashMap<StreamKey, WaitingFlow>,
+    // Events kept by all waiting flows
+    waiting_events: usize,
+    rx: Receiver<ShardOperation>,
+    time: TimeConverter,
+    filter: FlowFilter,
+    // Points written by all shards, shown as rows/s in the status bar
+    rows: Arc<AtomicU64>,
+    // Points written in the open transaction
+    pending: usize,
+    in_transaction: bool,
+    began: Instant,
+}
+
+impl WriterShard {
+    pub fn new(
+        db: Box<dyn TSDBInterface + Send>,
+        rx: Receiver<ShardOperation>,
+        time: TimeConverter,
+        filter: FlowFilter,
+        rows: Arc<AtomicU64>,
+    ) -> WriterShard {
+        WriterShard {
+            db,
+            streams: HashMap::new(),
+            waiting: HashMap::new(),
+            waiting_events: 0,
+            rx,
+            time,
+            filter,
+            rows,
+            pending: 0,
+            in_transaction: false,
+            began: Instant::now(),
+        }
+    }
+
+    fn begin(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.in_transaction {
+            return Ok(());
+        }
+
+        self.db
+            .begin_transaction()
+            .map_err(|err| format!("Failed to begin transaction: {}", err))?;
+        self.in_transaction = true;
+        self.began = Instant::now();
+        Ok(())
+    }
+
+    // Points of a failed transaction are lost, the shard has to stop
+    fn commit(&mut self) -> Result<(), Box<dyn Error>> {
+        if !self.in_transaction {
+            return Ok(());
+        }
+
+        self.in_transaction = false;
+        let pending = std::mem::take(&mut self.pending);
+        self.db.commit_transaction().map_err(|err| {
+            format!(
+                "Failed to commit transaction with {} points: {}",
+                pending, err
+            )
+        })?;
+        Ok(())
+    }
+
+    fn written(&mut self, rows: usize) {
+        self.pending += rows;
+        self.rows.fetch_add(rows as u64, Ordering::Relaxed);
+    }
+
+    fn track<T: EventIndexer + Debug>(
+        &mut self,
+        key: StreamKey,
+        etype: EventType,
+        suffix: &'static str,
+        event: &T,
+    ) -> Result<(), Box<dyn Error>> {
+        let db = &self.db;
+        let dropped = &self.filter.drop_fields;
+        let tracker = self
+            .streams
+            .entry(key)
+            .or_insert_with_key(|(tuple, cookie)| {
+                FlowTracker::new(db, tuple, *cookie, dropped.clone())
+            });
+
+        let rows = tracker
+            .add_event(&self.db, etype, suffix, event, &self.time)
+            .map_err(|err| {
+                format!(
+                    "Failed to trace {}: {:?}. Error: {}",
+                    etype.as_str(),
+                    event,
+                    err
+                )
+            })?;
+        self.written(rows);
+        Ok(())
+    }
+
+    fn track_operation(
+        &mut self,
+        key: StreamKey,
+        suffix: &'static str,
+        event: DBOperation,
+    ) -> Result<(), Box<dyn Error>> {
+        match event {
+            DBOperation::Packet(data, _) => self.track(key, EventType::Packet, suffix, &data),
+            DBOperation::Probe(data, _) => self.track(key, EventType::TcpProbe, suffix, &data),
+            DBOperation::Socket(data, _) => self.track(key, EventType::Socket, suffix, &data),
+            DBOperation::Cwnd(data, _) => self.track(key, EventType::Cwnd, suffix, &data),
+            DBOperation::RetransmitSynack(data, _) => {
+                self.track(key, EventType::RetransmitSynack, suffix, &data)
+            }
+            DBOperation::BadCsum(data, _) => self.track(key, EventType::BadCsum, suffix, &data),
+            // Handled by the DBWriter
+            DBOperation::Flush => Ok(()),
+        }
+    }
+
+    // Keeps the events of new flows until they have enough packets, returns the events to track
+    fn wait_for_packets(
+        &mut self,
+        key: &StreamKey,
+        suffix: &'static str,
+        event: DBOperation,
+    ) -> Vec<(&'static str, DBOperation)> {
+        if self.filter.min_packets == 0 || self.streams.contains_key(key) {
+            return vec![(suffix, event)];
+        }
+
+        let waiting = self.waiting.entry(key.clone()).or_default();
+        if matches!(event, DBOperation::Packet(..)) {
+            waiting.packets += 1;
+        }
+        // Scans and short flows never reach the minimum, their events must not fill the memory
+        if waiting.events.len() < MAX_WAITING_EVENTS && self.waiting_events < MAX_WAITING_TOTAL {
+            waiting.events.push((suffix, event));
+            self.waiting_events += 1;
+        } else {
+            waiting.dropped += 1;
+        }
+
+        if waiting.packets < self.filter.min_packets {
+            return Vec::new();
+        }
+        let Some(waiting) = self.waiting.remove(key) else {
+            return Vec::new();
+        };
+        self.waiting_events -= waiting.events.len();
+        if waiting.dropped > 0 {
+            warn!(
+                "Dropped {} events of flow {:?} while waiting for {} packets",
+                waiting.dropped, key.0, self.filter.min_packets
+            );
+        }
+        waiting.events
+    }
+
+    fn handle(&mut self, operation: ShardOperation) -> Result<(), Box<dyn Error>> {
+        match operation {
+            ShardOperation::Event(key, suffix, event) => {
+                let events = self.wait_for_packets(&key, suffix, event);
+                if events.is_empty() {
+                    return Ok(());
+                }
+
+                self.begin()?;
+                for (suffix, event) in events {
+                    self.track_operation(key.clone(), suffix, event)?;
+                }
+
+                if self.pending >= TRANSACTION_ROWS {
+                    self.commit()?;
+                }
+            }
+            ShardOperation::Flush => {
+                self.begin()?;
+                let mut rows = 0;
+                for (_key, tracker) in self.streams.iter_mut() {
+                    rows += tracker.write_buffered(&self.db)?;
+                }
+                self.written(rows);
+                self.commit()?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn run(&mut self) -> Result<(), Box<dyn Error>> {
+        loop {
+            let operation = match self.rx.try_recv() {
+                Ok(operation) => operation,
+                Err(TryRecvError::Empty) => {
+                    // Bridge short gaps, but do not hold locks while waiting longer
+                    // Other shards may need them
+                    if self.in_transaction && self.began.elapsed() < TRANSACTION_MIN_AGE {
+                        thread::sleep(IDLE_POLL_INTERVAL);
+                        continue;
+                    }
+                    self.commit()?;
+                    match self.rx.blocking_recv() {
+                        Some(operation) => operation,
+                        None => break,
+                    }
+                }
+                Err(TryRecvError::Disconnected) => break,
+            };
+
+            self.handle(operation)?;
+        }
+
+        // This is reached when the DBWriter is done, flush files!
+        if !self.waiting.is_empty() {
+            info!(
+                "Skipped {} flows with less than {} packets",
+                self.waiting.len(),
+                self.filter.min_packets
+            );
+        }
+
+        self.begin()?;
+        for (_key, tracker) in self.streams.iter_mut() {
+            tracker.flush(&self.db, &self.time)?;
+        }
+        self.commit()
+    }
+}
@@ -8,8 +8,9 @@ use super::{__u64, __u8, trace_entry };
 #[derive(Debug)]
 pub struct trace_event_raw_tcp_bad_csum {
     pub ent: trace_entry,
-    pub saddr: [__u8; 4usize],
-    pub daddr: [__u8; 4usize],
+    pub skbaddr: *const ::aya_ebpf::cty::c_void,
+    pub saddr: [__u8; 28usize],
+    pub daddr: [__u8; 28usize],
 }
 
 #[repr(C)]
@@ -17,6 +18,7 @@ pub struct trace_event_raw_tcp_bad_csum {
 #[cfg_attr(feature = "user", derive(Serialize, Deserialize))]
 pub struct tcp_bad_csum_entry {
     pub time: __u64,
-    pub saddr: [__u8; 4usize],
-    pub daddr: [__u8; 4usize],
+    // sockaddr_in6 of the segment, contains family, port and address
+    pub saddr: [__u8; 28usize],
+    pub daddr: [__u8; 28usize],
 }
@@ -568,5 +568,38 @@ pub struct tcp_packet_trace {
     pub flag_rst: bool,
     pub flag_syn: bool,
     pub flag_fin: bool,
-    pub checksum: u16
+    pub checksum: u16,
+    // TCP options, fields are only valid if their bit in options is set
+    pub options: u8,
+    pub mss: u16,
+    pub wscale: u8,
+    // Number of valid SACK blocks, start and end of every block
+    pub sack_blocks: u8,
+    pub sack: [u32; 8usize],
+    pub tsval: u32,
+    pub tsecr: u32,
+    // Data bytes of the segment, IP payload without the TCP header
+    pub payload_len: u16,
+    // IPv4 identification, 0 for IPv6
+    pub ip_id: u16,
+    // TTL or hop limit
+    pub ttl: u8,
+    // DSCP of the IPv4 TOS or IPv6 traffic class
+    pub dscp: u8,
+    // TCP header length including options
+    pub tcp_hdr_len: u8,
+    // ECN codepoint of the IP header, 0 Not-ECT, 1 ECT(1), 2 ECT(0), 3 CE
+    pub ecn: u8,
+    pub flag_ece: bool,
+    pub flag_cwr: bool,
 }
+
+// Bits of tcp_packet_trace.options, set for every option found in the header
+pub const TCP_OPTION_MSS: u8 = 1;
+pub const TCP_OPTION_WSCALE: u8 = 2;
+pub const TCP_OPTION_SACK_PERMITTED: u8 = 4;
+pub const TCP_OPTION_SACK: u8 = 8;
+pub const TCP_OPTION_TIMESTAMP: u8 = 16;
+
+// Max number of SACK blocks in tcp_packet_trace.sack
+pub const MAX_SACK_BLOCKS: usize = 4;
@@ -65573,6 +65573,8 @@ pub struct cwnd_trace_entry {
     pub dst_v6: [u8; 16usize],
     pub ports: u32,
     pub family: u16,
+    // Identifies the socket, stays unique when ports are reused
+    pub sock_cookie: u64,
     // Data
     pub snd_cwnd: u32,
 }
@@ -65587,6 +65589,8 @@ pub struct sock_trace_entry {
     pub dst_v6: [u8; 16usize],
     pub ports: u32,
     pub family: u16,
+    // Identifies the socket, stays unique when ports are reused
+    pub sock_cookie: u64,
     // SOCK Stats
     pub pacing_rate: u64,
     pub max_pacing_rate: u64,
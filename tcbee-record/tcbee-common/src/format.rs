@@ -0,0 +1,68 @@
+// Recording file format shared with tcbee-process
+// Has to be kept in sync with tcbee-process/src/format.rs!
+//
+// Header:
+//  magic "TCBEEREC" | version u16 | record type u16 | record size u32 | boot offset i64
+//  | start time u64 | layout length u16 | host length u16 | layout | host | crc32 u32
+// Followed by records:
+//  marker "TCBR" | payload length u32 | crc32 of payload u32 | payload
+// A file that was closed by the recorder ends with the marker "TCBE".
+// All integers are little endian, payloads are bincode encoded structs.
+use crate::bindings::{
+    tcp_bad_csum::tcp_bad_csum_entry,
+    tcp_header::tcp_packet_trace,
+    tcp_probe::tcp_probe_entry,
+    tcp_retransmit_synack::tcp_retransmit_synack_entry,
+    tcp_sock::{cwnd_trace_entry, sock_trace_entry},
+};
+
+pub const FILE_MAGIC: [u8; 8] = *b"TCBEEREC";
+pub const FORMAT_VERSION: u16 = 1;
+pub const RECORD_MARKER: [u8; 4] = *b"TCBR";
+pub const END_MARKER: [u8; 4] = *b"TCBE";
+
+// Record types, identify the struct stored in a file
+pub const RECORD_TCP_PACKET: u16 = 1;
+pub const RECORD_TCP_PROBE: u16 = 2;
+pub const RECORD_SOCK: u16 = 3;
+pub const RECORD_CWND: u16 = 4;
+pub const RECORD_RETRANSMIT_SYNACK: u16 = 5;
+pub const RECORD_BAD_CSUM: u16 = 6;
+
+// Implemented by all structs that are written to a recording
+pub trait RecordFormat {
+    const RECORD_TYPE: u16;
+    // Field names and types in order of serialization
+    // Changing a struct requires changing its layout, otherwise tcbee-process will misread it
+    const LAYOUT: &'static str;
+}
+
+impl RecordFormat for tcp_packet_trace {
+    const RECORD_TYPE: u16 = RECORD_TCP_PACKET;
+    const LAYOUT: &'static str = "time:u64,saddr:u32,daddr:u32,saddr_v6:[u8;16],daddr_v6:[u8;16],sport:u16,dport:u16,seq:u32,ack:u32,window:u16,flag_urg:bool,flag_ack:bool,flag_psh:bool,flag_rst:bool,flag_syn:bool,flag_fin:bool,checksum:u16,options:u8,mss:u16,wscale:u8,sack_blocks:u8,sack:[u32;8],tsval:u32,tsecr:u32,payload_len:u16,ip_id:u16,ttl:u8,dscp:u8,tcp_hdr_len:u8,ecn:u8,flag_ece:bool,flag_cwr:bool";
+}
+
+impl RecordFormat for tcp_probe_entry {
+    const RECORD_TYPE: u16 = RECORD_TCP_PROBE;
+    const LAYOUT: &'static str = "time:u64,saddr:[u8;28],daddr:[u8;28],sport:u16,dport:u16,family:u16,mark:u32,data_len:u16,snd_nxt:u32,snd_una:u32,snd_cwnd:u32,ssthresh:u32,snd_wnd:u32,srtt:u32,rcv_wnd:u32,sock_cookie:u64";
+}
+
+impl RecordFormat for sock_trace_entry {
+    const RECORD_TYPE: u16 = RECORD_SOCK;
+    const LAYOUT: &'static str = "time:u64,addr_v4:u64,src_v6:[u8;16],dst_v6:[u8;16],ports:u32,family:u16,sock_cookie:u64,pacing_rate:u64,max_pacing_rate:u64,backoff:u8,rto:u32,ato:u32,rcv_mss:u16,snd_cwnd:u32,bytes_acked:u64,snd_ssthresh:u32,total_retrans:u32,probes:u8,lost:u32,sacked_out:u32,retrans:u32,rcv_ssthresh:u32,rttvar:u32,advmss:u16,reordering:u32,rcv_rtt:u32,rcv_space:u32,bytes_received:u64,segs_out:u32,segs_in:u32,snd_wscale:u16,rcv_wscale:u16";
+}
+
+impl RecordFormat for cwnd_trace_entry {
+    const RECORD_TYPE: u16 = RECORD_CWND;
+    const LAYOUT: &'static str = "time:u64,addr_v4:u64,src_v6:[u8;16],dst_v6:[u8;16],ports:u32,family:u16,sock_cookie:u64,snd_cwnd:u32";
+}
+
+impl RecordFormat for tcp_retransmit_synack_entry {
+    const RECORD_TYPE: u16 = RECORD_RETRANSMIT_SYNACK;
+    const LAYOUT: &'static str = "time:u64,sport:u16,dport:u16,family:u16,saddr:[u8;4],daddr:[u8;4],saddr_v6:[u8;16],daddr_v6:[u8;16]";
+}
+
+impl RecordFormat for tcp_bad_csum_entry {
+    const RECORD_TYPE: u16 = RECORD_BAD_CSUM;
+    const LAYOUT: &'static str = "time:u64,saddr:[u8;28],daddr:[u8;28]";
+}
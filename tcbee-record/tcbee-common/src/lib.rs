@@ -1,2 +1,3 @@
 #![no_std]
-pub mod bindings;
\ No newline at end of file
+pub mod bindings;
+pub mod format;
@@ -15,6 +15,7 @@ mod config;
 // Performance counters for UI
 pub mod counters;
 pub mod flow_tracker;
+pub mod tcp_options;
 
 use aya_ebpf::{
     bindings::{xdp_action, TC_ACT_PIPE},
@@ -18,6 +18,7 @@ use crate::{
     },
     counters::{try_dropped_counter, try_egress_counter, try_handled_counter},
     flow_tracker::try_flow_tracker,
+    tcp_options::parse_tcp_options,
     FILTER_PORT,
 };
 
@@ -57,7 +58,7 @@ pub fn tc_hook(ctx: TcContext) -> Result<i32, i32> {
     }
 
     // If this code is reached, packet is IPv4 or IPv6 TCP so process and pass to map
-    let packet_trace: tcp_packet_trace;
+    let mut packet_trace: tcp_packet_trace;
     if ethertype == ETHERTYPE_IPV4 {
         // Get IPv4 header
         let ip4_hdr = ctx.load::<iphdr>(ETH_HDR_LEN).map_err(|_| TC_ACT_OK)?;
@@ -87,15 +88,42 @@ pub fn tc_hook(ctx: TcContext) -> Result<i32, i32> {
                 seq: tcp_hdr.seq.to_be(),
                 ack: tcp_hdr.ack_seq.to_be(),
                 window: tcp_hdr.window.to_be(),
-                flag_urg: tcp_hdr.urg().to_be() == 1,
-                flag_ack: tcp_hdr.ack().to_be() == 1,
-                flag_psh: tcp_hdr.psh().to_be() == 1,
-                flag_rst: tcp_hdr.rst().to_be() == 1,
-                flag_fin: tcp_hdr.fin().to_be() == 1,
-                flag_syn: tcp_hdr.syn().to_be() == 1,
+                flag_urg: tcp_hdr.urg() == 1,
+                flag_ack: tcp_hdr.ack() == 1,
+                flag_psh: tcp_hdr.psh() == 1,
+                flag_rst: tcp_hdr.rst() == 1,
+                flag_fin: tcp_hdr.fin() == 1,
+                flag_syn: tcp_hdr.syn() == 1,
                 checksum: tcp_hdr.check.to_be(),
+                options: 0,
+                mss: 0,
+                wscale: 0,
+                sack_blocks: 0,
+                sack: [0; 8],
+                tsval: 0,
+                tsecr: 0,
+                // IP total length without IP and TCP headers
+                payload_len: ip4_hdr
+                    .tot_len
+                    .to_be()
+                    .saturating_sub(ip4_hdr.ihl() as u16 * 4 + tcp_hdr.doff() * 4),
+                ip_id: ip4_hdr.id.to_be(),
+                ttl: ip4_hdr.ttl,
+                dscp: ip4_hdr.tos >> 2,
+                tcp_hdr_len: (tcp_hdr.doff() * 4) as u8,
+                ecn: ip4_hdr.tos & 0x3,
+                flag_ece: tcp_hdr.ece() == 1,
+                flag_cwr: tcp_hdr.cwr() == 1,
             };
 
+            // Options follow the fixed header, their length is given by the data offset
+            parse_tcp_options(
+                |offset| ctx.load::<u8>(offset).ok(),
+                ETH_HDR_LEN + IP_HDR_LEN,
+                tcp_hdr.doff() as usize * 4,
+                &mut packet_trace,
+            );
+
             let mut src = [0; 16];
             let mut dst = [0; 16];
             src[12..16].copy_from_slice(&ip4_hdr.saddr.to_le_bytes());
@@ -139,15 +167,39 @@ pub fn tc_hook(ctx: TcContext) -> Result<i32, i32> {
                 seq: tcp_hdr.seq.to_be(),
                 ack: tcp_hdr.ack_seq.to_be(),
                 window: tcp_hdr.window.to_be(),
-                flag_urg: tcp_hdr.urg().to_be() == 1,
-                flag_ack: tcp_hdr.ack().to_be() == 1,
-                flag_psh: tcp_hdr.psh().to_be() == 1,
-                flag_rst: tcp_hdr.rst().to_be() == 1,
-                flag_fin: tcp_hdr.fin().to_be() == 1,
-                flag_syn: tcp_hdr.syn().to_be() == 1,
+                flag_urg: tcp_hdr.urg() == 1,
+                flag_ack: tcp_hdr.ack() == 1,
+                flag_psh: tcp_hdr.psh() == 1,
+                flag_rst: tcp_hdr.rst() == 1,
+                flag_fin: tcp_hdr.fin() == 1,
+                flag_syn: tcp_hdr.syn() == 1,
                 checksum: tcp_hdr.check.to_be(),
+                options: 0,
+                mss: 0,
+                wscale: 0,
+                sack_blocks: 0,
+                sack: [0; 8],
+                tsval: 0,
+                tsecr: 0,
+                // TCP directly follows the IPv6 header, no extension headers
+                payload_len: ip6_hdr.payload_len.to_be().saturating_sub(tcp_hdr.doff() * 4),
+                ip_id: 0,
+                ttl: ip6_hdr.hop_limit,
+                // Upper 6 bits of the traffic class, which is split between priority and flow label
+                dscp: (ip6_hdr.priority() << 2) | (ip6_hdr.flow_lbl[0] >> 6),
+                tcp_hdr_len: (tcp_hdr.doff() * 4) as u8,
+                ecn: (ip6_hdr.flow_lbl[0] >> 4) & 0x3,
+                flag_ece: tcp_hdr.ece() == 1,
+                flag_cwr: tcp_hdr.cwr() == 1,
             };
 
+            parse_tcp_options(
+                |offset| ctx.load::<u8>(offset).ok(),
+                ETH_HDR_LEN + IP6_HDR_LEN,
+                tcp_hdr.doff() as usize * 4,
+                &mut packet_trace,
+            );
+
             // Write to flow tracker
             let a = try_flow_tracker(IpTuple {
                 src_ip: packet_trace.saddr_v6,
@@ -2,7 +2,7 @@ use aya_ebpf::{
     cty::c_void,
     helpers::{
         bpf_probe_read_kernel, bpf_probe_read_kernel_buf, bpf_skc_to_tcp_sock, bpf_tcp_sock,
-        gen::bpf_ktime_get_ns,
+        gen::{bpf_get_socket_cookie, bpf_ktime_get_ns},
     },
     macros::map,
     maps::RingBuf,
@@ -41,6 +41,13 @@ fn read_kernel<T>(src: *const T) -> Result<T, u32> {
     unsafe { bpf_probe_read_kernel(src).map_err(|_| 1u32) }
 }
 
+// Cookie of the socket, unique for the lifetime of the socket
+// Used by tcbee-process to separate connections that reuse the same ports
+#[inline(always)]
+fn socket_cookie(sk_ptr: *const sock) -> u64 {
+    unsafe { bpf_get_socket_cookie(sk_ptr as *mut c_void) }
+}
+
 
 #[inline(always)]
 pub fn try_sock_recvmsg_cwnd_only(ctx: FEntryContext) -> Result<u32, u32> {
@@ -68,10 +75,11 @@ let sport = ((ports & 0xFFFF) as u16).to_be();
         let cwnd_entry = cwnd_trace_entry {
             time: bpf_ktime_get_ns(),
             addr_v4,
-            src_v6: (*sk_ptr).__sk_common.skc_v6_rcv_saddr.in6_u.u6_addr8,
-            dst_v6: (*sk_ptr).__sk_common.skc_v6_daddr.in6_u.u6_addr8,
+            src_v6: (*sk_ptr).__sk_common.skc_v6_daddr.in6_u.u6_addr8,
+            dst_v6: (*sk_ptr).__sk_common.skc_v6_rcv_saddr.in6_u.u6_addr8,
             ports,
             family: (*sk_ptr).__sk_common.skc_family,
+            sock_cookie: socket_cookie(sk_ptr),
             snd_cwnd: read_kernel(&(*tcp_sck_ptr).snd_cwnd)?,
         };
         
@@ -118,6 +126,7 @@ pub fn try_sock_sendmsg_cwnd_only(ctx: FEntryContext) -> Result<u32, u32> {
             dst_v6: (*sk_ptr).__sk_common.skc_v6_daddr.in6_u.u6_addr8,
             ports: *ports,
             family: (*sk_ptr).__sk_common.skc_family,
+            sock_cookie: socket_cookie(sk_ptr),
             snd_cwnd: read_kernel(&(*tcp_sck_ptr).snd_cwnd)?,
         };
         
@@ -163,6 +172,7 @@ pub fn try_sock_sendmsg(ctx: FEntryContext) -> Result<u32, u32> {
             dst_v6: read_kernel(&(*sk_ptr).__sk_common.skc_v6_daddr.in6_u.u6_addr8)?,
             ports: read_kernel(&(*sk_ptr).__sk_common.__bindgen_anon_3.skc_portpair)?,
             family: read_kernel(&(*sk_ptr).__sk_common.skc_family)?,
+            sock_cookie: socket_cookie(sk_ptr),
             // SOCK Stats
             pacing_rate: read_kernel(&(*sk_ptr).sk_pacing_rate)?,
             max_pacing_rate: read_kernel(&(*sk_ptr).sk_max_pacing_rate)?,
@@ -239,6 +249,7 @@ pub fn try_tcp_recv_socket(ctx: FEntryContext) -> Result<u32, u32> {
             dst_v6: read_kernel(&(*sk_ptr).__sk_common.skc_v6_rcv_saddr.in6_u.u6_addr8)?,
             ports,
             family: read_kernel(&(*sk_ptr).__sk_common.skc_family)?,
+            sock_cookie: socket_cookie(sk_ptr),
             // SOCK Stats
             pacing_rate: read_kernel(&(*sk_ptr).sk_pacing_rate)?,
             max_pacing_rate: read_kernel(&(*sk_ptr).sk_max_pacing_rate)?,
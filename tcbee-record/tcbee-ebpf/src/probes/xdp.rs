@@ -17,7 +17,9 @@ use crate::{
         TCP_PROTOCOL, XDP_BUF_SIZE,
     },
     counters::{try_dropped_counter, try_handled_counter, try_ingress_counter},
-    flow_tracker::try_flow_tracker, FILTER_PORT,
+    flow_tracker::try_flow_tracker,
+    tcp_options::parse_tcp_options,
+    FILTER_PORT,
 };
 
 #[map(name = "TCP_PACKETS_INGRESS")]
@@ -30,8 +32,16 @@ pub fn xdp_hook(ctx: XdpContext) -> Result<u32, u32> {
     let data_end = ctx.data_end();
     //let data_len = data_end - data_start;
 
+    // Reads a single byte of the packet, None if it is outside of the packet
+    let load_byte = |offset: usize| -> Option<u8> {
+        if data_start + offset + 1 > data_end {
+            return None;
+        }
+        Some(unsafe { *((data_start + offset) as *const u8) })
+    };
+
     // Struct to write result to
-    let packet_trace: tcp_packet_trace;
+    let mut packet_trace: tcp_packet_trace;
 
     // Check if data long enough to read eth header
     if data_start + ETH_HDR_LEN > data_end {
@@ -102,15 +112,42 @@ pub fn xdp_hook(ctx: XdpContext) -> Result<u32, u32> {
                 seq: tcp_hdr.seq.to_be(),
                 ack: tcp_hdr.ack_seq.to_be(),
                 window: tcp_hdr.window.to_be(),
-                flag_urg: tcp_hdr.urg().to_be() == 1,
-                flag_ack: tcp_hdr.ack().to_be() == 1,
-                flag_psh: tcp_hdr.psh().to_be() == 1,
-                flag_rst: tcp_hdr.rst().to_be() == 1,
-                flag_fin: tcp_hdr.fin().to_be() == 1,
-                flag_syn: tcp_hdr.syn().to_be() == 1,
+                flag_urg: tcp_hdr.urg() == 1,
+                flag_ack: tcp_hdr.ack() == 1,
+                flag_psh: tcp_hdr.psh() == 1,
+                flag_rst: tcp_hdr.rst() == 1,
+                flag_fin: tcp_hdr.fin() == 1,
+                flag_syn: tcp_hdr.syn() == 1,
                 checksum: tcp_hdr.check.to_be(),
+                options: 0,
+                mss: 0,
+                wscale: 0,
+                sack_blocks: 0,
+                sack: [0; 8],
+                tsval: 0,
+                tsecr: 0,
+                // IP total length without IP and TCP headers
+                payload_len: ip4_hdr
+                    .tot_len
+                    .to_be()
+                    .saturating_sub(ip4_hdr.ihl() as u16 * 4 + tcp_hdr.doff() * 4),
+                ip_id: ip4_hdr.id.to_be(),
+                ttl: ip4_hdr.ttl,
+                dscp: ip4_hdr.tos >> 2,
+                tcp_hdr_len: (tcp_hdr.doff() * 4) as u8,
+                ecn: ip4_hdr.tos & 0x3,
+                flag_ece: tcp_hdr.ece() == 1,
+                flag_cwr: tcp_hdr.cwr() == 1,
             };
 
+            // Options follow the fixed header, their length is given by the data offset
+            parse_tcp_options(
+                load_byte,
+                ETH_HDR_LEN + IP_HDR_LEN,
+                tcp_hdr.doff() as usize * 4,
+                &mut packet_trace,
+            );
+
             // Write to flow tracker
             
             let mut src = [0; 16];
@@ -176,15 +213,39 @@ pub fn xdp_hook(ctx: XdpContext) -> Result<u32, u32> {
                 seq: tcp_hdr.seq.to_be(),
                 ack: tcp_hdr.ack_seq.to_be(),
                 window: tcp_hdr.window.to_be(),
-                flag_urg: tcp_hdr.urg().to_be() == 1,
-                flag_ack: tcp_hdr.ack().to_be() == 1,
-                flag_psh: tcp_hdr.psh().to_be() == 1,
-                flag_rst: tcp_hdr.rst().to_be() == 1,
-                flag_fin: tcp_hdr.fin().to_be() == 1,
-                flag_syn: tcp_hdr.syn().to_be() == 1,
+                flag_urg: tcp_hdr.urg() == 1,
+                flag_ack: tcp_hdr.ack() == 1,
+                flag_psh: tcp_hdr.psh() == 1,
+                flag_rst: tcp_hdr.rst() == 1,
+                flag_fin: tcp_hdr.fin() == 1,
+                flag_syn: tcp_hdr.syn() == 1,
                 checksum: tcp_hdr.check.to_be(),
+                options: 0,
+                mss: 0,
+                wscale: 0,
+                sack_blocks: 0,
+                sack: [0; 8],
+                tsval: 0,
+                tsecr: 0,
+                // TCP directly follows the IPv6 header, no extension headers
+                payload_len: ip6_hdr.payload_len.to_be().saturating_sub(tcp_hdr.doff() * 4),
+                ip_id: 0,
+                ttl: ip6_hdr.hop_limit,
+                // Upper 6 bits of the traffic class, which is split between priority and flow label
+                dscp: (ip6_hdr.priority() << 2) | (ip6_hdr.flow_lbl[0] >> 6),
+                tcp_hdr_len: (tcp_hdr.doff() * 4) as u8,
+                ecn: (ip6_hdr.flow_lbl[0] >> 4) & 0x3,
+                flag_ece: tcp_hdr.ece() == 1,
+                flag_cwr: tcp_hdr.cwr() == 1,
             };
 
+            parse_tcp_options(
+                load_byte,
+                ETH_HDR_LEN + IP6_HDR_LEN,
+                tcp_hdr.doff() as usize * 4,
+                &mut packet_trace,
+            );
+
             // Write to flow tracker
             let _ = try_flow_tracker(IpTuple {
                 src_ip: ip6_hdr.saddr.in6_u.u6_addr8,
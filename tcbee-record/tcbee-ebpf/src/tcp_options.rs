@@ -0,0 +1,131 @@
+// Parsing of TCP options for the XDP and TC probes
+// Options are read byte by byte through a loader that checks the packet bounds,
+// the loop is bounded by the max options length so that the verifier accepts it.
+use tcbee_common::bindings::tcp_header::{
+    tcp_packet_trace, MAX_SACK_BLOCKS, TCP_OPTION_MSS, TCP_OPTION_SACK,
+    TCP_OPTION_SACK_PERMITTED, TCP_OPTION_TIMESTAMP, TCP_OPTION_WSCALE,
+};
+
+// Max length of options, data offset is at most 15 words
+pub const MAX_OPTIONS_LEN: usize = 40;
+
+// Option kinds
+const TCPOPT_EOL: u8 = 0;
+const TCPOPT_NOP: u8 = 1;
+const TCPOPT_MSS: u8 = 2;
+const TCPOPT_WSCALE: u8 = 3;
+const TCPOPT_SACK_PERMITTED: u8 = 4;
+const TCPOPT_SACK: u8 = 5;
+const TCPOPT_TIMESTAMP: u8 = 8;
+
+#[inline(always)]
+fn load_u16<F: Fn(usize) -> Option<u8>>(load: &F, offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([load(offset)?, load(offset + 1)?]))
+}
+
+#[inline(always)]
+fn load_u32<F: Fn(usize) -> Option<u8>>(load: &F, offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes([
+        load(offset)?,
+        load(offset + 1)?,
+        load(offset + 2)?,
+        load(offset + 3)?,
+    ]))
+}
+
+// Parses the options of a TCP header of length header_len that starts at offset start
+// load returns the byte at an offset of the packet, None if it is out of bounds
+#[inline(always)]
+pub fn parse_tcp_options<F: Fn(usize) -> Option<u8>>(
+    load: F,
+    start: usize,
+    header_len: usize,
+    trace: &mut tcp_packet_trace,
+) {
+    // Options follow the fixed 20 byte header
+    if header_len <= 20 {
+        return;
+    }
+    let mut len = header_len - 20;
+    if len > MAX_OPTIONS_LEN {
+        len = MAX_OPTIONS_LEN;
+    }
+    let start = start + 20;
+
+    let mut offset = 0;
+    for _ in 0..MAX_OPTIONS_LEN {
+        if offset >= len {
+            break;
+        }
+
+        let Some(kind) = load(start + offset) else {
+            break;
+        };
+        if kind == TCPOPT_EOL {
+            break;
+        }
+        if kind == TCPOPT_NOP {
+            offset += 1;
+            continue;
+        }
+
+        let Some(size) = load(start + offset + 1) else {
+            break;
+        };
+        let size = size as usize;
+        if size < 2 || offset + size > len {
+            break;
+        }
+        let value = start + offset + 2;
+
+        match kind {
+            TCPOPT_MSS if size == 4 => {
+                if let Some(mss) = load_u16(&load, value) {
+                    trace.mss = mss;
+                    trace.options |= TCP_OPTION_MSS;
+                }
+            }
+            TCPOPT_WSCALE if size == 3 => {
+                if let Some(wscale) = load(value) {
+                    trace.wscale = wscale;
+                    trace.options |= TCP_OPTION_WSCALE;
+                }
+            }
+            TCPOPT_SACK_PERMITTED if size == 2 => trace.options |= TCP_OPTION_SACK_PERMITTED,
+            TCPOPT_SACK if size >= 10 => {
+                let blocks = (size - 2) / 8;
+                let mut read = 0;
+                for block in 0..MAX_SACK_BLOCKS {
+                    if block >= blocks {
+                        break;
+                    }
+                    let (Some(left), Some(right)) = (
+                        load_u32(&load, value + block * 8),
+                        load_u32(&load, value + block * 8 + 4),
+                    ) else {
+                        break;
+                    };
+                    trace.sack[block * 2] = left;
+                    trace.sack[block * 2 + 1] = right;
+                    read += 1;
+                }
+                if read > 0 {
+                    trace.sack_blocks = read;
+                    trace.options |= TCP_OPTION_SACK;
+                }
+            }
+            TCPOPT_TIMESTAMP if size == 10 => {
+                if let (Some(tsval), Some(tsecr)) =
+                    (load_u32(&load, value), load_u32(&load, value + 4))
+                {
+                    trace.tsval = tsval;
+                    trace.tsecr = tsecr;
+                    trace.options |= TCP_OPTION_TIMESTAMP;
+                }
+            }
+            _ => {}
+        }
+
+        offset += size;
+    }
+}
@@ -3,5 +3,7 @@ pub const AF_INET6: u16 = 10;
 
 // Number of entries in buffered file writers
 pub const WRITER_BUFFER_SIZE: usize = 10000;
+// Max time entries stay in the file writer buffer, bounds the delay of tcbee-process --follow
+pub const WRITER_FLUSH_MS_INTERVAL: u128 = 500;
 
 pub const UI_UPDATE_MS_INTERVAL: u128 = 100;
\ No newline at end of file
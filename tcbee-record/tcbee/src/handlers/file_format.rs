@@ -0,0 +1,78 @@
+// Encoding of the recording file format, see tcbee-common/src/format.rs for the layout
+use tcbee_common::format::{RecordFormat, FILE_MAGIC, FORMAT_VERSION, RECORD_MARKER};
+
+// Nanoseconds of the given clock, 0 if the clock cannot be read
+fn clock_ns(clock: libc::clockid_t) -> i64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    if unsafe { libc::clock_gettime(clock, &mut ts) } != 0 {
+        return 0;
+    }
+    ts.tv_sec as i64 * 1_000_000_000 + ts.tv_nsec as i64
+}
+
+// Recorded timestamps come from bpf_ktime_get_ns which uses CLOCK_MONOTONIC
+// Adding this offset to a timestamp gives nanoseconds since UNIX epoch
+fn boot_offset() -> i64 {
+    clock_ns(libc::CLOCK_REALTIME) - clock_ns(libc::CLOCK_MONOTONIC)
+}
+
+fn host_info() -> String {
+    let mut uts: libc::utsname = unsafe { std::mem::zeroed() };
+    if unsafe { libc::uname(&mut uts) } != 0 {
+        return String::new();
+    }
+
+    let field = |raw: &[libc::c_char]| -> String {
+        let bytes: Vec<u8> = raw
+            .iter()
+            .take_while(|c| **c != 0)
+            .map(|c| *c as u8)
+            .collect();
+        String::from_utf8_lossy(&bytes).to_string()
+    };
+
+    format!(
+        "{} {} {}",
+        field(&uts.nodename),
+        field(&uts.release),
+        field(&uts.machine)
+    )
+}
+
+// Creates the file header for records of type T with a serialized size of record_size
+pub fn encode_header<T: RecordFormat>(record_size: usize) -> Vec<u8> {
+    let layout = T::LAYOUT.as_bytes();
+    let host = host_info();
+    let host = host.as_bytes();
+
+    let mut header: Vec<u8> = Vec::with_capacity(36 + layout.len() + host.len() + 4);
+    header.extend_from_slice(&FILE_MAGIC);
+    header.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    header.extend_from_slice(&T::RECORD_TYPE.to_le_bytes());
+    header.extend_from_slice(&(record_size as u32).to_le_bytes());
+    header.extend_from_slice(&boot_offset().to_le_bytes());
+    header.extend_from_slice(&(clock_ns(libc::CLOCK_MONOTONIC) as u64).to_le_bytes());
+    header.extend_from_slice(&(layout.len() as u16).to_le_bytes());
+    header.extend_from_slice(&(host.len() as u16).to_le_bytes());
+    header.extend_from_slice(layout);
+    header.extend_from_slice(host);
+
+    // Checksum over all previous header bytes
+    let checksum = crc32fast::hash(&header);
+    header.extend_from_slice(&checksum.to_le_bytes());
+
+    header
+}
+
+// Prepends marker, length and checksum to a serialized record
+pub fn encode_record(payload: &[u8]) -> Vec<u8> {
+    let mut record: Vec<u8> = Vec::with_capacity(12 + payload.len());
+    record.extend_from_slice(&RECORD_MARKER);
+    record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    record.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+    record.extend_from_slice(payload);
+    record
+}
@@ -2,13 +2,15 @@ pub mod tracepoints;
 pub mod xdp_handler;
 pub mod socks;
 pub mod cwnd;
+pub mod file_format;
 
-use std::marker::PhantomData;
+use std::{marker::PhantomData, time::Instant};
 
 use aya::maps::RingBuf;
 use libc::O_NONBLOCK;
 use log::{debug, error, info};
 use serde::Serialize;
+use tcbee_common::format::{RecordFormat, END_MARKER};
 use tokio::{
     fs::OpenOptions,
     io::{AsyncWriteExt, BufWriter},
@@ -16,7 +18,7 @@ use tokio::{
 };
 use tokio_util::sync::CancellationToken;
 
-use crate::config::WRITER_BUFFER_SIZE;
+use crate::config::{WRITER_BUFFER_SIZE, WRITER_FLUSH_MS_INTERVAL};
 
 
 // Turn sized struct into u8 buffer for datagram sockets
@@ -39,7 +41,7 @@ pub struct BufferHandler<T> {
 }
 
 // TODO: T should be restricted to structs that are used for tracepoint queues!
-impl<T: std::fmt::Debug + Clone + Copy + Serialize> BufferHandler<T> {
+impl<T: std::fmt::Debug + Clone + Copy + Default + Serialize + RecordFormat> BufferHandler<T> {
     pub fn new<Entry>(
         name: &str,
         token: CancellationToken,
@@ -70,7 +72,8 @@ impl<T: std::fmt::Debug + Clone + Copy + Serialize> BufferHandler<T> {
         let try_outfile = OpenOptions::new()
             .create(true)
             .write(true)
-            .append(true)
+            // Every recording starts with a new header, do not append to old recordings
+            .truncate(true)
             .custom_flags(O_NONBLOCK) // Do not wait on OS to finish writing, this allows to write into the buffer earlier
             .open(&self.file_path)
             .await;
@@ -91,12 +94,28 @@ impl<T: std::fmt::Debug + Clone + Copy + Serialize> BufferHandler<T> {
 
         info!("Handler {}, size: {} bytes", self.name, entry_size);
 
+        // Size of a serialized entry, differs from the in memory size due to padding
+        let record_size = bincode::serialized_size(&T::default()).unwrap() as usize;
+
+        let header = file_format::encode_header::<T>(record_size);
+        if let Err(err) = writer.write_all(&header).await {
+            error!(
+                "Cannot write header to file {}. Error: {}",
+                self.file_path, err
+            );
+            return;
+        }
+        // Make the header visible to readers that follow the file
+        let _ = writer.flush().await;
+        let mut last_flush = Instant::now();
+
         loop {
             // Stop thread if signal received by parent
             if self.token.is_cancelled() {
                 info!("Stopping {} handler on token cancel!", self.name);
-                // Flush remaining entries in writer to file
-                writer.flush().await;
+                // Mark file as complete and flush remaining entries in writer to file
+                let _ = writer.write_all(&END_MARKER).await;
+                let _ = writer.flush().await;
                 return;
             }
 
@@ -120,26 +139,25 @@ impl<T: std::fmt::Debug + Clone + Copy + Serialize> BufferHandler<T> {
                     return;
                 }
 
-                unsafe {    
-                    let res_val = res.unwrap();
-                    // TODO: error handling!
-                    let buf = bincode::serialize(&res_val).unwrap();
+                let res_val = res.unwrap();
+                // TODO: error handling!
+                let buf = bincode::serialize(&res_val).unwrap();
 
-                    //let slice = as_u8_slice(&res_val);
+                debug!("Entry: {:?}", buf);
 
-                    debug!("Entry: {:?}",buf);
-
-                    let written = writer
-                        .write(&buf)
-                        .await
-                        .expect("Failed write!");
-
-                    // Marker for alignment of packet in read step
-                    // TODO: maybe remove for performance reasons!
-                    let _ = writer.write(&[255,255,255,255]).await;
-                }
+                // Length and checksum allow the reader to validate and resync
+                writer
+                    .write_all(&file_format::encode_record(&buf))
+                    .await
+                    .expect("Failed write!");
             } else {
-                // Queue was empty, yield to let other tasks work
+                // Queue was empty, write buffered entries so that followers do not wait for a full buffer
+                if last_flush.elapsed().as_millis() > WRITER_FLUSH_MS_INTERVAL {
+                    let _ = writer.flush().await;
+                    last_flush = Instant::now();
+                }
+
+                // Yield to let other tasks work
                 // TODO: check if it is better to yield after every buffer.next()
                 task::yield_now().await;
             }
@@ -5,13 +5,14 @@ mod tcpprobe;
 
 use aya::Pod;
 use serde::Serialize;
+use tcbee_common::format::RecordFormat;
 use tcbee_common::bindings::{
     tcp_bad_csum::tcp_bad_csum_entry, tcp_probe::tcp_probe_entry, tcp_retransmit_synack::tcp_retransmit_synack_entry, tcp_sock::sock_trace_entry, EBPFTracePointType
 };
 
 // Constraint bounds for handler objects
 pub trait HandlerConstraints<T>:
-    std::fmt::Debug + Clone + Copy + Pod + std::marker::Send + std::marker::Sync + EBPFTracePointType + Serialize
+    std::fmt::Debug + Clone + Copy + Pod + std::marker::Send + std::marker::Sync + EBPFTracePointType + Serialize + Default + RecordFormat
 {
 }
 impl HandlerConstraints<tcp_probe_entry> for tcp_probe_entry {}
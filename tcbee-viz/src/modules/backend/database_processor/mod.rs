@@ -1,5 +1,4 @@
 // contains logic fro pre-processor that consumes ProcessedPlotData
 // and manipulates the given dataset
-pub mod processor_dummy;
+// the processors themselves are implemented in the shared tcbee_plugins crate
 pub mod trait_database_processor;
-pub mod plugin_upper_window;
\ No newline at end of file
@@ -1,78 +1,44 @@
-//  contains trate for implementing a processor for data from Flows and their series-data
+//  connects the processors of the shared tcbee_plugins crate to the visualization
 // database_processor can:
 //  - create new datapoints based on given flow-series-data
 //  - modify the database with new elements / delete entries from database
 
-use crate::{modules::backend::database_processor::plugin_upper_window::UpperWindow, DataValue, FlowSeriesData, ProcessedPlotData};
-// ----- //
-use std::slice::Iter;
-
-use super::processor_dummy::DummyProcessor;
-
-pub trait PreProcessor {
-    // internal constants
-    // const MODULE_NAME:str;
-    // const MODULE_ID:i32;
-    // const MODULE_DESCRIPTION:str;
-    // const MODULE_REQUIRED_TIME_SERIES_NAMES:Vec<str>;
-
-    fn receive_name(&self) -> String;
-
-    /// returns description of module
-    fn receive_description(&self) -> String;
-
-    /// reteurns a Vector rcontaining string representation of the names for each time series required
-    /// REQUIREMENT: must contain a valid name for a timeseries!
-    fn receive_required_timeseries(&self) -> Vec<String>;
-
-    fn receive_required_series_formatted(&self, collection_of_names: Vec<String>) -> String {
-        let mut formatted_string_collection = String::new();
-
-        for entry in collection_of_names {
-            let formatted_line = format!("name of timeseries: {:?}", entry);
-            formatted_string_collection =
-                format!("{formatted_string_collection}\n{formatted_line}");
-        }
-        formatted_string_collection
+use crate::{FlowSeriesData, ProcessedPlotData};
+use ts_storage::DataValue;
+
+pub use tcbee_plugins::{PreProcessor, ProcessorImplementation, SeriesData};
+
+/// takes ProcessedPlotData representing a single Flow and runs the given processor on it
+/// returns a vector of the newly generated Time-Series for the given Flow
+/// new series inherit their plot settings from the first series of the flow
+pub fn create_new_time_series_from_plot_data(
+    processor: &dyn PreProcessor,
+    plot_data: &ProcessedPlotData,
+) -> Result<Vec<FlowSeriesData>, String> {
+    let series_collection = &plot_data.point_collection;
+    let template = series_collection.first().ok_or("no series received")?;
+
+    // Processors expect their series in the order of receive_required_timeseries
+    let mut input: Vec<SeriesData> = Vec::new();
+    for name in processor.receive_required_timeseries() {
+        let series = series_collection
+            .iter()
+            .find(|entry| entry.name == name)
+            .ok_or(format!("No {} series!", name))?;
+        input.push(series.to_series_data());
     }
-
-    // fn process_time_values( time_information: &ProcessedPlotData) -> ProcessedPlotData;
-    /// takes ProcessedPlotData representing a single Flow and the required time_series values
-    /// returns a vector of the newly generated Time-Series for the given Flow
-    /// ASSUMPTION:the supplied database has not been modified yet
-    fn create_new_time_series_from_plot_data(
-        &self,
-        plot_data: &ProcessedPlotData,
-    ) -> Result<Vec<FlowSeriesData>, String>;
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum ProcessorImplementation {
-    // None,
-    DummyProcessor,
-    UpperWindow
-}
-impl ToString for ProcessorImplementation {
-    fn to_string(&self) -> String {
-        match self {
-            ProcessorImplementation::DummyProcessor => "Dummy Processor".to_string(),
-            ProcessorImplementation::UpperWindow => "Upper TCP Window Number".to_string(),
-            _ => "None selected".to_string(),
+    // Optional series follow, empty if the flow does not contain them
+    for name in processor.receive_optional_timeseries() {
+        match series_collection.iter().find(|entry| entry.name == name) {
+            Some(series) => input.push(series.to_series_data()),
+            None => input.push(SeriesData::new(&name, DataValue::Boolean(false))),
         }
     }
-}
-impl ProcessorImplementation {
-    pub const ALL: &'static [Self] = &[Self::DummyProcessor, Self::UpperWindow];
 
-    pub fn create_processor(&self) -> Box<dyn PreProcessor> {
-        match self {
-            Self::DummyProcessor => Box::new(DummyProcessor::default()),
-            Self::UpperWindow => Box::new(UpperWindow::default())
-        }
-    }
-}
+    let output = processor.create_new_time_series(&input)?;
 
-//     pub fn iterator() -> Iter<'static, PreProcessor> {
-//         ProcessorImplementation::ALL.iter()
-//     }
-// }
+    Ok(output
+        .into_iter()
+        .map(|series| FlowSeriesData::from_series_data(series, template))
+        .collect())
+}
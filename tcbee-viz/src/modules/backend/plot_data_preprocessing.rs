@@ -15,6 +15,11 @@ use std::{
     f64::{MAX, MIN},
     usize,
 };
+use tcbee_plugins::plugin_cc_state::{
+    STATE_APP_LIMITED, STATE_CONGESTION_AVOIDANCE, STATE_FAST_RECOVERY, STATE_LOSS,
+    STATE_SLOW_START,
+};
+use tcbee_plugins::plugin_limiter::{LIMITED_CWND, LIMITED_PACING, LIMITED_RWND};
 use ts_storage::DataValue;
 
 pub fn retrieve_y_bounds_from_plot_data(
@@ -285,6 +290,35 @@ pub fn filter_and_prepare_string_from_series(
     }
 }
 
+/// string series that hold a state until their next point, drawn as colored bands behind the chart
+pub const STATE_SERIES: &[&str] = &["CC_STATE", "LIMITER"];
+
+pub fn is_state_series(name: &str) -> bool {
+    STATE_SERIES.contains(&name)
+}
+
+/// returns a transparent color for a state, known congestion control phases and limiters have fixed colors
+pub fn state_band_color(state: &str) -> style::RGBAColor {
+    let color = match state {
+        STATE_SLOW_START => style::RGBAColor(77, 175, 74, 1.0),
+        STATE_CONGESTION_AVOIDANCE => style::RGBAColor(55, 126, 184, 1.0),
+        STATE_FAST_RECOVERY => style::RGBAColor(255, 127, 0, 1.0),
+        STATE_LOSS => style::RGBAColor(228, 26, 28, 1.0),
+        // APP_LIMITED of the limiter plugin shares the color
+        STATE_APP_LIMITED => style::RGBAColor(153, 153, 153, 1.0),
+        LIMITED_CWND => style::RGBAColor(152, 78, 163, 1.0),
+        LIMITED_RWND => style::RGBAColor(166, 86, 40, 1.0),
+        LIMITED_PACING => style::RGBAColor(247, 129, 191, 1.0),
+        _ => {
+            // same color for the same state on every redraw
+            let colors = generate_12_colors_scheme2();
+            let index = state.bytes().map(|byte| byte as usize).sum::<usize>() % colors.len();
+            colors[index]
+        }
+    };
+    style::RGBAColor(color.0, color.1, color.2, 0.2)
+}
+
 #[derive(Clone)]
 pub enum ColorScheme {
     Random,
@@ -8,13 +8,13 @@ use iced::{
 };
 use iced_aw::TabLabel;
 use plotters_iced::ChartWidget;
+use rfd::FileDialog;
 
 use crate::{
     modules::{
         backend::{
-            database_processor::{
-                processor_dummy,
-                trait_database_processor::{PreProcessor, ProcessorImplementation},
+            database_processor::trait_database_processor::{
+                create_new_time_series_from_plot_data, PreProcessor, ProcessorImplementation,
             },
             struct_tcp_flow_wrapper::TcpFlowWrapper,
         },
@@ -49,6 +49,7 @@ pub enum MessageModifyDatabase {
     FlowSelected(i64),
     PreviewRequested,
     SaveToDatabase,
+    ButtonLoadPluginPressed,
 }
 
 impl MessageCreator for MessageModifyDatabase {
@@ -60,6 +61,8 @@ impl MessageCreator for MessageModifyDatabase {
 pub struct ScreenModifyDatabase {
     application_settings: Arc<RwLock<ApplicationSettings>>,
     selected_model: Option<ProcessorImplementation>,
+    // WebAssembly plugins loaded at runtime, offered next to the built-in ones
+    loaded_plugins: Vec<ProcessorImplementation>,
     selected_flow: TcpFlowWrapper,
     flow_data: Option<ProcessedPlotData>,
     // bundle_of_new_series: Option<FlowSeriesData>,
@@ -72,6 +75,7 @@ impl ScreenModifyDatabase {
         ScreenModifyDatabase {
             application_settings: settings_reference,
             selected_model: None,
+            loaded_plugins: Vec::new(),
             selected_flow: TcpFlowWrapper::default(),
             flow_data: None,
             // bundle_of_new_series: None,
@@ -101,12 +105,23 @@ impl ScreenModifyDatabase {
         if let Some(module_selected) = &self.selected_model {
             if let Some(flow_id) = self.selected_flow.flow_id {
                 let instance = module_selected.create_processor();
-                let required_series_ids = read_settings
+                let mut required_series_ids = read_settings
                     .intermediate_interface
                     .receive_series_id_from_string_and_flow_id(
                         flow_id,
                         instance.receive_required_timeseries(),
                     );
+                // optional series are only added if the flow contains them
+                if let Ok(Some(ids)) = &mut required_series_ids {
+                    for name in instance.receive_optional_timeseries() {
+                        if let Ok(Some(found)) = read_settings
+                            .intermediate_interface
+                            .receive_series_id_from_string_and_flow_id(flow_id, vec![name])
+                        {
+                            ids.extend(found);
+                        }
+                    }
+                }
                 println!("found the following ids to use {:?}", required_series_ids);
                 return required_series_ids;
             // && self.selected_flow.flow_id.is_some()
@@ -145,7 +160,7 @@ impl ScreenModifyDatabase {
             Ok(flow_data) => {
                 let module_instance = selected_module.create_processor();
                 let maybe_new_data =
-                    module_instance.create_new_time_series_from_plot_data(&flow_data);
+                    create_new_time_series_from_plot_data(module_instance.as_ref(), &flow_data);
                 return maybe_new_data;
             }
             Err(value) => {
@@ -257,6 +272,28 @@ impl ScreenModifyDatabase {
 
                 // then take and add these information to the database accordingly
             }
+            MessageModifyDatabase::ButtonLoadPluginPressed => {
+                let file_selection = FileDialog::new()
+                    .add_filter("*.wasm", &["wasm"])
+                    .set_directory("~/")
+                    .pick_file();
+                let Some(path) = file_selection else {
+                    println!("no path was selected, aborting");
+                    return;
+                };
+                match ProcessorImplementation::load_wasm(&path.to_string_lossy()) {
+                    Ok(plugin) => {
+                        // reloading a plugin replaces the previous version
+                        self.loaded_plugins.retain(|entry| entry != &plugin);
+                        self.loaded_plugins.push(plugin.clone());
+                        self.status_message = Some(format!("loaded plugin {}", plugin.to_string()));
+                        self.update(MessageModifyDatabase::ModuleSelected(plugin));
+                    }
+                    Err(error) => {
+                        self.status_message = Some(format!("could not load plugin: {}", error));
+                    }
+                }
+            }
             MessageModifyDatabase::MouseEvent(_event, _point) => {}
         }
     }
@@ -294,12 +331,24 @@ impl ScreenModifyDatabase {
     }
 
     fn display_module_picklist(&self) -> Element<'_, MessageModifyDatabase> {
+        let mut available_modules: Vec<ProcessorImplementation> =
+            ProcessorImplementation::ALL.to_vec();
+        available_modules.extend(self.loaded_plugins.iter().cloned());
+
         let module_pick_list = pick_list(
-            ProcessorImplementation::ALL,
+            available_modules,
             self.selected_model.clone(),
             MessageModifyDatabase::ModuleSelected,
         );
-        module_pick_list.into()
+        let load_button =
+            button("Load Plugin").on_press(MessageModifyDatabase::ButtonLoadPluginPressed);
+
+        Row::new()
+            .spacing(SPACE_BETWEEN_TEXT)
+            .align_y(Alignment::Center)
+            .push(module_pick_list)
+            .push(load_button)
+            .into()
     }
 
     fn display_module_selection(&self) -> Element<'_, MessageModifyDatabase> {
@@ -311,8 +360,13 @@ impl ScreenModifyDatabase {
                 (
                     instance.receive_description(),
                     instance.receive_name(),
-                    instance
-                        .receive_required_series_formatted(instance.receive_required_timeseries()),
+                    instance.receive_required_series_formatted(
+                        [
+                            instance.receive_required_timeseries(),
+                            instance.receive_optional_timeseries(),
+                        ]
+                        .concat(),
+                    ),
                 )
             }
             _ => (
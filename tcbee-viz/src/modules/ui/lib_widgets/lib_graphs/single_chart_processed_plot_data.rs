@@ -2,8 +2,9 @@
 use crate::{
     modules::{
         backend::plot_data_preprocessing::{
-            filter_false_boolean_from_data, prepare_bool, prepare_float, prepare_int,
-            retrieve_y_bounds_from_plot_data, skip_every_nth, skip_outside_of_bound,
+            filter_false_boolean_from_data, is_state_series, prepare_bool, prepare_float,
+            prepare_int, retrieve_y_bounds_from_plot_data, skip_every_nth, skip_outside_of_bound,
+            state_band_color,
         },
         ui::{
             lib_styling::app_style_settings::{
@@ -14,7 +15,7 @@ use crate::{
             lib_widgets::lib_graphs::struct_zoom_bounds::{zoom_range_is_small, ZoomBound2D},
         },
     },
-    DataValue, ProcessedPlotData,
+    DataValue, FlowSeriesData, ProcessedPlotData,
 };
 
 use plotters::{
@@ -22,7 +23,7 @@ use plotters::{
     coord::types::RangedCoordf64,
     prelude::{Cartesian2d, Circle, FontTransform, Rectangle},
     series::{LineSeries, PointSeries},
-    style::{IntoFont, RGBAColor},
+    style::{Color, IntoFont, RGBAColor},
 };
 
 use plotters_iced::{Chart, ChartBuilder, DrawingBackend, Renderer};
@@ -54,6 +55,39 @@ impl ProcessedPlotData {
 
         let _ = chart.draw_series(vertical_line);
     }
+
+    /// draws a colored band for each value of a state series, e.g. CC_STATE
+    /// a state holds until the next point, the last one until the end of the chart
+    fn draw_state_bands<DB: DrawingBackend>(
+        &self,
+        chart: &mut ChartContext<'_, DB, Cartesian2d<RangedCoordf64, RangedCoordf64>>,
+        series: &FlowSeriesData,
+    ) {
+        let chart_x_ranges = chart.plotting_area().get_x_range();
+        let chart_y_ranges = chart.plotting_area().get_y_range();
+
+        let ends = series
+            .timestamps
+            .iter()
+            .skip(1)
+            .chain(std::iter::once(&chart_x_ranges.end));
+
+        for ((start, value), end) in series.timestamps.iter().zip(series.data.iter()).zip(ends) {
+            if *end < chart_x_ranges.start || *start > chart_x_ranges.end {
+                continue;
+            }
+            if let DataValue::String(state) = value {
+                let band = Rectangle::new(
+                    [
+                        (start.max(chart_x_ranges.start), chart_y_ranges.start),
+                        (end.min(chart_x_ranges.end), chart_y_ranges.end),
+                    ],
+                    state_band_color(state).filled(),
+                );
+                let _ = chart.draw_series(std::iter::once(band));
+            }
+        }
+    }
 }
 // Fixme remove bound to ScreenSingleFlowPlotting-Struct
 // Should be bound to PlotSeriesData
@@ -169,7 +203,18 @@ impl<Message: 'static + Clone + MessageCreator> Chart<Message> for ProcessedPlot
         //  creating line for each attribute selected!
 
         let data_point_collection = &self.point_collection;
+
+        // state bands are drawn first to stay behind all other series
         for series_attribute in data_point_collection {
+            if is_state_series(&series_attribute.name) {
+                self.draw_state_bands(&mut chart, series_attribute);
+            }
+        }
+
+        for series_attribute in data_point_collection {
+            if is_state_series(&series_attribute.name) {
+                continue;
+            }
             println!("drawing: {:?}", series_attribute.name);
             // FIXME --> memory footprint of cloning?
             let as_bundle: Vec<(f64, DataValue)> = series_attribute
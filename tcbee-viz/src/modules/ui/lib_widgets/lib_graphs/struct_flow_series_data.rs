@@ -11,6 +11,7 @@ use ::iced::{
     Color, Length,
 };
 use plotters_iced::ChartWidget;
+use tcbee_plugins::SeriesData;
 
 #[derive(Debug)]
 pub struct FlowSeriesData {
@@ -61,6 +62,48 @@ impl FlowSeriesData {
     pub fn update_chart_height(&mut self, new_height: f32) {
         self.chart_height = new_height;
     }
+
+    /// strips plot information to hand the series to a processor
+    pub fn to_series_data(&self) -> SeriesData {
+        SeriesData {
+            name: self.name.clone(),
+            timestamps: self.timestamps.clone(),
+            data: self.data.clone(),
+            data_val_type: self.data_val_type.clone(),
+        }
+    }
+
+    /// wraps a processor result, plot settings are taken from template
+    pub fn from_series_data(series: SeriesData, template: &FlowSeriesData) -> FlowSeriesData {
+        let (min_val, max_val) = match series.value_bounds() {
+            Some((min, max)) => (Some(min), Some(max)),
+            None => (None, None),
+        };
+        FlowSeriesData {
+            min_timestamp: series
+                .timestamps
+                .iter()
+                .cloned()
+                .reduce(f64::min)
+                .unwrap_or(template.min_timestamp),
+            max_timestamp: series
+                .timestamps
+                .iter()
+                .cloned()
+                .reduce(f64::max)
+                .unwrap_or(template.max_timestamp),
+            name: series.name,
+            timestamps: series.timestamps,
+            min_val,
+            max_val,
+            data_val_type: series.data_val_type,
+            data: series.data,
+            zoom_bounds: template.zoom_bounds.clone(),
+            chart_height: template.chart_height,
+            line_color: template.line_color,
+            cache: Cache::new(),
+        }
+    }
 }
 
 impl FlowSeriesData {
@@ -8,7 +8,7 @@ use duckdb::arrow::array::{Datum, UnionArray};
 use duckdb::types::ValueRef;
 use duckdb::{types::Value, Connection, Row, Rows, Statement, ToSql};
 
-use crate::{DataPoint, DataValue, Flow, FlowAttribute, IpTuple, TimeSeries};
+use crate::{DataPoint, DataValue, Flow, FlowAttribute, ImportState, IpTuple, TimeSeries};
 
 fn parse_value(row: &Row) -> Option<DataValue> {
     // Value is of type "Union(Text("{'inum': 10}"))"
@@ -88,7 +88,10 @@ impl DuckDBCursorStruct for Flow {
             return None;
         };
 
-        IpTuple::from_row(row).map(|tuple| Flow::new_with_id(id, tuple))
+        // Databases created before the cookie was introduced do not contain the column
+        let sock_cookie = row.get::<&str, i64>("sock_cookie").unwrap_or(0);
+
+        IpTuple::from_row(row).map(|tuple| Flow::new_with_cookie(id, tuple, sock_cookie))
     }
 }
 
@@ -159,6 +162,26 @@ impl DuckDBCursorStruct for FlowAttribute {
     }
 }
 
+impl DuckDBCursorStruct for ImportState {
+    fn from_row(row: &Row) -> Option<Self> {
+        let Ok(path) = row.get::<&str, String>("path") else {
+            return None;
+        };
+        let Ok(file_id) = row.get::<&str, i64>("file_id") else {
+            return None;
+        };
+        let Ok(offset) = row.get::<&str, i64>("read_offset") else {
+            return None;
+        };
+
+        Some(ImportState {
+            path,
+            file_id,
+            offset,
+        })
+    }
+}
+
 impl DuckDBCursorStruct for IpTuple {
     fn from_row(row: &Row) -> Option<Self> {
         // TODO: can this be cleaner?
@@ -3,12 +3,12 @@ use duckdb::{params, Appender, Connection, ToSql};
 
 use crate::duckdb::cursor::{DuckDBCursor, DuckDBCursorStruct};
 use crate::duckdb::queries::{
-    COUNT_TIME_SERIES_DATA, CREATE_FLOW_ATTRIBUTE_ID_SEQ, CREATE_FLOW_ATTRIBUTE_TABLE, CREATE_FLOW_ID_SEQ, CREATE_FLOW_TABLE, CREATE_TIME_SERIES_DATA_TABLE, CREATE_TIME_SERIES_TABLE, CREATE_TS_ID_SEQ, DELETE_FLOW_ATTRIBUTE_BY_NAME, DELETE_FLOW_BY_TUPLE, DELETE_TIME_SERIES_BY_NAME, INSERT_FLOW, INSERT_FLOW_ATTRIBUTE, INSERT_TIME_SERIES, INSERT_TIME_SERIES_DATA, SELECT_ALL_FLOWS, SELECT_FIRST_TIME_SERIES_DATA, SELECT_FLOW_ATTRIBUTES_BY_FLOW_ID, SELECT_FLOW_ATTRIBUTE_BY_ID, SELECT_FLOW_ATTRIBUTE_BY_NAME, SELECT_FLOW_BY_ID, SELECT_FLOW_BY_TUPLE, SELECT_HIGHEST_TIME_SERIES_DATA, SELECT_LAST_TIME_SERIES_DATA, SELECT_LOWEST_TIME_SERIES_DATA, SELECT_TIME_SERIES_BY_FLOW, SELECT_TIME_SERIES_BY_FLOW_AND_NAME, SELECT_TIME_SERIES_BY_ID, SELECT_TIME_SERIES_DATA_BY_SERIES, TIME_SERIES_DATA_TABLE
+    COUNT_FLOW_SOCK_COOKIE_COLUMN, COUNT_TIME_SERIES_DATA, CREATE_FLOW_ATTRIBUTE_ID_SEQ, CREATE_FLOW_ATTRIBUTE_TABLE, CREATE_FLOW_ID_SEQ, CREATE_FLOW_TABLE, CREATE_IMPORT_STATE_TABLE, CREATE_TIME_SERIES_DATA_TABLE, CREATE_TIME_SERIES_TABLE, CREATE_TS_ID_SEQ, DELETE_FLOW_ATTRIBUTE_BY_NAME, DELETE_FLOW_BY_TUPLE, DELETE_TIME_SERIES_BY_NAME, INSERT_FLOW, INSERT_FLOW_ATTRIBUTE, INSERT_IMPORT_STATE, INSERT_TIME_SERIES, INSERT_TIME_SERIES_DATA, SELECT_ALL_FLOWS, SELECT_FIRST_TIME_SERIES_DATA, SELECT_FLOW_ATTRIBUTES_BY_FLOW_ID, SELECT_FLOW_ATTRIBUTE_BY_ID, SELECT_FLOW_ATTRIBUTE_BY_NAME, SELECT_FLOW_BY_ID, SELECT_FLOW_BY_TUPLE, SELECT_FLOW_BY_TUPLE_AND_COOKIE, SELECT_HIGHEST_TIME_SERIES_DATA, SELECT_IMPORT_STATE_BY_PATH, SELECT_LAST_TIME_SERIES_DATA, SELECT_LOWEST_TIME_SERIES_DATA, SELECT_TIME_SERIES_BY_FLOW, SELECT_TIME_SERIES_BY_FLOW_AND_NAME, SELECT_TIME_SERIES_BY_ID, SELECT_TIME_SERIES_DATA_BY_SERIES, TIME_SERIES_DATA_TABLE
 };
 use crate::duckdb::DuckDBTSDB;
 use crate::error::TSDBError;
 use crate::{
-    DataPoint, DataValue, Flow, FlowAttribute, IpTuple, TSBounds, TSDBInterface, TimeSeries,
+    DataPoint, DataValue, Flow, FlowAttribute, ImportState, IpTuple, TSBounds, TSDBInterface, TimeSeries,
 };
 
 use std::error::Error;
@@ -66,10 +66,21 @@ impl DuckDBTSDB {
 
         // Main Tables
         self.conn.execute(CREATE_FLOW_TABLE, params![])?;
+        // DuckDB cannot change constraints of a table that other tables reference
+        let columns: i64 = self
+            .conn
+            .query_row(COUNT_FLOW_SOCK_COOKIE_COLUMN, [], |row| row.get(0))?;
+        if columns == 0 {
+            return Err(Box::new(TSDBError::OutdatedSchemaError {
+                table: "flows".to_string(),
+                column: "sock_cookie".to_string(),
+            }));
+        }
         self.conn.execute(CREATE_FLOW_ATTRIBUTE_TABLE, params![])?;
         self.conn.execute(CREATE_TIME_SERIES_TABLE, params![])?;
         self.conn
             .execute(CREATE_TIME_SERIES_DATA_TABLE, params![])?;
+        self.conn.execute(CREATE_IMPORT_STATE_TABLE, params![])?;
 
         Ok(())
     }
@@ -147,25 +158,36 @@ impl TSDBInterface for DuckDBTSDB {
     // Flow interaction
     // Will overwrite Flow ID field!
     fn create_flow(&self, tuple: &IpTuple) -> Result<Flow, Box<dyn Error>> {
+        self.create_flow_with_cookie(tuple, 0)
+    }
+
+    fn create_flow_with_cookie(&self, tuple: &IpTuple, sock_cookie: i64) -> Result<Flow, Box<dyn Error>> {
         // Ensure that database is ready to add this flow
         self.check_setup()?;
 
-        // First: create flow entry
-        let mut query = self
-            .conn
-            .prepare(INSERT_FLOW)?;
         let params = params![
             tuple.src.to_string(),
             tuple.dst.to_string(),
             tuple.sport,
             tuple.dport,
-            tuple.l4proto
+            tuple.l4proto,
+            sock_cookie
         ];
-        query.execute(params);
+
+        // Flow may exist from a previous import, continue it
+        if let Some(entry) = get_entry::<Flow>(params, SELECT_FLOW_BY_TUPLE_AND_COOKIE, &self.conn)? {
+            return Ok(entry);
+        }
+
+        // First: create flow entry
+        let mut query = self
+            .conn
+            .prepare(INSERT_FLOW)?;
+        query.execute(params)?;
 
         // Second: query flow entry to get ID field
 
-        match get_entry::<Flow>(params, SELECT_FLOW_BY_TUPLE, &self.conn)? {
+        match get_entry::<Flow>(params, SELECT_FLOW_BY_TUPLE_AND_COOKIE, &self.conn)? {
             Some(entry) => Ok(entry),
             None => Err(Box::new(TSDBError::ReadFlowIDError))
         }
@@ -182,7 +204,8 @@ impl TSDBInterface for DuckDBTSDB {
             tuple.dst.to_string(),
             tuple.sport,
             tuple.dport,
-            tuple.l4proto
+            tuple.l4proto,
+            flow.sock_cookie.unwrap_or(0)
         ];
         // Map usize return to bool value
         match query.execute(params) {
@@ -503,6 +526,51 @@ impl TSDBInterface for DuckDBTSDB {
         Ok(bounds)
     }
 
+    fn begin_transaction(&self) -> Result<(), Box<dyn Error>> {
+        self.check_setup()?;
+        self.conn.execute_batch("BEGIN TRANSACTION;")?;
+        Ok(())
+    }
+
+    fn commit_transaction(&self) -> Result<(), Box<dyn Error>> {
+        self.check_setup()?;
+        self.conn.execute_batch("COMMIT;")?;
+        Ok(())
+    }
+
+    fn rollback_transaction(&self) -> Result<(), Box<dyn Error>> {
+        self.check_setup()?;
+        self.conn.execute_batch("ROLLBACK;")?;
+        Ok(())
+    }
+
+    fn new_connection(&self) -> Result<Box<dyn TSDBInterface + Send>, Box<dyn Error>> {
+        self.check_setup()?;
+        // Connections of the same database instance, DuckDB only allows one instance per file
+        Ok(Box::new(DuckDBTSDB {
+            path: self.path.clone(),
+            conn: self.conn.try_clone()?,
+            is_setup: true,
+        }))
+    }
+
+    fn get_import_state(&self, path: &str) -> Result<Option<ImportState>, Box<dyn Error>> {
+        self.check_setup()?;
+
+        get_entry(params![path], SELECT_IMPORT_STATE_BY_PATH, &self.conn)
+    }
+
+    fn set_import_state(&self, state: &ImportState) -> Result<bool, Box<dyn Error>> {
+        self.check_setup()?;
+
+        let mut query = self
+            .conn
+            .prepare(INSERT_IMPORT_STATE)?;
+        query.execute(params![state.path, state.file_id, state.offset])?;
+
+        Ok(true)
+    }
+
     fn get_data_points_count(&self, series: &TimeSeries) -> Result<i64, Box<dyn Error>> {
         self.check_setup()?;
         let id = self.check_ts(series)?;
@@ -14,8 +14,11 @@ pub const CREATE_FLOW_TABLE: &str = "CREATE TABLE IF NOT EXISTS flows (
             sport INTEGER NOT NULL,
             dport INTEGER NOT NULL,
             l4proto INTEGER NOT NULL,
-            UNIQUE (src, dst, sport, dport, l4proto)
+            sock_cookie BIGINT NOT NULL DEFAULT 0,
+            UNIQUE (src, dst, sport, dport, l4proto, sock_cookie)
         );";
+// Databases created before socket cookies have no sock_cookie column
+pub const COUNT_FLOW_SOCK_COOKIE_COLUMN: &str = "SELECT COUNT(*) FROM information_schema.columns WHERE table_name = 'flows' AND column_name = 'sock_cookie';";
 pub const CREATE_FLOW_ATTRIBUTE_TABLE: &str = "CREATE TABLE IF NOT EXISTS flow_attributes (
             id INTEGER PRIMARY KEY DEFAULT nextval('flow_attribute_id_seq'),
             flow_id INTEGER,
@@ -46,14 +49,22 @@ pub const CREATE_TIME_SERIES_DATA_TABLE: &str = "CREATE TABLE IF NOT EXISTS time
             PRIMARY KEY (time_series_id, timestamp),
             FOREIGN KEY (time_series_id) REFERENCES time_series(time_series_id)
         );";
+// Read progress of imported input files, used to continue imports
+pub const CREATE_IMPORT_STATE_TABLE: &str = "CREATE TABLE IF NOT EXISTS import_state (
+            path TEXT PRIMARY KEY,
+            file_id BIGINT NOT NULL,
+            read_offset BIGINT NOT NULL
+        );";
 
 // -------- SELECT queries
 pub const SELECT_FLOW_BY_TUPLE: &str = "SELECT * FROM flows WHERE src = ? AND dst = ? AND sport = ? AND dport = ? AND l4proto = ?;";
+pub const SELECT_FLOW_BY_TUPLE_AND_COOKIE: &str = "SELECT * FROM flows WHERE src = ? AND dst = ? AND sport = ? AND dport = ? AND l4proto = ? AND sock_cookie = ?;";
 pub const SELECT_FLOW_BY_ID: &str = "SELECT * FROM flows WHERE id = ?;";
 pub const SELECT_FLOW_ATTRIBUTE_BY_ID: &str = "SELECT * FROM flow_attributes WHERE id = ?;";
 pub const SELECT_FLOW_ATTRIBUTE_BY_NAME: &str = "SELECT * FROM flow_attributes WHERE flow_id = ? AND name = ?;";
 pub const SELECT_FLOW_ATTRIBUTES_BY_FLOW_ID: &str = "SELECT * FROM flow_attributes WHERE flow_id = ?";
 pub const SELECT_TIME_SERIES_BY_ID: &str = "SELECT * FROM flow_attributes WHERE id = ?;";
+pub const SELECT_IMPORT_STATE_BY_PATH: &str = "SELECT * FROM import_state WHERE path = ?;";
 pub const SELECT_ALL_FLOWS: &str = "SELECT * FROM flows;";
 pub const SELECT_TIME_SERIES_BY_FLOW_AND_NAME: &str = "SELECT * FROM time_series WHERE flow_id = ? AND name = ? AND type = ?;";
 pub const SELECT_TIME_SERIES_BY_FLOW: &str = "SELECT * FROM time_series WHERE flow_id = ?;";
@@ -67,13 +78,14 @@ pub const SELECT_HIGHEST_TIME_SERIES_DATA: &str =  "SELECT * FROM time_series_da
 pub const COUNT_TIME_SERIES_DATA: &str = "SELECT COUNT(*) FROM time_series_data WHERE time_series_id = ?;";
 
 // -------- INSERT queries
-pub const INSERT_FLOW: &str = "INSERT INTO flows (src, dst, sport, dport, l4proto) VALUES(?,?,?,?,?);";
+pub const INSERT_FLOW: &str = "INSERT INTO flows (src, dst, sport, dport, l4proto, sock_cookie) VALUES(?,?,?,?,?,?);";
 pub const INSERT_FLOW_ATTRIBUTE: &str = "INSERT INTO flow_attributes (flow_id, name, value, type) VALUES (?, ?, ?, ?);";
 pub const INSERT_TIME_SERIES: &str = "INSERT INTO time_series (flow_id, name, type) VALUES (?, ?, ?);";
+pub const INSERT_IMPORT_STATE: &str = "INSERT OR REPLACE INTO import_state (path, file_id, read_offset) VALUES (?, ?, ?);";
 pub const INSERT_TIME_SERIES_DATA: &str = "INSERT INTO time_series_data (time_series_id, timestamp, value, type) VALUES (?, ?, ?, ?);";
 
 // -------- DELETE queries
-pub const DELETE_FLOW_BY_TUPLE: &str = "DELETE FROM flows WHERE src = ? AND dst = ? AND sport = ? AND dport = ? AND l4proto = ?;";
+pub const DELETE_FLOW_BY_TUPLE: &str = "DELETE FROM flows WHERE src = ? AND dst = ? AND sport = ? AND dport = ? AND l4proto = ? AND sock_cookie = ?;";
 pub const DELETE_FLOW_ATTRIBUTE_BY_NAME: &str = "DELETE FROM flow_attributes WHERE flow_id = ? AND name = ?;";
 pub const DELETE_TIME_SERIES_BY_NAME: &str =  "DELETE FROM time_series WHERE flow_id = ? AND name = ?;";
 
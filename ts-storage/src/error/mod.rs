@@ -44,4 +44,9 @@ pub enum TSDBError {
     TimeSeriesNotSetup,
     #[error("The queried TimeSeries does not have any values!")]
     TimeSeriesNoValue,
+    #[error("Table {table} has no column {column}, the database was created by an older version and cannot be updated. Use a new database!")]
+    OutdatedSchemaError {
+        table: String,
+        column: String
+    },
 }
@@ -33,6 +33,8 @@ pub struct TSBounds {
 pub struct Flow {
     pub id: Option<i64>,
     pub tuple: IpTuple,
+    // Kernel socket cookie, distinguishes connections that reuse the same tuple
+    pub sock_cookie: Option<i64>,
 }
 impl Flow {
     pub fn get_id(&self) -> Option<i64> {
@@ -42,12 +44,22 @@ impl Flow {
         return Flow {
             id: None,
             tuple: tuple,
+            sock_cookie: None,
         };
     }
     pub fn new_with_id(id: i64, tuple: IpTuple) -> Flow {
         return Flow {
             id: Some(id),
             tuple: tuple,
+            sock_cookie: None,
+        };
+    }
+    // A cookie of 0 is stored for flows without a known socket
+    pub fn new_with_cookie(id: i64, tuple: IpTuple, sock_cookie: i64) -> Flow {
+        return Flow {
+            id: Some(id),
+            tuple: tuple,
+            sock_cookie: if sock_cookie == 0 { None } else { Some(sock_cookie) },
         };
     }
 }
@@ -58,6 +70,15 @@ pub struct FlowAttribute {
     pub value: DataValue,
 }
 
+// Progress of importing an input file, allows to continue an import later
+// file_id identifies the recording, offset is the number of bytes already imported
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportState {
+    pub path: String,
+    pub file_id: i64,
+    pub offset: i64,
+}
+
 // Structs that represent time series data
 // Todo: possible vectors?
 #[derive(Debug, Clone)]
@@ -225,6 +246,8 @@ impl ToString for Condition {
 pub trait TSDBInterface {
     // --- FLOW CREATION AND MANAGEMENT
     fn create_flow(&self, tuple: &IpTuple) -> Result<Flow, Box<dyn Error>>;
+    // Creates a flow for a single socket, allows multiple flows with the same tuple
+    fn create_flow_with_cookie(&self, tuple: &IpTuple, sock_cookie: i64) -> Result<Flow, Box<dyn Error>>;
     fn delete_flow(&self, flow: &Flow) -> Result<bool, Box<dyn Error>>;
     fn list_flows(&self) -> Result<Box<dyn Iterator<Item = Flow> + '_>, Box<dyn Error>>;
     fn get_flow(&self, tuple: &IpTuple) -> Result<Option<Flow>, Box<dyn Error>>;
@@ -283,6 +306,21 @@ pub trait TSDBInterface {
     ) -> Result<bool, Box<dyn Error>>;
     fn get_data_points_count(&self, series: &TimeSeries) -> Result<i64, Box<dyn Error>>;
 
+    // --- TRANSACTIONS AND CONNECTIONS
+    // Writes between begin and commit are stored together, speeds up bulk imports
+    fn begin_transaction(&self) -> Result<(), Box<dyn Error>>;
+    fn commit_transaction(&self) -> Result<(), Box<dyn Error>>;
+    // Reverts all writes since begin
+    fn rollback_transaction(&self) -> Result<(), Box<dyn Error>>;
+    // Opens another connection to the same database, e.g. for writers in other threads
+    fn new_connection(&self) -> Result<Box<dyn TSDBInterface + Send>, Box<dyn Error>>;
+
+    // --- IMPORT STATE
+    // Returns None if the file was never imported into this database
+    fn get_import_state(&self, path: &str) -> Result<Option<ImportState>, Box<dyn Error>>;
+    // Creates or overwrites the state of the file
+    fn set_import_state(&self, state: &ImportState) -> Result<bool, Box<dyn Error>>;
+
     /*
     fn delete_data_points(&self, flow: &Flow, name: String, conditions: Vec<Condition>) -> bool;
     // Allow direct query execution for special cases
@@ -4,7 +4,7 @@ use std::str::FromStr;
 
 
 use sqlite::{self, State, Statement, Value};
-use crate::{Flow, IpTuple, DataPoint, TimeSeries, DataValue, FlowAttribute};
+use crate::{Flow, IpTuple, DataPoint, TimeSeries, DataValue, FlowAttribute, ImportState};
 
 
 pub trait SQLiteCursorStruct: Sized {
@@ -27,8 +27,11 @@ impl SQLiteCursorStruct for Flow {
             return None;
         }
 
+        // Databases created before the cookie was introduced do not contain the column
+        let sock_cookie = stmt.read::<i64,_>("sock_cookie").unwrap_or(0);
+
         // Return parsed flow object
-        return Some(Flow::new_with_id(id.unwrap(),tuple.unwrap()));
+        return Some(Flow::new_with_cookie(id.unwrap(),tuple.unwrap(),sock_cookie));
 
     }
 }
@@ -143,6 +146,16 @@ impl SQLiteCursorStruct for FlowAttribute {
     }
 }
 
+impl SQLiteCursorStruct for ImportState {
+    fn from_statement(stmt: &Statement) -> Option<Self> {
+        let path = stmt.read::<String,_>("path").ok()?;
+        let file_id = stmt.read::<i64,_>("file_id").ok()?;
+        let offset = stmt.read::<i64,_>("read_offset").ok()?;
+
+        Some(ImportState { path, file_id, offset })
+    }
+}
+
 impl SQLiteCursorStruct for IpTuple {
 
     fn from_statement(stmt: &Statement) -> Option<Self> {
@@ -1,4 +1,4 @@
-use crate::{Flow,DataPoint, DataValue, FlowAttribute, IpTuple, TSDBInterface, TimeSeries, TSBounds};
+use crate::{Flow,DataPoint, DataValue, FlowAttribute, ImportState, IpTuple, TSDBInterface, TimeSeries, TSBounds};
 use crate::sqlite::cursor::SQLiteCursor;
 use crate::sqlite::SQLiteTSDB;
 use crate::error::TSDBError;
@@ -7,11 +7,31 @@ use std::error::Error;
 use std::f64;
 use sqlite::{State, Value};
 
+// Time to wait for locks held by other connections, e.g. parallel writers
+const BUSY_TIMEOUT_MS: usize = 60000;
+
+// Flows are identified by tuple and socket cookie
+fn flows_table_query(table: &str) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {table} (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            src TEXT NOT NULL,
+            dst TEXT NOT NULL,
+            sport INTEGER NOT NULL,
+            dport INTEGER NOT NULL,
+            l4proto INTEGER NOT NULL,
+            sock_cookie INTEGER NOT NULL DEFAULT 0,
+            UNIQUE (src, dst, sport, dport, l4proto, sock_cookie)
+        )"
+    )
+}
+
 impl SQLiteTSDB {
     // Creates SQLite connection to file under given path
     // Passes error from rusqlite connection if one occurs
     pub fn new(path: String) -> Result<Self, Box<dyn Error>> {
-        let conn = sqlite::open(&path)?;
+        let mut conn = sqlite::open(&path)?;
+        conn.set_busy_timeout(BUSY_TIMEOUT_MS)?;
         let mut db = SQLiteTSDB {
             path,
             conn,
@@ -36,18 +56,9 @@ impl SQLiteTSDB {
 
         // Create flows table
         // TODO: add manual ids to have entries for same ID?
-        let flows_query = "CREATE TABLE IF NOT EXISTS flows (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            src TEXT NOT NULL,
-            dst TEXT NOT NULL,
-            sport INTEGER NOT NULL,
-            dport INTEGER NOT NULL,
-            l4proto INTEGER NOT NULL,
-            UNIQUE (src, dst, sport, dport, l4proto)
-        )";
-
         //let flows_query = "CREATE TABLE IF NOT EXISTS flows(id INTEGER PRIMARY KEY ASC AUTO_INCREMENT, src TEXT NOT NULL, dst TEXT NOT NULL, sport INTEGER NOT NULL, dport INTEGER NOT NULL, l4proto INTEGER NOT NULL);";
-        self.conn.execute(flows_query)?;
+        self.conn.execute(flows_table_query("flows"))?;
+        self.migrate_sock_cookie()?;
 
         // Create flow attributes table
         // TODO: add typecolumn that determines which value column is parsed!
@@ -96,8 +107,52 @@ impl SQLiteTSDB {
 
         self.conn.execute(time_series_data_query)?;
 
+        // Read progress of imported input files, used to continue imports
+        let import_state_query = "
+            CREATE TABLE IF NOT EXISTS import_state (
+                path TEXT PRIMARY KEY,
+                file_id INTEGER NOT NULL,
+                read_offset INTEGER NOT NULL
+            );";
+
+        self.conn.execute(import_state_query)?;
+
         Ok(())
     }
+    // Databases created before socket cookies have flows without sock_cookie
+    // SQLite cannot change a UNIQUE constraint, the table is rebuilt with the same ids
+    fn migrate_sock_cookie(&self) -> Result<(), Box<dyn Error>> {
+        let mut query = self
+            .conn
+            .prepare("SELECT COUNT(*) AS found FROM pragma_table_info('flows') WHERE name = 'sock_cookie';")?;
+        query.next()?;
+        if query.read::<i64, _>("found")? > 0 {
+            return Ok(());
+        }
+        // An active statement would lock the table
+        drop(query);
+
+        // Foreign keys can only be switched outside of a transaction
+        self.conn.execute("PRAGMA foreign_keys=OFF;")?;
+        let migration = format!(
+            "BEGIN IMMEDIATE;
+            {};
+            INSERT INTO flows_migrated (id, src, dst, sport, dport, l4proto)
+                SELECT id, src, dst, sport, dport, l4proto FROM flows;
+            DROP TABLE flows;
+            ALTER TABLE flows_migrated RENAME TO flows;
+            COMMIT;",
+            flows_table_query("flows_migrated")
+        );
+        let result = self.conn.execute(migration);
+        if result.is_err() {
+            let _ = self.conn.execute("ROLLBACK;");
+        }
+        self.conn.execute("PRAGMA foreign_keys=ON;")?;
+        result?;
+        Ok(())
+    }
+
     // Check if DB is setup correctly to perform an operation
     // TODO: move to a wrapper?
     fn check_setup(&self) -> Result<(), TSDBError> {
@@ -212,24 +267,39 @@ impl TSDBInterface for SQLiteTSDB {
     // Flow interaction
     // Will overwrite Flow ID field!
     fn create_flow(&self, tuple: &IpTuple) -> Result<Flow, Box<dyn Error>> {
+        self.create_flow_with_cookie(tuple, 0)
+    }
+
+    fn create_flow_with_cookie(&self, tuple: &IpTuple, sock_cookie: i64) -> Result<Flow, Box<dyn Error>> {
         // Ensure that database is ready to add this flow
         self.check_setup()?;
 
-        // First: create flow entry
-        let mut query = self.conn.prepare("INSERT INTO flows (src, dst, sport, dport, l4proto) VALUES(:src,:dst,:sport,:dport,:l4proto);")?;
         let params: &[(_, Value)] = &[
             (":src", tuple.src.to_string().into()),
             (":dst", tuple.dst.to_string().into()),
             (":sport", tuple.sport.into()),
             (":dport", tuple.dport.into()),
             (":l4proto", tuple.l4proto.into()),
+            (":sock_cookie", sock_cookie.into()),
         ][..];
 
+        let select_str = "SELECT * FROM flows WHERE src = :src AND dst = :dst AND sport = :sport AND dport = :dport AND l4proto = :l4proto AND sock_cookie = :sock_cookie;";
+
+        // Flow may exist from a previous import, continue it
+        let mut get_query = self.conn.prepare(select_str)?;
+        get_query.bind::<&[(_, Value)]>(params)?;
+        if let Some(entry) = SQLiteCursor::<Flow>::new(get_query).next() {
+            return Ok(entry);
+        }
+
+        // First: create flow entry
+        let mut query = self.conn.prepare("INSERT INTO flows (src, dst, sport, dport, l4proto, sock_cookie) VALUES(:src,:dst,:sport,:dport,:l4proto,:sock_cookie);")?;
+
         query.bind::<&[(_, Value)]>(params)?;
         let _ = query.next()?;
 
         // Second: query flow entry to get ID field
-        let mut get_query = self.conn.prepare("SELECT * FROM flows WHERE src = :src AND dst = :dst AND sport = :sport AND dport = :dport AND l4proto = :l4proto;")?;
+        let mut get_query = self.conn.prepare(select_str)?;
 
         get_query.bind::<&[(_, Value)]>(params)?;
 
@@ -241,7 +311,6 @@ impl TSDBInterface for SQLiteTSDB {
             return Err(Box::new(TSDBError::ReadFlowIDError));
         }
 
-        // TODO: Add better handling if did not create a new flow
         // TODO: handling if entry.id is NONE?
         Ok(entry.unwrap())
     }
@@ -250,7 +319,7 @@ impl TSDBInterface for SQLiteTSDB {
         // Ensure that database is ready to remove this flow
         self.check_setup()?;
         //TODO: create query builder class
-        let mut query = self.conn.prepare("DELETE FROM flows WHERE src = :src AND dst = :dst AND sport = :sport AND dport = :dport AND l4proto = :l4proto;")?;
+        let mut query = self.conn.prepare("DELETE FROM flows WHERE src = :src AND dst = :dst AND sport = :sport AND dport = :dport AND l4proto = :l4proto AND sock_cookie = :sock_cookie;")?;
         let tuple = &flow.tuple;
 
         query.bind::<&[(_, Value)]>(
@@ -260,6 +329,7 @@ impl TSDBInterface for SQLiteTSDB {
                 (":sport", tuple.sport.into()),
                 (":dport", tuple.dport.into()),
                 (":l4proto", tuple.l4proto.into()),
+                (":sock_cookie", flow.sock_cookie.unwrap_or(0).into()),
             ][..],
         )?;
 
@@ -551,38 +621,37 @@ impl TSDBInterface for SQLiteTSDB {
         points: &Vec<DataPoint>,
     ) -> Result<bool, Box<dyn Error>> {
         self.check_setup()?;
+        let id = self.check_ts(series)?;
 
         let col = series.ts_type.column_name()?;
 
-        let mut query_params = format!("INSERT INTO time_series_data (time_series_id, timestamp, {col}) VALUES");
-
-        let mut it = points.iter().peekable();
-
-        while let Some(entry) = it.next() {
-            query_params.push_str(" ( ");
-            query_params.push_str(&series.id.unwrap().to_string());
-            query_params.push_str(" , ");
-            query_params.push_str(&entry.timestamp.to_string());
-            query_params.push_str(" , ");
-            query_params.push_str(&entry.value.as_string());
-            query_params.push_str(" ) ");
-
-            // Add comma if there is a next entry
-            if it.peek().is_some() {
-                query_params.push_str(",");
+        // Prepare once and rebind for every point
+        let mut query = self.conn.prepare(format!(
+            "INSERT INTO time_series_data (time_series_id, timestamp, {col}) VALUES (?, ?, ?);"
+        ))?;
+
+        // Savepoint keeps the batch atomic, also when called inside of a transaction
+        self.conn.execute("SAVEPOINT insert_points;")?;
+
+        for point in points {
+            let res = query
+                .reset()
+                .and_then(|_| {
+                    query.bind::<&[Value]>(
+                        &[id.into(), point.timestamp.into(), point.value.clone().into()][..],
+                    )
+                })
+                .and_then(|_| query.next());
+
+            if let Err(error) = res {
+                // Revert all points of this batch
+                self.conn.execute("ROLLBACK TO insert_points; RELEASE insert_points;")?;
+                // TODO: custom error
+                return Err(Box::new(error));
             }
-
-
         }
 
-        query_params.push_str(";");
-
-        // Execute query
-        let insert_res = self.conn.execute(query_params);
-        if let Err(error) = insert_res {
-            // TODO: custom error
-            return Err(Box::new(error))
-        }
+        self.conn.execute("RELEASE insert_points;")?;
         // Insert succeeded
         Ok(true)
     }
@@ -598,7 +667,10 @@ impl TSDBInterface for SQLiteTSDB {
             .prepare("SELECT * FROM time_series_data WHERE time_series_id = :time_series_id ORDER by timestamp ASC LIMIT 1")?;
         xmin_query.bind::<&[(_, Value)]>(&[(":time_series_id", id.into())][..])?;
         let mut xmin_cursor = Box::new(SQLiteCursor::<DataPoint>::new(xmin_query));
-        let xmin = xmin_cursor.next().unwrap().timestamp;
+        let Some(xmin_point) = xmin_cursor.next() else {
+            return Err(Box::new(TSDBError::TimeSeriesNoValue));
+        };
+        let xmin = xmin_point.timestamp;
 
         // XMAX
         let mut  xmax_query = self
@@ -606,7 +678,10 @@ impl TSDBInterface for SQLiteTSDB {
             .prepare("SELECT * FROM time_series_data WHERE time_series_id = :time_series_id ORDER by timestamp DESC LIMIT 1")?;
         xmax_query.bind::<&[(_, Value)]>(&[(":time_series_id", id.into())][..])?;
         let mut xmax_cursor = Box::new(SQLiteCursor::<DataPoint>::new( xmax_query));
-        let xmax = xmax_cursor.next().unwrap().timestamp;
+        let Some(xmax_point) = xmax_cursor.next() else {
+            return Err(Box::new(TSDBError::TimeSeriesNoValue));
+        };
+        let xmax = xmax_point.timestamp;
 
         let mut bounds = TSBounds {xmin, xmax, ymin: None, ymax: None};
 
@@ -651,6 +726,57 @@ impl TSDBInterface for SQLiteTSDB {
         Ok(bounds)
     }
 
+    fn begin_transaction(&self) -> Result<(), Box<dyn Error>> {
+        self.check_setup()?;
+        // Take the write lock now, other writers wait on the busy timeout
+        self.conn.execute("BEGIN IMMEDIATE;")?;
+        Ok(())
+    }
+
+    fn commit_transaction(&self) -> Result<(), Box<dyn Error>> {
+        self.check_setup()?;
+        self.conn.execute("COMMIT;")?;
+        Ok(())
+    }
+
+    fn rollback_transaction(&self) -> Result<(), Box<dyn Error>> {
+        self.check_setup()?;
+        self.conn.execute("ROLLBACK;")?;
+        Ok(())
+    }
+
+    fn new_connection(&self) -> Result<Box<dyn TSDBInterface + Send>, Box<dyn Error>> {
+        Ok(Box::new(SQLiteTSDB::new(self.path.clone())?))
+    }
+
+    fn get_import_state(&self, path: &str) -> Result<Option<ImportState>, Box<dyn Error>> {
+        self.check_setup()?;
+
+        let mut query = self
+            .conn
+            .prepare("SELECT * FROM import_state WHERE path = :path;")?;
+        query.bind::<&[(_, Value)]>(&[(":path", path.into())][..])?;
+
+        let mut cursor = Box::new(SQLiteCursor::<ImportState>::new(query));
+        Ok(cursor.next())
+    }
+
+    fn set_import_state(&self, state: &ImportState) -> Result<bool, Box<dyn Error>> {
+        self.check_setup()?;
+
+        let mut query = self
+            .conn
+            .prepare("INSERT OR REPLACE INTO import_state (path, file_id, read_offset) VALUES (:path, :file_id, :read_offset);")?;
+        query.bind::<&[(_, Value)]>(&[
+            (":path", state.path.clone().into()),
+            (":file_id", state.file_id.into()),
+            (":read_offset", state.offset.into()),
+        ][..])?;
+        query.next()?;
+
+        Ok(true)
+    }
+
     fn get_data_points_count(&self, series: &TimeSeries) -> Result<i64, Box<dyn Error>> {
         self.check_setup()?;
         let id = self.check_ts(series)?;
@@ -0,0 +1,43 @@
+// Helpers shared by the database tests
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use ts_storage::IpTuple;
+
+// Database file in the temp directory, removed when the test ends, also if it fails
+pub struct TempDb {
+    pub path: String,
+}
+
+impl TempDb {
+    pub fn new(name: &str) -> TempDb {
+        let path = std::env::temp_dir().join(format!("tcbee-{}-{}", name, std::process::id()));
+        let db = TempDb {
+            path: path.to_string_lossy().to_string(),
+        };
+        db.remove();
+        db
+    }
+
+    fn remove(&self) {
+        // DuckDB keeps a write ahead log next to the database
+        let _ = std::fs::remove_file(&self.path);
+        let _ = std::fs::remove_file(format!("{}.wal", self.path));
+    }
+}
+
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+pub fn tuple() -> IpTuple {
+    IpTuple {
+        src: IpAddr::from_str("10.0.0.1").unwrap(),
+        dst: IpAddr::from_str("10.0.0.2").unwrap(),
+        sport: 100,
+        dport: 200,
+        l4proto: 6,
+    }
+}
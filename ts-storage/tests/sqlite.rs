@@ -1,6 +1,10 @@
+mod common;
+
+use common::{tuple, TempDb};
 use ts_storage::sqlite::SQLiteTSDB;
 use ts_storage::{
-    database_factory, DBBackend, DataPoint, DataValue, FlowAttribute, IpTuple, TSDBInterface,
+    database_factory, DBBackend, DataPoint, DataValue, FlowAttribute, ImportState, IpTuple,
+    TSDBInterface,
 };
 use std::error::Error;
 use std::net::IpAddr;
@@ -194,3 +198,171 @@ fn all_func() {
     //    .expect("Could not delete TS!");
     //let _ = db.delete_flow(&flow3).expect("Failed to delete flow!");
 }
+
+fn open(file: &TempDb) -> Box<dyn TSDBInterface + Send> {
+    database_factory::<SQLiteTSDB>(DBBackend::SQLite(file.path.clone()))
+        .expect("Failed to open database!")
+}
+
+#[test]
+fn flows_with_different_sock_cookies() {
+    let file = TempDb::new("cookies.sqlite");
+    let db = open(&file);
+
+    let reused1 = db
+        .create_flow_with_cookie(&tuple(), 1)
+        .expect("Failed to write flow with cookie!");
+    let reused2 = db
+        .create_flow_with_cookie(&tuple(), 2)
+        .expect("Failed to write second flow with cookie!");
+    assert_ne!(reused1.id, reused2.id);
+    assert_eq!(reused2.sock_cookie, Some(2));
+
+    // Creating a known flow again continues it
+    let again = db
+        .create_flow_with_cookie(&tuple(), 1)
+        .expect("Failed to create existing flow!");
+    assert_eq!(again.id, reused1.id);
+    assert_eq!(db.list_flows().expect("Failed to get flows!").count(), 2);
+}
+
+#[test]
+fn transaction_from_second_connection() {
+    let file = TempDb::new("transaction.sqlite");
+    let db = open(&file);
+    let flow = db.create_flow(&tuple()).expect("Failed to write flow!");
+    let series = db
+        .create_time_series(&flow, "SEQ_NUM", DataValue::Int(0))
+        .expect("Failed to create time series!");
+
+    let writer = db
+        .new_connection()
+        .expect("Failed to open second connection!");
+    let batch = vec![
+        DataPoint {
+            timestamp: 200.0,
+            value: DataValue::Int(5),
+        },
+        DataPoint {
+            timestamp: 201.0,
+            value: DataValue::Int(6),
+        },
+    ];
+    writer
+        .begin_transaction()
+        .expect("Failed to begin transaction!");
+    writer
+        .insert_multiple_points(&series, &batch)
+        .expect("Failed to add points in transaction!");
+    writer
+        .commit_transaction()
+        .expect("Failed to commit transaction!");
+
+    let count = db
+        .get_data_points_count(&series)
+        .expect("Failed to get data point count!");
+    assert_eq!(count, 2);
+
+    // Rolled back points are not visible to other connections
+    writer
+        .begin_transaction()
+        .expect("Failed to begin transaction!");
+    writer
+        .insert_multiple_points(&series, &vec![DataPoint {
+            timestamp: 300.0,
+            value: DataValue::Int(7),
+        }])
+        .expect("Failed to add points in transaction!");
+    writer
+        .rollback_transaction()
+        .expect("Failed to roll back transaction!");
+
+    let count = db
+        .get_data_points_count(&series)
+        .expect("Failed to get data point count!");
+    assert_eq!(count, 2);
+}
+
+#[test]
+fn import_state_round_trip() {
+    let file = TempDb::new("import-state.sqlite");
+    let db = open(&file);
+
+    let mut state = ImportState {
+        path: "/tmp/test.tcp".to_string(),
+        file_id: 1234,
+        offset: 100,
+    };
+    db.set_import_state(&state)
+        .expect("Failed to set import state!");
+    state.offset = 200;
+    db.set_import_state(&state)
+        .expect("Failed to update import state!");
+    let stored = db
+        .get_import_state(&state.path)
+        .expect("Failed to get import state!");
+    assert_eq!(stored, Some(state));
+
+    let unknown = db
+        .get_import_state("/tmp/unknown.tcp")
+        .expect("Failed to get import state!");
+    assert!(unknown.is_none());
+}
+
+#[test]
+fn migrate_flows_without_sock_cookie() {
+    let file = TempDb::new("migrate.sqlite");
+    let path = file.path.clone();
+
+    // Schema of databases created before socket cookies
+    {
+        let conn = sqlite::open(&path).expect("Failed to create database!");
+        conn.execute(
+            "CREATE TABLE flows (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                src TEXT NOT NULL,
+                dst TEXT NOT NULL,
+                sport INTEGER NOT NULL,
+                dport INTEGER NOT NULL,
+                l4proto INTEGER NOT NULL,
+                UNIQUE (src, dst, sport, dport, l4proto)
+            );
+            CREATE TABLE time_series (
+                time_series_id INTEGER PRIMARY KEY AUTOINCREMENT,
+                flow_id INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                type INTEGER NOT NULL,
+                UNIQUE (flow_id,name),
+                FOREIGN KEY (flow_id) REFERENCES flows(id)
+            );
+            INSERT INTO flows (id, src, dst, sport, dport, l4proto) VALUES (7, '10.0.0.1', '10.0.0.2', 100, 200, 6);
+            INSERT INTO time_series (flow_id, name, type) VALUES (7, 'SEQ_NUM', 0);",
+        )
+        .expect("Failed to create old schema!");
+    }
+
+    let db = database_factory::<SQLiteTSDB>(DBBackend::SQLite(path.clone()))
+        .expect("Failed to migrate database!");
+
+    let flow = db
+        .get_flow_by_id(7)
+        .expect("Failed to read flow!")
+        .expect("Flow was not migrated!");
+    assert_eq!(flow.sock_cookie, None);
+    let series: Vec<String> = db
+        .list_time_series(&flow)
+        .expect("Failed to list time series!")
+        .map(|ts| ts.name)
+        .collect();
+    assert_eq!(series, vec!["SEQ_NUM".to_string()]);
+
+    // Tuple of the old flow can be reused by another connection
+    let reused = db
+        .create_flow_with_cookie(&flow.tuple, 42)
+        .expect("Failed to create flow with cookie!");
+    assert!(reused.id.unwrap() > 7);
+
+    drop(db);
+    // Migration runs only once
+    database_factory::<SQLiteTSDB>(DBBackend::SQLite(path.clone())).expect("Failed to reopen database!");
+}